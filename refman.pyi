@@ -16,18 +16,80 @@ Classes:
     RefDataset: Represents a reference dataset containing genomic data files
     RegistryOptions: Configuration options for initializing a reference dataset registry
     RefmanProject: Represents a reference registry project
+    RefmanWarning: A non-fatal problem collected during an operation
+    RefmanBatchError: Raised when some items of a bulk operation failed
 
 Functions:
     init: Initialize a new RefMan project registry
+    registry_path: Show where the registry file lives
     register: Register a new reference dataset
+    register_batch: Register many reference datasets at once
+    register_manifest: Register every dataset listed in a TSV or CSV manifest
+    export_manifest: Write every registered dataset to a TSV or CSV manifest
+    resolve_source: Resolve a provider identifier into file URLs
+    register_accession: Register a dataset from an NCBI or Ensembl accession
     list_datasets: List registered reference datasets
+    labels: List the labels of registered reference datasets
     download: Download a registered reference dataset
+    download_all: Download every registered reference dataset
     remove: Remove a dataset from the registry
+    clear: Remove every dataset from the registry
+    rename: Relabel a registered dataset
+    update: Replace some of the files of a registered dataset
+    relocate: Move a dataset's downloaded files to another directory
+    diff_downloads: Compare two downloaded copies of a dataset
+    reconcile: Fold observed checksums and sizes back into the registry
+    merge: Fold the datasets of one registry into another
+    diff: Show what changed between two registries
+    stats: Count the registered datasets and their files
+    validate: Check a registry for consistency problems
+    verify: Check that every registered source is still reachable
+    download_size: Estimate how many bytes a download would fetch
+    build_indexes: Index a dataset's already-downloaded files
+    scan_dir: Reconstruct a registry from reference files already on disk
+    cache_info: Summarize the shared download cache
+    cache_clear: Evict entries from the shared download cache
+    compute_checksum: Hash a local file, e.g. to register its SHA-256
+    validate_label: Check that a label is safe to register
+    normalize_label: Turn an arbitrary name into a valid label
+    set_log_level: Show log events on stderr
+
+Configuration:
+    Defaults can be set once in a TOML file at `$REFMAN_CONFIG`, or otherwise at
+    `refman/config.toml` under `$XDG_CONFIG_HOME` (`~/.config` by default):
+
+        registry = "/data/refs/refman.toml"  # or: global_project = true
+        max_concurrent_downloads = 8
+        lock_timeout_secs = 300
+
+    The config registry is used whenever a call names neither a registry path nor
+    the global registry. Explicit arguments always take precedence over the config,
+    which takes precedence over the built-in defaults. A config file that can't be
+    parsed, or that has unknown keys, raises a `ValueError` rather than being
+    ignored.
+
+Concurrency:
+    Functions that change a registry hold an advisory lock on it, beside the
+    registry as `.refman.toml.lock`, from reading it to writing it back, so that
+    processes sharing a registry take turns rather than losing each other's
+    updates. A process that can't take the lock within `lock_timeout_secs` (60 by
+    default) raises a `ValueError` saying the registry is locked.
+
+Registry formats:
+    Registries are TOML by default. A registry path ending in `.yaml`, `.yml`, or
+    `.json`, e.g. `init(requested_path="refs/refman.yaml")`, is read and written in
+    that format instead, with the same fields.
 """
 
+import os
+from typing import BinaryIO, Callable, Iterator
+
+
 class RefDataset:
     """
-    A reference dataset containing genomic data files.
+    A reference dataset containing genomic data files. Datasets can be pickled, e.g.
+    to pass them between processes with `multiprocessing`, and are restored with
+    exactly the same files and metadata.
 
     Methods:
         try_new: Creates a new reference dataset.
@@ -38,6 +100,17 @@ class RefDataset:
         gff: Returns the associated GFF file URL.
         gtf: Returns the associated GTF file URL.
         bed: Returns the associated BED file URL.
+        twobit: Returns the associated UCSC `.2bit` file URL.
+        nib: Returns the associated UCSC `.nib` file URL.
+        vcf: Returns the associated VCF file URL.
+        vcf_index: Returns the associated `.tbi` or `.csi` VCF index URL.
+        extra: Returns the dataset's free-form key-value metadata.
+        tags: Returns the dataset's tags.
+        description: Returns the dataset's free-text description.
+        available_formats: Returns the names of the formats the dataset has a file for.
+        has_format: Checks whether the dataset has a file of a format.
+        __repr__: Shows the label and the populated file fields, e.g. `RefDataset(label='e_coli', fasta='...')`.
+        __eq__: Compares the label and every file, so equal datasets also hash the same and can be set members or dict keys.
     """
 
     @staticmethod
@@ -92,6 +165,99 @@ class RefDataset:
         """
         ...
 
+    @property
+    def twobit(self) -> str | None:
+        """
+        Return a UCSC `.2bit` file URL registered with a reference dataset, if available.
+        """
+        ...
+
+    @property
+    def nib(self) -> str | None:
+        """
+        Return a UCSC `.nib` file URL registered with a reference dataset, if available.
+        """
+        ...
+
+    @property
+    def vcf(self) -> str | None:
+        """
+        Return a VCF file URL registered with a reference dataset, if available.
+        """
+        ...
+
+    @property
+    def vcf_index(self) -> str | None:
+        """
+        Return a `.tbi` or `.csi` index URL for the VCF of a reference dataset, if
+        available.
+        """
+        ...
+
+    @property
+    def extra(self) -> dict[str, str]:
+        """
+        Return the free-form key-value metadata registered with a reference dataset,
+        e.g. its assembly level or a contact. Downloads never use this metadata.
+        """
+        ...
+
+    @property
+    def tags(self) -> list[str]:
+        """
+        Return the tags registered with a reference dataset, e.g. `["bacteria"]`.
+        """
+        ...
+
+    @property
+    def description(self) -> str | None:
+        """
+        Return the free-text description registered with a reference dataset, if any.
+        """
+        ...
+
+    def available_formats(self) -> list[str]:
+        """
+        Return the names of the formats the dataset has a file for, e.g.
+        `["fasta", "gtf"]`, including supplementary formats like `twobit`.
+        """
+        ...
+
+    def has_format(self, format: str) -> bool:
+        """
+        Check whether the dataset has a file of `format`, e.g. `"gff"`, ignoring case.
+
+        Raises:
+            ValueError: If `format` isn't a supported file kind.
+        """
+        ...
+
+class RefmanWarning:
+    """
+    A non-fatal problem encountered during an operation, returned to the caller
+    rather than only printed. Operations accepting `strict=True` raise a
+    `ValueError` for the first warning instead.
+
+    Attributes:
+        code: A short, stable, machine-readable identifier, e.g. `unavailable-source`.
+        message: A human-readable description of what happened.
+    """
+
+    code: str
+    message: str
+
+class RefmanBatchError(ValueError):
+    """
+    Raised when some items of a bulk operation failed, after the rest completed.
+
+    Attributes:
+        successes: The items that completed.
+        failures: An `(item, error message)` pair for each item that failed.
+    """
+
+    successes: list[str]
+    failures: list[tuple[str, str]]
+
 class RegistryOptions:
     """
     Configuration options for initializing a reference dataset registry, which includes resolving the file path to the `refman.toml` used to cache validated URLs on disk.
@@ -99,6 +265,7 @@ class RegistryOptions:
     Methods:
         new: Creates a new RegistryOptions instance.
         init_project: Initializes a new registry project.
+        registry_path: Returns the absolute path of the registry file.
         read_registry: Reads an existing registry and returns a RefmanProject.
         write_registry: Writes the current registry state to disk.
     """
@@ -112,12 +279,21 @@ class RegistryOptions:
     ) -> RegistryOptions:
         """
         Create a new instance of `RegistryOptions` while validating that the path to the `refman.toml` file exists under the hood.
+        Passing both `requested_path` and `global_project=True` raises a `ValueError`, since
+        the two disagree about where the registry lives.
         """
         ...
 
-    def init_project(self):
+    def init_project(self, force: bool = False):
         """
         Create an `refman.toml` based on the metadata in the registry options that does not yet contain any registered datasets.
+        Raises a `ValueError` if the registry already exists, unless `force` is set to replace it.
+        """
+        ...
+
+    def registry_path(self) -> str:
+        """
+        The absolute path of the registry file these options resolve to.
         """
         ...
 
@@ -131,22 +307,31 @@ class RegistryOptions:
     def write_registry(self, project: RefmanProject) -> None:
         """
         Use the registry options and an instance of a RefMan project to write information to the `refman.toml`.
+        The registry is written to a temporary file that then replaces it, so it is never left half-written.
         """
         ...
 
 class RefmanProject:
     """
-    Represents a reference registry project.
+    Represents a reference registry project. Projects can be pickled, e.g. to pass
+    them between processes, and are restored with the same datasets and metadata.
 
     Methods:
         new: Creates a new RefmanProject.
         datasets: Returns a list of registered reference datasets.
-        get_dataset: Retrieves a specific dataset by label.
+        get_dataset: Retrieves a specific dataset by label or alias.
+        get_dataset_optional: Retrieves a specific dataset by label or alias, or None if it isn't registered.
         get_dataset_urls: Returns a list of URLs for a given dataset.
-        is_registered: Checks if a dataset with the given label is registered.
+        is_registered: Checks if a dataset with the given label or alias is registered.
+        __len__: Returns the number of registered datasets, as in `len(project)`.
+        __contains__: Checks a label or alias with `is_registered`, as in `"e_coli" in project`.
+        __iter__: Iterates over the labels of registered datasets.
+        __repr__: Summarizes the title and number of datasets, e.g. `RefmanProject(title='Refs', datasets=3)`.
         register: Registers a new dataset and returns an updated project.
         read_registry: Reads an existing registry and returns a RefmanProject.
         write_registry: Writes the current registry state to disk.
+        from_str: Parses a registry held in memory rather than on disk.
+        to_str: Serializes the registry to a string rather than to disk.
     """
     @staticmethod
     def new(
@@ -167,12 +352,27 @@ class RefmanProject:
         """
         ...
 
+    def __len__(self) -> int: ...
+    def __contains__(self, label: str) -> bool: ...
+    def __iter__(self) -> Iterator[str]: ...
     def get_datasets(self) -> list[RefDataset]:
         """
         Return a list of registered reference datasets.
         """
         ...
 
+    def get_dataset_optional(self, label: str) -> RefDataset | None:
+        """
+        Look up a registered dataset by label or alias in a single call.
+
+        Args:
+            label: Label or alias of the dataset.
+
+        Returns:
+            The dataset, or `None` if nothing is registered under `label`.
+        """
+        ...
+
     def get_dataset_urls(self) -> list[str]:
         """
         Return a list of URLs associated with a reference dataset.
@@ -185,24 +385,60 @@ class RefmanProject:
     def register(
         self,
         label: str,
-        fasta: str | None = None,
-        genbank: str | None = None,
-        gfa: str | None = None,
-        gff: str | None = None,
-        gtf: str | None = None,
-        bed: str | None = None,
+        fasta: str | list[str] | None = None,
+        genbank: str | list[str] | None = None,
+        gfa: str | list[str] | None = None,
+        gff: str | list[str] | None = None,
+        gtf: str | list[str] | None = None,
+        bed: str | list[str] | None = None,
+        force: bool = False,
+        aliases: list[str] | None = None,
+        extra: dict[str, str] | None = None,
+        sha256: dict[str, str] | None = None,
+        validate_urls: bool = True,
+        tags: list[str] | None = None,
+        description: str | None = None,
+        validate_content: bool = False,
+        twobit: str | list[str] | None = None,
+        nib: str | list[str] | None = None,
+        vcf: str | list[str] | None = None,
+        vcf_index: str | list[str] | None = None,
     ) -> "RefmanProject":
         """
-        Register a new reference dataset with a RefMan project.
+        Register a new reference dataset with a RefMan project. Registering a dataset
+        identical to the one already registered under its label returns the project
+        unchanged.
 
         Args:
             label: Identifier for the dataset.
-            fasta: Path to the FASTA file.
+            fasta: Path to the FASTA file, or a list of paths whose first is
+                registered and whose rest are mirrors, as for the top-level
+                `register`. Every file argument accepts a list the same way.
             genbank: Path to the GenBank file.
             gfa: Path to the GFA file.
             gff: Path to the GFF file.
             gtf: Path to the GTF file.
             bed: Path to the BED file.
+            force: Whether to re-register the dataset even if it is unchanged.
+            aliases: Secondary labels the dataset can also be looked up by, replacing
+                any it already had. Aliases must not collide with any other label or
+                alias in the project.
+            extra: Free-form key-value metadata to attach to the dataset, replacing any
+                it already had.
+            sha256: Optional expected SHA-256 digests by file kind, e.g.
+                `{"fasta": "9f86d0..."}`, that downloads are verified against.
+            validate_urls: Whether to check that every source can be fetched before
+                registering the dataset. See the top-level `register`.
+            tags: Categories to file the dataset under, replacing any it already had.
+            description: Free text describing the dataset, replacing any it already
+                had. An empty string removes it.
+            validate_content: Whether to check that local files look like their
+                format. See the top-level `register`.
+            twobit: Path to a UCSC `.2bit` file, or `""` to remove it.
+            nib: Path to a UCSC `.nib` file, or `""` to remove it.
+            vcf: Path to a VCF file, or `""` to remove it.
+            vcf_index: Path to a `.tbi` or `.csi` index of the VCF, or `""` to
+                remove it.
 
         Returns:
             An updated RefmanProject instance.
@@ -218,6 +454,8 @@ class RefmanProject:
     ) -> "RefmanProject":
         """
         Use individually specified project options to read an existing registry from disk into memory.
+        A title or description only applies when initializing a registry, so passing either here
+        raises a `ValueError`, as does passing both `requested_path` and `global_dataset=True`.
 
         Args:
             global_dataset: Whether to use a global registry.
@@ -238,7 +476,9 @@ class RefmanProject:
         requested_path: str | None = None,
     ) -> None:
         """
-        Write a RefmanProject to disk using individually specified project options.
+        Write a RefmanProject to disk using individually specified project options. The
+        registry is written to a temporary file that then replaces it, so it is never left
+        half-written.
 
         Args:
             project: The RefmanProject instance to write.
@@ -248,11 +488,42 @@ class RefmanProject:
             requested_path: Optional path to the registry file.
         """
         ...
+    @staticmethod
+    def from_str(text: str, format: str = "toml") -> "RefmanProject":
+        """
+        Parse a registry from a string, e.g. one passed around in memory, as
+        `read_registry` would parse it from disk but without touching the
+        filesystem. Dataset metadata lives in a sidecar file next to a registry
+        rather than in the registry itself, so the project starts without any.
+
+        Args:
+            text: The contents of a registry.
+            format: The format of `text`, one of "toml", "json", or "yaml".
+
+        Returns:
+            A RefmanProject instance holding the parsed registry.
+        """
+        ...
+    def to_str(self, format: str = "toml") -> str:
+        """
+        Serialize the registry to a string in the same form `write_registry`
+        writes to disk, so that `RefmanProject.from_str` can restore it. Dataset
+        metadata isn't part of the registry and so isn't included.
+
+        Args:
+            format: The format to serialize to, one of "toml", "json", or "yaml".
+
+        Returns:
+            The serialized registry.
+        """
+        ...
 
 def init(
     title: str | None = None,
     description: str | None = None,
+    requested_path: str | None = None,
     global_project: bool = False,
+    force: bool = False,
 ) -> None:
     """
     Initialize a new RefMan project with metadata and an empty registry of datasets.
@@ -262,29 +533,73 @@ def init(
         description: Optional description.
         requested_path: Optional path for the registry.
         global_project: Whether to initialize a global registry.
+        force: Replace a registry that already exists at the path, discarding its
+            datasets and their metadata.
+
+    Raises:
+        ValueError: If a registry already exists at the path and `force` isn't set.
+    """
+    ...
+
+def registry_path(
+    registry: str | None = None,
+    global_project: bool = False,
+) -> str:
+    """
+    Resolve where the registry lives, e.g. to back it up or put it under version
+    control, without reading, creating, or writing anything. The registry from the
+    user config is used when neither argument is given, just as for every other
+    function.
+
+    Args:
+        registry: Optional registry path or directory.
+        global_project: Whether to use the global registry.
+
+    Returns:
+        The absolute path of the registry file, whether or not it exists yet.
     """
     ...
 
 def register(
     label: str,
-    fasta: str | None = None,
-    genbank: str | None = None,
-    gfa: str | None = None,
-    gff: str | None = None,
-    gtf: str | None = None,
-    bed: str | None = None,
+    fasta: str | list[str] | None = None,
+    genbank: str | list[str] | None = None,
+    gfa: str | list[str] | None = None,
+    gff: str | list[str] | None = None,
+    gtf: str | list[str] | None = None,
+    bed: str | list[str] | None = None,
     registry: str | None = None,
     global_project: bool = False,
+    force: bool = False,
+    aliases: list[str] | None = None,
+    extra: dict[str, str] | None = None,
+    sha256: dict[str, str] | None = None,
+    validate_urls: bool = True,
+    tags: list[str] | None = None,
+    description: str | None = None,
+    validate_content: bool = False,
+    twobit: str | list[str] | None = None,
+    nib: str | list[str] | None = None,
+    vcf: str | list[str] | None = None,
+    vcf_index: str | list[str] | None = None,
 ) -> None:
     """
     Register a new reference dataset with a RefMan project. This function is different
     from class-based register methods in this module in that it doesn't register the
     dataset to an in-memory instance of a project, and instead caches everything on
-    disk.
+    disk. Re-registering a dataset identical to the one already on disk leaves the
+    registry file untouched. A dataset needs at least one file, so registering a
+    label on its own raises a `ValueError`, as does a label `validate_label`
+    rejects.
 
     Args:
         label: Identifier for the dataset.
-        fasta: Path to the FASTA file.
+        fasta: Path to the FASTA file, or a list of paths whose first is registered
+            and whose rest are mirrors. `download` falls back on each mirror in turn
+            when the registered source fails, and only fails, listing every source
+            it tried, once they all have. Mirrors are stored in `refman.meta.toml`
+            and aren't checked by `validate_urls`. Every file argument accepts a
+            list the same way.
         genbank: Path to the GenBank file.
         gfa: Path to the GFA file.
         gff: Path to the GFF file.
@@ -292,6 +607,212 @@ def register(
         bed: Path to the BED file.
         registry: Optional registry path.
         global_project: Whether to register in a global registry.
+        force: Whether to rewrite the registry even if the dataset is unchanged.
+        aliases: Secondary labels the dataset can also be looked up by, replacing any
+            it already had. Aliases are stored in a `refman.meta.toml` file next to the
+            registry and must not collide with any other label or alias.
+        extra: Free-form key-value metadata to attach to the dataset, replacing any it
+            already had. Like aliases, it is stored in `refman.meta.toml`.
+        sha256: Optional expected SHA-256 digests by file kind, e.g.
+            `{"fasta": "9f86d0..."}`. `download` rejects any file that doesn't match
+            its digest with a `ValueError`. Digests are stored in `refman.meta.toml`
+            and dropped when the dataset is re-registered with different sources.
+        validate_urls: Whether to check that every source can be fetched before
+            registering the dataset, with a HEAD request for HTTP(S) sources, a `SIZE`
+            command for `ftp://` sources, an `aws s3api head-object` call for `s3://`
+            sources, and an existence check for local ones, raising a `ValueError`
+            naming the first unreachable file. Remote sources aren't checked in offline
+            mode, i.e. with `REFMAN_OFFLINE=1`. Local sources, i.e. absolute paths or
+            `file://` URLs, must exist even when this is `False`. Sources may refer to
+            environment variables as `${VAR}` or `$VAR`, which are expanded when they
+            are checked and downloaded; this also requires every such variable to be
+            set, whereas with `False`, sources whose variables are unset are
+            registered unchecked.
+        tags: Categories to file the dataset under, e.g. `["bacteria", "host"]`,
+            replacing any it already had. Tags are stored in `refman.meta.toml` and
+            can be used to filter `list_datasets`.
+        description: Free text describing the dataset, e.g. "GRCh38 primary assembly,
+            Ensembl release 110", replacing any it already had. It is stored in
+            `refman.meta.toml`, shown by `list_datasets`, and never affects downloads.
+            An empty string removes it.
+        validate_content: Whether to check that each local file looks like the format
+            of its field, e.g. that a `fasta` file starts with a `>` header followed
+            by sequence, or that a `gff` file has a `##gff-version 3` header, raising
+            a `ValueError` naming the field and the problem otherwise. Only the first
+            few KB of each file are read, through gzip compression if present.
+            Remote sources are not checked.
+        twobit: Path to a UCSC `.2bit` file. `refman.toml` has no field for it, so
+            it is stored in `refman.meta.toml`, but it is checked by
+            `validate_urls`, accepts a `sha256` digest, and is downloaded with the
+            rest of the dataset like any other file. `""` removes it.
+        nib: Path to a UCSC `.nib` file, stored and handled like `twobit`.
+        vcf: Path to a VCF file, stored and handled like `twobit`. With
+            `validate_content`, a local VCF must start with `##fileformat=VCF`.
+        vcf_index: Path to a `.tbi` or `.csi` index of the VCF, stored and handled
+            like `twobit`. It is downloaded into the same directory as the VCF, so
+            tools find it next to it.
+    """
+    ...
+
+def register_batch(
+    datasets: list[dict[str, str]],
+    registry: str | None = None,
+    global_project: bool = False,
+    skip_unavailable: bool = False,
+    flush_every: int | None = None,
+    strict: bool = False,
+    fail_fast: bool = False,
+    offline: bool = False,
+) -> dict[str, list]:
+    """
+    Register many reference datasets with a RefMan project in one registry write.
+    Every entry that can be registered is, and the registry is written with them,
+    before a `RefmanBatchError` is raised for any entries that failed.
+
+    Args:
+        datasets: One dictionary per dataset, with a `label` key and a key for each
+            file kind to register, e.g. `{"label": "e_coli", "fasta": "https://..."}`.
+        registry: Optional registry path.
+        global_project: Whether to register in a global registry.
+        skip_unavailable: Whether to check that every source exists before
+            registering, with HEAD requests for remote sources and existence checks
+            for local ones, skipping datasets with an unavailable source rather than
+            registering them.
+        flush_every: Optionally persist the in-progress registry, atomically, after
+            every this many successful registrations, so that a crash keeps partial
+            progress. Datasets already registered with identical sources are skipped,
+            so rerunning the same batch resumes where it left off.
+        strict: Whether to raise on the first warning instead of collecting it.
+        fail_fast: Whether to stop at the first entry that fails to register rather
+            than attempting the rest.
+        offline: Whether to forbid network access, in which case checking a remote
+            source with `skip_unavailable` fails that entry instead of sending a HEAD
+            request. Also enabled by setting `REFMAN_OFFLINE=1`.
+
+    Returns:
+        A dictionary with the labels in `registered`, a `(label, source)` pair in
+        `skipped` for each dataset skipped because of an unavailable source, and the
+        `warnings` collected along the way as `RefmanWarning`s.
+
+    Raises:
+        RefmanBatchError: If any entry failed to register, including one whose
+            label is already an alias of another dataset or whose sources couldn't
+            be checked. Digests and mirrors stored for a label whose sources
+            changed are dropped either way.
+    """
+    ...
+
+def register_manifest(
+    path: str | os.PathLike[str],
+    registry: str | None = None,
+    global_project: bool = False,
+) -> list[str]:
+    """
+    Register every dataset listed in a manifest file in one registry write. The
+    manifest has a header row naming a `label` column and any of the `fasta`,
+    `genbank`, `gfa`, `gff`, `gtf`, `bed`, `twobit`, `nib`, `vcf`, and `vcf_index`
+    columns, in any order, followed by one
+    row per dataset. Files ending in `.csv` are comma-separated, and anything else
+    is read as tab-separated. Blank cells mean the dataset has no file of that kind,
+    and blank lines and lines starting with `#` are skipped. Any `<kind>_sha256`
+    columns, as written by `export_manifest(checksums=True)`, are stored as the
+    digests expected of those files. The `twobit`, `nib`, `vcf`, and `vcf_index`
+    files are only changed where their column is present, so a manifest without
+    them leaves a dataset's existing ones alone.
+
+    Args:
+        path: Path to the manifest.
+        registry: Optional registry path.
+        global_project: Whether to register in a global registry.
+
+    Returns:
+        The labels of the registered datasets, in manifest order.
+
+    Raises:
+        ValueError: If the manifest is malformed, lists a label more than once, or
+            holds a checksum that isn't a SHA-256 digest, naming the offending row, or if any dataset fails to register. Nothing
+            is registered in that case.
+    """
+    ...
+
+def export_manifest(
+    path: str | os.PathLike[str],
+    checksums: bool = False,
+    registry: str | None = None,
+    global_project: bool = False,
+) -> None:
+    """
+    Write every registered dataset to a manifest that `register_manifest` can import
+    again, e.g. to edit references in a spreadsheet. The file has a header row with
+    the `label`, `fasta`, `genbank`, `gfa`, `gff`, `gtf`, `bed`, `twobit`, `nib`,
+    `vcf`, and `vcf_index` columns, even
+    when the registry is empty, and one row per dataset with blank cells for missing
+    files. Paths ending in `.csv` are written comma-separated, and anything else
+    tab-separated.
+
+    Args:
+        path: Where to write the manifest, replacing any existing file.
+        checksums: Whether to add a `<kind>_sha256` column per file kind after the
+            file columns, holding the stored SHA-256 digest of each file, or blank
+            where none is known, so the manifest doubles as a checksum reference.
+        registry: Optional registry path.
+        global_project: Whether to export a global registry.
+
+    Raises:
+        ValueError: If a label or source contains the delimiter or a line break,
+            which the manifest couldn't represent.
+    """
+    ...
+
+def resolve_source(source: str) -> dict[str, str]:
+    """
+    Resolve a provider identifier of the form `<scheme>:<id>` into the file URLs a
+    dataset would be registered with, without registering anything. Supported schemes
+    are `ncbi` for nucleotide accessions, e.g. `ncbi:NC_045512.2`, and for assemblies
+    with their assembly name, e.g. `ncbi:GCF_000005845.2_ASM584v2`, `ensembl` for
+    Ensembl genomes, e.g. `ensembl:Homo_sapiens.GRCh38.112`, and `ucsc` for UCSC
+    assemblies, e.g. `ucsc:hg38`.
+
+    Args:
+        source: The identifier to resolve.
+
+    Returns:
+        A dictionary with a suggested `label` and one URL per resolved file kind, in the
+        same shape `register_batch` accepts.
+    """
+    ...
+
+def register_accession(
+    label: str,
+    accession: str,
+    source: str = "ncbi",
+    registry: str | None = None,
+    global_project: bool = False,
+    validate_urls: bool = True,
+) -> None:
+    """
+    Register a dataset with the standard FASTA, GFF, and GTF files a provider
+    publishes for an accession, as `resolve_source` finds them, instead of
+    pasting each URL. NCBI assemblies are given with their assembly name, e.g.
+    `GCF_000005845.2_ASM584v2`, and nucleotide accessions like `NC_045512.2`
+    resolve to FASTA and GenBank records instead, which are downloaded as
+    `NC_045512.2.fasta` and `NC_045512.2.gbk` since their URLs don't end in a
+    usable name. `rename` in `download` still takes precedence. Ensembl genomes are given as
+    species, assembly, and release, e.g. `Homo_sapiens.GRCh38.112`.
+
+    Args:
+        label: Label to register the dataset under.
+        accession: The accession to register, in the form `source` uses.
+        source: `"ncbi"` or `"ensembl"`.
+        registry: Optional registry path.
+        global_project: Whether to use a global registry.
+        validate_urls: Check that each resolved file is reachable before
+            registering, as with `register`. Not every assembly publishes every
+            file, so this is best left on.
+
+    Raises:
+        ValueError: If `source` is unknown, or the accession isn't in the form
+            `source` uses, before any request is made.
     """
     ...
 
@@ -299,7 +820,11 @@ def list_datasets(
     label: str | None = None,
     registry: str | None = None,
     global_project: bool = False,
-) -> None:
+    format: str = "text",
+    return_value: bool = False,
+    tag: str | None = None,
+    verbosity: str = "normal",
+) -> list[RefDataset] | None:
     """
     List registered reference datasets.
 
@@ -307,23 +832,292 @@ def list_datasets(
         label: Optional label to filter the list.
         registry: Optional registry path.
         global_project: Whether to list datasets from a global registry.
+        format: `"text"` for a human-readable table, or `"json"` to print a JSON
+            document that scripts can parse, shaped as follows. Every file field is
+            present, holding `null` when the dataset has no file of that kind, and
+            `schema_version` only changes if fields are renamed or removed.
+
+                {
+                  "schema_version": 1,
+                  "title": str | null,
+                  "description": str | null,
+                  "datasets": [
+                    {
+                      "label": str,
+                      "description": str | null,
+                      "aliases": [str],
+                      "tags": [str],
+                      "fasta": str | null,
+                      "genbank": str | null,
+                      "gfa": str | null,
+                      "gff": str | null,
+                      "gtf": str | null,
+                      "bed": str | null,
+                      "twobit": str | null,
+                      "nib": str | null,
+                      "vcf": str | null,
+                      "vcf_index": str | null,
+                      "extra": {str: str}
+                    }
+                  ]
+                }
+
+        return_value: Whether to return the matching datasets instead of printing
+            them.
+        tag: Optional tag to restrict the list to, matched ignoring case.
+        verbosity: `"quiet"` to print nothing, e.g. when only `return_value` is
+            wanted, `"normal"`, or `"verbose"` to also print the size recorded for
+            each file by an earlier download.
+
+    Returns:
+        The datasets matching `label` and `tag`, or every dataset without either, if
+        `return_value` is `True`, and `None` otherwise.
+    """
+    ...
+
+def labels(
+    registry: str | None = None,
+    global_project: bool = False,
+) -> list[str]:
+    """
+    List the label of every registered dataset, e.g. to offer them as choices,
+    without building a full dataset object for each.
+
+    Args:
+        registry: Optional registry path.
+        global_project: Whether to use a global registry.
+
+    Returns:
+        The labels, in the order the registry lists them. Aliases aren't included.
     """
     ...
 
 def download(
     label: str | None = None,
-    dest: str | None = None,
+    dest: str | os.PathLike | Callable[[str], BinaryIO] | None = None,
     registry: str | None = None,
     global_project: bool = False,
-) -> None:
+    sentinel: bool = False,
+    min_sizes: dict[str, int] | None = None,
+    max_sizes: dict[str, int] | None = None,
+    mode: int | None = None,
+    group: str | None = None,
+    create_dest: bool = True,
+    provenance: bool = False,
+    auto_verify: bool = False,
+    require_checksum: bool = False,
+    offline: bool = False,
+    resume: bool = True,
+    max_concurrent_downloads: int | None = None,
+    progress: Callable[[int, int | None], object] | None = None,
+    decompress: bool = False,
+    link_local: bool = False,
+    force: bool = False,
+    max_retries: int = 3,
+    retry_base_delay_ms: int = 500,
+    timeout: int | None = None,
+    headers: dict[str, str] | None = None,
+    dry_run: bool = False,
+    fetch_indexes: bool = False,
+    build_index: bool = False,
+    skip_space_check: bool = False,
+    verbosity: str = "normal",
+    return_paths: bool = False,
+    cache_dir: str | None = None,
+    rename: dict[str, str] | None = None,
+    layout: str = "flat",
+    on_complete: Callable[[list[str]], object] | None = None,
+) -> list[tuple[str, str]] | dict[str, str] | None:
+    """
+    Download a reference dataset registered in `refman.toml`. HTTP(S) sources
+    and `ftp://` sources are fetched directly, the latter with an anonymous login
+    unless the URL carries credentials, while `rsync://` sources are fetched by
+    shelling out to `rsync` and `s3://bucket/key` sources by shelling out to the AWS
+    CLI (`aws s3 cp`), which must be available on PATH. S3 objects are read with whatever AWS credentials are
+    configured in the environment, e.g. `AWS_PROFILE` or an instance role.
+    Environment variables in sources, e.g. `${REFS_MIRROR}/hg38.fa`, are expanded
+    before fetching, and a source referring to an unset variable raises a
+    `ValueError` naming it. A file whose transfer fails, fails its checks, or is
+    cancelled with Ctrl+C is removed rather than left truncated under its final
+    name, while the files that completed before it are kept.
+
+    Args:
+        label: Label or alias of the dataset to download.
+        dest: Destination directory (defaults to the current directory if not specified),
+            or a writer factory: a callable taking a file name and returning a writable
+            binary file object, e.g. `lambda name: fs.open(f"s3://refs/{name}", "wb")`
+            for an `fsspec` file system. Files bound for a writer factory are staged in
+            a temporary directory that is removed afterward, and can't be combined with
+            `sentinel`, `provenance`, `build_index`, `mode`, or `group`.
+        registry: Optional registry path.
+        global_project: Whether to use a global registry.
+        sentinel: Whether to write a `.<label>.done` JSON file to `dest` recording a
+            timestamp and the files written, once all of the dataset's files have been
+            verified. Any stale sentinel is removed before the download starts.
+        min_sizes: Optional per-kind minimum sizes in bytes, e.g. `{"fasta": 1000}`.
+            Smaller downloads are rejected as likely truncated. Off by default.
+        max_sizes: Optional per-kind maximum sizes in bytes. Larger downloads are
+            rejected. Off by default.
+        mode: Optional permission bits, e.g. `0o644`, applied to each written file.
+        group: Optional group name or numeric ID to hand written files to. Both `mode`
+            and `group` are Unix-only and are skipped with a warning elsewhere.
+        create_dest: Whether to create `dest` and any missing parents if it doesn't
+            exist, honoring `mode`. When `False`, a missing `dest` raises a `ValueError`.
+        provenance: Whether to write a `<label>.provenance.json` file to `dest` once the
+            download is verified, recording each file's source URL, any redirects
+            followed, its SHA-256 checksum, size, and ETag, when it was downloaded, and
+            the refman version used.
+        auto_verify: Whether to verify each file against a `<file>.sha256` or `<file>.md5`
+            checksum published next to its source, raising a `ValueError` on a mismatch.
+            Sources without a published checksum are skipped with a warning.
+        require_checksum: Whether a missing published checksum should raise a `ValueError`
+            instead of being skipped. Implies `auto_verify`. Files with a known
+            SHA-256 digest, e.g. one given at registration, are always verified.
+        offline: Whether to forbid network access, for air-gapped environments. Local
+            sources are copied as usual, while any HTTP(S), FTP, `rsync://`, or `s3://`
+            source raises a `ValueError` before a connection is attempted. Also enabled
+            by setting `REFMAN_OFFLINE=1`.
+        resume: Whether to continue an HTTP(S) download interrupted by an earlier run.
+            Files are streamed to `<file>.part` until complete, and a leftover partial
+            file is continued with a `Range` request, or restarted if the server doesn't
+            support ranges. A file whose final size doesn't match the server's
+            `Content-Length` raises a `ValueError` and is kept for the next attempt.
+        max_concurrent_downloads: How many of the dataset's files to fetch at once,
+            largest first, defaulting to the config's `max_concurrent_downloads`, or
+            4. The first file to fail cancels the others and raises.
+        progress: Optional callable invoked as `progress(downloaded, total)` with the
+            bytes written so far across the dataset's files and their combined size,
+            or `None` when some file's size isn't known up front. It is called at most
+            every 100 ms or every MiB, plus once more on completion with `downloaded`
+            equal to `total`. An exception raised by it cancels the download.
+        decompress: Whether to decompress gzip and bgzip files once they are fetched and
+            verified, writing e.g. `genome.fa.gz` out as `genome.fa` and removing the
+            compressed copy. Files that aren't gzipped are left as they are, and a
+            corrupt archive raises a `ValueError`.
+        link_local: Whether to hard-link sources that are local paths or `file://`
+            URLs into `dest` rather than copying them, e.g. from a shared NFS mount.
+            Sources on a different filesystem than `dest`, or files that `mode` or
+            `group` apply to, are still copied.
+        force: Whether to fetch every file again even if an earlier download is still
+            in place. By default, the size each file is downloaded with is recorded in
+            `refman.meta.toml`, and a file already in `dest` with its recorded size is
+            kept as is, unless its source now reports a different size. Files written
+            out decompressed are always fetched again.
+        max_retries: How many more times to try an HTTP(S) file after a transient
+            failure, i.e. a dropped connection, a timeout, or an HTTP 429 or 5xx
+            response, before raising a `ValueError` naming the number of attempts.
+            Other failures, e.g. an HTTP 404, are raised immediately. Interrupted
+            transfers are continued on retry when `resume` is set.
+        retry_base_delay_ms: The delay before the first retry in milliseconds. Each
+            further retry waits twice as long, up to a minute, with random jitter.
+        timeout: Optional number of seconds each file may take to download, covering
+            connecting as well as the transfer itself. A stalled file raises a
+            `ValueError` once it runs out, after any retries. Each file, and each
+            retry, gets the full window. Unlimited by default.
+        headers: Optional extra HTTP headers to send with every request for the
+            dataset's HTTP(S) sources, e.g. `{"Authorization": "Bearer ..."}` for a
+            token-gated server. Headers are only sent to the host each source names,
+            not to other hosts it redirects to, and are never written to the registry
+            or shown in error messages. An HTTP 401 or 403 raises a `ValueError`
+            pointing at the credentials.
+        dry_run: Whether to only work out what would be downloaded and where, without
+            making any requests or writing anything, not even `dest` itself. For a
+            writer factory `dest`, targets are bare file names. Companion indexes
+            aren't included, since finding them takes requests.
+        fetch_indexes: Whether to also fetch well-known companion indexes that sit
+            next to the dataset's files on their server: `{fasta}.fai`, `{fasta}.gzi`
+            for a bgzipped FASTA, and `{vcf}.tbi`. Companions the server doesn't
+            have are skipped silently, as are those of files being decompressed,
+            since they index the compressed file. Companions aren't checked against
+            any digest or size limit.
+        build_index: Whether to build a `.fai` for the downloaded FASTA, and a `.gzi`
+            too if it is bgzipped, unless a current one is already in place, e.g.
+            from `fetch_indexes`. Plain FASTA is indexed natively; bgzipped FASTA
+            needs `samtools` on PATH. Indexing runs once the download is complete
+            and recorded, so a failure raises a `ValueError` but leaves the FASTA in
+            place.
+        skip_space_check: Whether to start downloading without first checking that
+            the files fit on the filesystem `dest` is on. By default, the sizes the
+            sources report, as for `download_size`, less any files already in
+            place, are compared against the free space there, and a `ValueError`
+            is raised before anything is written if they don't fit. Files whose
+            size isn't known up front aren't counted.
+        verbosity: `"quiet"` to print nothing but errors, e.g. no warning about a
+            missing published checksum, `"normal"`, or `"verbose"` to also print
+            each file's source, destination, and size to stderr as it lands.
+        return_paths: Whether to return where each file landed, e.g. to hand the
+            files to the next tool. Requires a local `dest`.
+        cache_dir: A content-addressed cache, e.g. one shared by every project on a
+            cluster, that each fetched file is moved into under its SHA-256 digest
+            and symlinked back into `dest` from. Files whose digest is known, from
+            `register(..., sha256=...)` or an earlier cached download, are linked
+            straight from the cache without being fetched again. Decompressed files
+            aren't cached, and `mode` and `group` can't be combined with it. See
+            `cache_info` and `cache_clear`.
+        rename: File names to write files under by file kind, instead of the name
+            their URL ends in, e.g. `{"fasta": "genome.fa"}`. Companion indexes
+            follow their file's new name. Names must be plain file names, and two
+            files ending up with the same name raises a `ValueError`. Helpers that
+            find downloaded files by name, like `relocate` or `diff_downloads`,
+            still expect the URL-derived names.
+        layout: `"flat"` to write the files straight into `dest`, or `"per_label"`
+            to write them into a subdirectory of it named after the dataset's
+            label, along with any sentinel or provenance record. A flat download
+            raises a `ValueError` before fetching anything if one of its files
+            would be written under the same name as a file of another registered
+            dataset. Has no effect when `dest` is a writer.
+        on_complete: A callable invoked with the paths of every written file,
+            companion indexes included, once the dataset has downloaded and been
+            recorded, e.g. to run `samtools faidx` or `bwa index` on it. An
+            exception it raises is re-raised as a `ValueError`, and the
+            downloaded files are kept. Requires a local `dest`.
+
+    Returns:
+        With `dry_run`, a `(url, target path)` pair for each file that would be
+        fetched. With `return_paths`, a dictionary from each file field to the
+        path it was written to, e.g. `{"fasta": "/refs/hg38.fa"}`, leaving out
+        companion indexes. `None` otherwise.
+    """
+    ...
+
+def download_all(
+    dest: str | os.PathLike | None = None,
+    registry: str | None = None,
+    global_project: bool = False,
+    verbosity: str = "normal",
+    layout: str = "per_label",
+    offline: bool = False,
+    fail_fast: bool = False,
+) -> list[str]:
     """
-    Download a reference dataset registered in `refman.toml`.
+    Download every reference dataset registered in `refman.toml`, by default each
+    into a subdirectory of `dest` named after its label. A dataset that fails to
+    download doesn't stop the others, and files already in place from an earlier
+    download are kept, as with `download`. Once every dataset has been attempted,
+    a `RefmanBatchError` is raised for any that failed.
 
     Args:
-        label: Identifier of the dataset to download.
         dest: Destination directory (defaults to the current directory if not specified).
         registry: Optional registry path.
         global_project: Whether to use a global registry.
+        verbosity: `"quiet"`, `"normal"`, or `"verbose"`, as for `download`.
+        layout: `"per_label"` to give each dataset its own subdirectory, or
+            `"flat"` to write every dataset's files straight into `dest`. A flat
+            download raises a `ValueError` before fetching anything if two files
+            would be written under the same name.
+        offline: Whether to forbid network access, so that only local sources are
+            fetched and every remote one fails its dataset, as for `download`. Also
+            enabled by setting `REFMAN_OFFLINE=1`.
+        fail_fast: Whether to stop at the first dataset that fails to download
+            rather than attempting the rest.
+
+    Returns:
+        The labels of the datasets downloaded, which is all of them.
+
+    Raises:
+        RefmanBatchError: If any dataset failed to download, with the labels that
+            did download in `successes`. Datasets after the first failure with
+            `fail_fast` appear in neither list.
     """
     ...
 
@@ -331,6 +1125,9 @@ def remove(
     label: str | None = None,
     registry: str | None = None,
     global_project: bool = False,
+    delete_files: bool = False,
+    dest: str | os.PathLike | None = None,
+    confirm: Callable[[str], bool] | None = None,
 ) -> None:
     """
     Remove a registered reference dataset from the project using its label.
@@ -339,5 +1136,481 @@ def remove(
         label: Identifier of the dataset to remove.
         registry: Optional registry path.
         global_project: Whether to operate on a global registry.
+        delete_files: Also delete the files a download of the dataset wrote to
+            `dest`: each file, its decompressed copy and indexes, and the
+            dataset's sentinel and provenance record. Nothing else in `dest` is
+            touched, and files that are already gone are skipped.
+        dest: Directory the dataset was downloaded to (defaults to the current
+            directory if not specified). Only valid with `delete_files`.
+        confirm: Optional callable asked with the dataset's label before anything
+            is removed or deleted, e.g. `lambda label: input(f"Remove {label}? ") == "y"`.
+            Returning a falsy value leaves the registry and files untouched. An
+            exception it raises propagates, also leaving everything untouched.
+    """
+    ...
+
+def clear(
+    registry: str | None = None,
+    global_project: bool = False,
+) -> None:
+    """
+    Remove every registered dataset along with its aliases, tags, and other
+    metadata, keeping the registry's title and description, e.g. to reset a test
+    registry without running `init` again. Downloaded files are left alone.
+
+    Args:
+        registry: Optional registry path.
+        global_project: Whether to operate on a global registry.
+    """
+    ...
+
+def update(
+    label: str,
+    fasta: str | None = None,
+    genbank: str | None = None,
+    gfa: str | None = None,
+    gff: str | None = None,
+    gtf: str | None = None,
+    bed: str | None = None,
+    registry: str | None = None,
+    global_project: bool = False,
+) -> None:
+    """
+    Replace some of the files of a registered dataset, leaving the others as they
+    are. Known checksums for replaced files are dropped.
+
+    Args:
+        label: Label or alias of the dataset to update.
+        fasta: New path or URL for the FASTA file, or `""` to remove it.
+        genbank: New path or URL for the GenBank file, or `""` to remove it.
+        gfa: New path or URL for the GFA file, or `""` to remove it.
+        gff: New path or URL for the GFF file, or `""` to remove it.
+        gtf: New path or URL for the GTF file, or `""` to remove it.
+        bed: New path or URL for the BED file, or `""` to remove it.
+        registry: Optional registry path.
+        global_project: Whether to operate on a global registry.
+
+    Raises:
+        ValueError: If no dataset is registered under `label`.
+    """
+    ...
+
+def rename(
+    old_label: str,
+    new_label: str,
+    registry: str | None = None,
+    global_project: bool = False,
+) -> None:
+    """
+    Register a dataset under a new label without re-entering its files. Its aliases
+    and other metadata move along with it.
+
+    Args:
+        old_label: Label or alias of the dataset to rename.
+        new_label: The label to register the dataset under instead, which must not
+            already be used by another dataset or alias.
+        registry: Optional registry path.
+        global_project: Whether to operate on a global registry.
+    """
+    ...
+
+def relocate(
+    label: str,
+    from_dir: str,
+    to_dir: str,
+    registry: str | None = None,
+    global_project: bool = False,
+    rename: dict[str, str] | None = None,
+    layout: str = "flat",
+) -> dict[str, list[str]]:
+    """
+    Move a dataset's already-downloaded files, including its supplementary files,
+    from one directory to another, along with its `.done` sentinel if one was
+    written. Files are renamed when possible and otherwise copied, with originals
+    only deleted once every copy has succeeded.
+
+    Args:
+        label: Label or alias of the dataset whose files should move.
+        from_dir: Directory the files were downloaded to.
+        to_dir: Directory to move the files to. Created if it doesn't exist.
+        registry: Optional registry path.
+        global_project: Whether to use a global registry.
+        rename: The `rename` the files were downloaded with, if any, so that they
+            are found under their custom names.
+        layout: The `layout` the files were downloaded with. With `"per_label"`,
+            the files move between the subdirectories of `from_dir` and `to_dir`
+            named after the dataset.
+
+    Returns:
+        A dictionary listing the `moved` file paths and the expected files that were
+        `missing` from `from_dir`.
+    """
+    ...
+
+def diff_downloads(
+    label: str,
+    dir_a: str,
+    dir_b: str,
+    registry: str | None = None,
+    global_project: bool = False,
+    rename: dict[str, str] | None = None,
+    layout: str = "flat",
+) -> dict[str, str]:
+    """
+    Compare a dataset's downloaded files, including its supplementary files, across
+    two directories, e.g. to check a mirror against a trusted source. Files are
+    compared by size and, when their sizes match, by streamed SHA-256 checksum.
+
+    Args:
+        label: Label or alias of the dataset to compare.
+        dir_a: The first download directory.
+        dir_b: The second download directory.
+        registry: Optional registry path.
+        global_project: Whether to use a global registry.
+        rename: The `rename` both copies were downloaded with, if any.
+        layout: The `layout` both copies were downloaded with, as for `relocate`.
+
+    Returns:
+        A dictionary mapping each file name to `"identical"`, `"differs"`,
+        `"missing_in_a"`, or `"missing_in_b"`.
+    """
+    ...
+
+def reconcile(
+    dest: str,
+    registry: str | None = None,
+    global_project: bool = False,
+) -> dict[str, list]:
+    """
+    Fold the checksums, sizes, and ETags recorded in the `<label>.provenance.json` files
+    under `dest` (see `download(..., provenance=True)`) back into the registry's
+    metadata, so that later integrity checks have data even for remote sources. Only
+    existing datasets are updated, and only for files downloaded from the source that
+    is still registered.
+
+    Args:
+        dest: The directory to search for provenance records, recursively.
+        registry: Optional registry path.
+        global_project: Whether to use a global registry.
+
+    Returns:
+        A dictionary with a `(label, kind)` pair in `updated` for each file whose stored
+        integrity information changed, and a `(record path, reason)` pair in `skipped`
+        for each provenance entry that was ignored.
+    """
+    ...
+
+def merge(
+    source_registry: str,
+    dest_registry: str,
+    strategy: str = "error",
+) -> dict[str, list[str]]:
+    """
+    Fold every dataset of one registry into another, along with its aliases, tags,
+    and other metadata. The destination keeps its title and description, and the
+    source registry is left untouched. A dataset added or replaced brings all of its
+    metadata, including digests, mirrors, and supplementary files, while one
+    registered identically in both gains whatever metadata only the source holds,
+    such as extra tags or digests. Its conflicting metadata, e.g. a different
+    `twobit` file, is taken from the source only with `"overwrite"`.
+
+    Args:
+        source_registry: Path to the registry to copy datasets from.
+        dest_registry: Path to the registry to merge them into.
+        strategy: What to do when a label holds different files in each registry:
+            `"error"` to raise a `ValueError` without changing anything, `"skip"` to
+            keep the destination's dataset, or `"overwrite"` to replace it with the
+            source's.
+
+    Returns:
+        A dictionary with the labels added or replaced in `merged`, and the labels
+        left out because of a conflict in `skipped`.
+    """
+    ...
+
+def diff(registry_a: str, registry_b: str) -> None:
+    """
+    Print a changelog of what it would take to turn one registry into another:
+    labels only in `registry_b` prefixed with `+`, labels only in `registry_a`
+    with `-`, and labels in both but holding different files with `~`, followed
+    by each changed file as `kind: before -> after`, counting the supplementary
+    files each registry's metadata holds. Neither registry is changed.
+
+    Args:
+        registry_a: Path to the registry to compare from, e.g. the shared one.
+        registry_b: Path to the registry to compare to, e.g. a teammate's copy.
+    """
+    ...
+
+def stats(
+    registry: str | None = None,
+    global_project: bool = False,
+    format: str | None = None,
+) -> dict[str, int | dict[str, int]]:
+    """
+    Tally the datasets in a registry without touching their files. Datasets with
+    no file fields at all are counted separately, since they are usually
+    mistakes.
+
+    Args:
+        registry: Optional registry path.
+        global_project: Whether to use a global registry.
+        format: Optionally also print the tally, as a short summary with `"text"` or
+            as a JSON object with the same keys as the returned dictionary with
+            `"json"`, e.g. for CI logs.
+
+    Returns:
+        A dictionary with the number of datasets in `datasets`, how many datasets
+        have each kind of file in `per_field`, e.g. `{"fasta": 3, "gff": 1, ...}`,
+        including supplementary kinds like `twobit`, the number of files registered
+        across every dataset in `total_files`, and the number of datasets without
+        any in `empty`.
+    """
+    ...
+
+def validate(
+    registry: str | None = None,
+    global_project: bool = False,
+) -> int:
+    """
+    Check a registry and its metadata for consistency problems and print each
+    one, or a note that there are none. Only the registry itself is inspected;
+    nothing is fetched, so unreachable sources are left to `verify`.
+
+    Errors are duplicate or invalid labels, datasets without any files, sources
+    that aren't well-formed URLs or paths ending in a file name, VCF indexes
+    without a VCF, and aliases that are also registered labels. Warnings are
+    sources naming environment variables that aren't set and annotations
+    without a sequence to annotate.
+
+    Args:
+        registry: Optional registry path.
+        global_project: Whether to use a global registry.
+
+    Returns:
+        The number of errors found, so that `0` means the registry is
+        consistent. Warnings are printed but not counted.
+    """
+    ...
+
+def verify(
+    registry: str | None = None,
+    global_project: bool = False,
+    max_concurrent_checks: int = 16,
+    format: str = "text",
+) -> list[tuple[str, str, str]]:
+    """
+    Check that every file of every registered dataset, including its supplementary
+    files, can still be fetched, without downloading anything, and print a table of each dataset's label, file field, and
+    status. HTTP(S) sources are checked with a HEAD request, `ftp://` sources with a
+    `SIZE` command, `s3://` sources with `aws s3api head-object`, and local sources
+    with an existence check, while `rsync://` sources are assumed to be reachable.
+    The registry is never modified. In offline mode, i.e. with `REFMAN_OFFLINE=1`,
+    every remote source is reported as unreachable.
+
+    Args:
+        registry: Optional registry path.
+        global_project: Whether to verify a global registry.
+        max_concurrent_checks: How many sources to check at once.
+        format: `"text"` to print the table, or `"json"` to print a JSON array with
+            a `label`, `field`, `status`, and `reason` for each source instead.
+
+    Returns:
+        A `(label, field, reason)` tuple for each unreachable source, which is empty
+        when every source is reachable, so scripts can fail on a non-empty result.
+    """
+    ...
+
+def download_size(
+    label: str | None = None,
+    registry: str | None = None,
+    global_project: bool = False,
+) -> dict[str, int | list[str]]:
+    """
+    Add up the sizes of the files a download would fetch, without downloading
+    anything, e.g. to check for disk space first. Sizes come from the same checks
+    `verify` makes: the `Content-Length` of a HEAD request for HTTP(S) sources, the
+    server-reported size for `ftp://` and `s3://` sources, and the file size for
+    local ones. Supplementary files, like `twobit`, are included.
+
+    Args:
+        label: Label or alias of the dataset to size, or `None` for every dataset.
+        registry: Optional registry path.
+        global_project: Whether to use a global registry.
+
+    Returns:
+        A dictionary with `total`, the sum of every size that could be learned, and
+        `unknown`, the sources whose size couldn't be, e.g. `rsync://` sources or
+        servers that send no `Content-Length`. `total` is a lower bound whenever
+        `unknown` isn't empty.
+    """
+    ...
+
+def build_indexes(
+    label: str,
+    dest: str | None = None,
+    kinds: list[str] | None = None,
+    registry: str | None = None,
+    global_project: bool = False,
+    csi: bool = False,
+) -> dict[str, str]:
+    """
+    Build indexes for a dataset's already-downloaded files without downloading them
+    again: a `.fai` for FASTA and a `.tbi` for bgzipped GFF, GTF, and BED files. Plain
+    FASTA is indexed natively, while bgzipped files require `samtools` and `tabix` to
+    be available on PATH. Files whose index is already present and newer than the
+    file itself are left alone.
+
+    Args:
+        label: Label or alias of the dataset to index.
+        dest: Optional directory holding the downloaded files. Defaults to the
+            current working directory.
+        kinds: Optional file kinds to restrict indexing to, e.g. `["fasta"]`.
+        registry: Optional registry path.
+        global_project: Whether to use a global registry.
+        csi: Whether to build `.csi` rather than `.tbi` indexes for bgzipped files.
+
+    Returns:
+        A dictionary mapping each indexable file to `"built"`, `"current"`, or
+        `"missing"` when the file isn't present in `dest`.
+    """
+    ...
+
+def scan_dir(
+    dir: str,
+    recursive: bool = True,
+    registry: str | None = None,
+    global_project: bool = False,
+    kind_map: dict[str, str] | None = None,
+    strict: bool = False,
+    max_depth: int | None = None,
+    include: list[str] | None = None,
+    exclude: list[str] | None = None,
+    follow_symlinks: bool = False,
+) -> dict[str, int | list]:
+    """
+    Reconstruct a registry from reference files that are already on disk and write
+    it, replacing any registry at the resolved location. Files are assigned a kind by
+    extension, e.g. `.fa`, `.gbk`, or `.gff3`, optionally followed by `.gz`. Files
+    directly in `dir` are grouped into datasets by file stem, while the files in each
+    subdirectory make up one dataset labeled by the subdirectory's relative path.
+
+    Args:
+        dir: Directory to scan.
+        recursive: Whether to scan subdirectories too.
+        registry: Optional registry path.
+        global_project: Whether to write a global registry.
+        kind_map: Optional extension-to-kind overrides that take precedence over the
+            built-in mapping, e.g. `{"gff": "gtf", "fna": "fasta"}`. Mapping to an
+            unknown kind raises a `ValueError`.
+        strict: Whether to raise on the first warning, before anything is written,
+            instead of collecting it.
+        max_depth: How many levels of subdirectories to descend into, where `0` only
+            scans `dir` itself. Unbounded by default.
+        include: Optional glob patterns; when given, only files whose name or path
+            relative to `dir` matches one of them are considered.
+        exclude: Optional glob patterns for files to ignore, e.g. `["*.tmp", "logs/*"]`.
+        follow_symlinks: Whether to descend into symlinked directories, which is off
+            by default to avoid cycles.
+
+    Returns:
+        A dictionary with the number of files `scanned` and `included` by the filters,
+        the reconstructed `datasets`, the `ambiguous` groupings
+        that were skipped because they held more than one file of a kind, the
+        `unrecognized` files whose extensions didn't map to a kind, and the same
+        problems as `warnings`.
+    """
+    ...
+
+def cache_info(cache_dir: str | None = None) -> dict[str, str | int]:
+    """
+    Summarize the content-addressed download cache.
+
+    Args:
+        cache_dir: Optional cache directory. Defaults to `$REFMAN_CACHE_DIR`, or
+            `refman` under the XDG cache home.
+
+    Returns:
+        A dictionary with the `cache_dir`, the number of `entries`, and their
+        `total_size` in bytes.
+    """
+    ...
+
+def cache_clear(
+    older_than_days: int | None = None,
+    cache_dir: str | None = None,
+) -> int:
+    """
+    Evict entries from the content-addressed download cache. Entries locked by an
+    in-progress download are skipped.
+
+    Args:
+        older_than_days: Only evict entries no download has stored or linked for
+            at least this many days. Evicts everything when not specified.
+        cache_dir: Optional cache directory, as in `cache_info`.
+
+    Returns:
+        The number of bytes freed.
+    """
+    ...
+
+def compute_checksum(path: str, algorithm: str = "sha256") -> str:
+    """
+    Compute the checksum of a local file, streaming it so that large genomes are
+    never held in memory. The SHA-256 digest can be passed straight to
+    `register(..., sha256={"fasta": digest})`.
+
+    Args:
+        path: Path to the file to hash.
+        algorithm: Either "sha256" or "md5".
+
+    Returns:
+        The lowercase hex digest of the file.
+
+    Raises:
+        ValueError: If the algorithm isn't supported or the file can't be read.
+    """
+    ...
+
+def validate_label(label: str) -> None:
+    """
+    Check that a label can be registered. Labels name the directories datasets
+    are downloaded into, so they may only contain ASCII letters, digits, `_`,
+    `-`, and `.`, and can't be empty or made only of dots. Every registration
+    and rename applies the same check.
+
+    Args:
+        label: The label to check.
+
+    Raises:
+        ValueError: If the label isn't valid, saying why.
+    """
+    ...
+
+def normalize_label(label: str) -> str:
+    """
+    Turn an arbitrary name into a label `validate_label` accepts by replacing
+    every character it doesn't allow with `_`, e.g. "E. coli K-12" becomes
+    "E._coli_K-12". `scan_dir` labels the datasets it finds this way.
+
+    Args:
+        label: The name to normalize.
+
+    Returns:
+        The normalized label.
+    """
+    ...
+
+def set_log_level(level: str) -> None:
+    """
+    Show log events from downloads, registry reads and writes, and registrations
+    on stderr. Nothing is logged until this is called.
+
+    Args:
+        level: One of "off", "error", "warn", "info", "debug", or "trace". URLs
+            are logged with any credentials and query strings removed.
+
+    Raises:
+        ValueError: If the level isn't recognized.
     """
     ...
@@ -0,0 +1,306 @@
+//! The cache submodule.
+//!
+//! Downloads can be shared across projects through a content-addressed cache,
+//! where each file is stored under its SHA-256 digest. This module owns the
+//! cache's on-disk layout, the per-key locks that keep writers and evictions
+//! from stepping on each other, and the inspection and eviction helpers exposed
+//! to Python. Locks are advisory locks on a file next to each entry, like the
+//! registry's, so a process that dies holding one releases it. Downloaded files are moved into the cache and linked back into
+//! their destination, so every project on a machine shares one copy of each.
+
+use std::{
+    env,
+    fs::{self, File, OpenOptions},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Context, Result};
+use fs2::FileExt;
+
+use crate::hashing;
+
+const LOCK_EXTENSION: &str = "lock";
+
+/// A content-addressed store of downloaded files rooted at a single directory.
+#[derive(Debug, Clone)]
+pub struct Cache {
+    root: PathBuf,
+}
+
+/// A summary of what the cache currently holds.
+#[derive(Debug)]
+pub struct CacheInfo {
+    pub cache_dir: PathBuf,
+    pub entries: usize,
+    pub total_size: u64,
+}
+
+/// An exclusive claim on a single cache key, released when dropped.
+#[derive(Debug)]
+pub struct CacheLock {
+    _file: File,
+}
+
+impl Cache {
+    /// Open the cache at `dir`, falling back to the default location, creating
+    /// the directory if it doesn't exist yet.
+    pub fn open(dir: Option<PathBuf>) -> Result<Self> {
+        let root = match dir {
+            Some(dir) => dir,
+            None => Self::default_dir()?,
+        };
+        fs::create_dir_all(&root)
+            .with_context(|| format!("Failed to create cache directory '{}'.", root.display()))?;
+        Ok(Self { root })
+    }
+
+    /// The default cache location: `$REFMAN_CACHE_DIR` if set, otherwise
+    /// `refman` under the XDG cache home.
+    pub fn default_dir() -> Result<PathBuf> {
+        if let Some(dir) = env::var_os("REFMAN_CACHE_DIR") {
+            return Ok(PathBuf::from(dir));
+        }
+        if let Some(dir) = env::var_os("XDG_CACHE_HOME") {
+            return Ok(PathBuf::from(dir).join("refman"));
+        }
+        let home = env::var_os("HOME")
+            .context("Unable to locate a home directory to place the refman cache in.")?;
+        Ok(PathBuf::from(home).join(".cache").join("refman"))
+    }
+
+    /// Where the file with the provided digest lives in the cache.
+    pub fn entry_path(&self, key: &str) -> PathBuf {
+        self.root.join(key)
+    }
+
+    /// Try to take the lock for `key`, returning `None` if another writer or
+    /// eviction currently holds it.
+    pub fn try_lock(&self, key: &str) -> Result<Option<CacheLock>> {
+        let path = self.root.join(format!("{key}.{LOCK_EXTENSION}"));
+        // the lock file is left in place when released, since removing it could
+        // let two processes lock different files under the same name
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .with_context(|| format!("Failed to open cache lock '{}'.", path.display()))?;
+        match file.try_lock_exclusive() {
+            Ok(()) => Ok(Some(CacheLock { _file: file })),
+            Err(err) if err.kind() == fs2::lock_contended_error().kind() => Ok(None),
+            Err(err) => Err(err).with_context(|| format!("Failed to lock '{}'.", path.display())),
+        }
+    }
+
+    /// Link the entry for `key` to `target`, replacing whatever is there, and
+    /// return whether the cache held that entry at all.
+    pub fn link_entry(&self, key: &str, target: &Path) -> Result<bool> {
+        let entry = self.entry_path(key);
+        if !entry.is_file() {
+            return Ok(false);
+        }
+        link(&entry, target)?;
+        touch(&entry);
+        Ok(true)
+    }
+
+    /// Move the file at `path` into the cache under its SHA-256 digest and
+    /// leave a link to the entry in its place. When the cache already holds the
+    /// same content, the file is dropped in favor of the existing entry. Returns
+    /// the digest, or `None` if another process holds the entry's lock, in which
+    /// case the file is left alone.
+    pub fn adopt(&self, path: &Path) -> Result<Option<String>> {
+        let key = hashing::sha256_file(path)
+            .with_context(|| format!("Failed to hash '{}' for the cache.", path.display()))?;
+        let Some(_lock) = self.try_lock(&key)? else {
+            return Ok(None);
+        };
+        let entry = self.entry_path(&key);
+        // renaming can't cross filesystems, in which case the file is copied in
+        // under a temporary name so that a partial entry is never visible
+        if !entry.is_file() && fs::rename(path, &entry).is_err() {
+            let partial = self.root.join(format!("{key}.partial"));
+            fs::copy(path, &partial)
+                .and_then(|_| fs::rename(&partial, &entry))
+                .with_context(|| format!("Failed to store '{}' in the cache.", path.display()))?;
+        }
+        link(&entry, path)?;
+        touch(&entry);
+        Ok(Some(key))
+    }
+
+    /// The keys and metadata of every complete entry in the cache.
+    fn entries(&self) -> Result<Vec<(String, fs::Metadata)>> {
+        let mut entries = Vec::new();
+        let listing = fs::read_dir(&self.root).with_context(|| {
+            format!("Failed to read cache directory '{}'.", self.root.display())
+        })?;
+        for entry in listing {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !metadata.is_file() || name.contains('.') {
+                // lock and partial files carry an extension; entries are bare digests
+                continue;
+            }
+            entries.push((name, metadata));
+        }
+        Ok(entries)
+    }
+
+    pub fn info(&self) -> Result<CacheInfo> {
+        let entries = self.entries()?;
+        Ok(CacheInfo {
+            cache_dir: self.root.clone(),
+            entries: entries.len(),
+            total_size: entries.iter().map(|(_, metadata)| metadata.len()).sum(),
+        })
+    }
+
+    /// Evict every entry, or only those that haven't been touched within
+    /// `older_than`, returning the number of bytes freed. Entries locked by an
+    /// in-flight download are left alone.
+    pub fn clear(&self, older_than: Option<Duration>) -> Result<u64> {
+        let now = SystemTime::now();
+        let mut freed = 0;
+        for (key, metadata) in self.entries()? {
+            if let Some(threshold) = older_than {
+                let age = now
+                    .duration_since(metadata.modified()?)
+                    .unwrap_or(Duration::ZERO);
+                if age < threshold {
+                    continue;
+                }
+            }
+            let Some(_lock) = self.try_lock(&key)? else {
+                continue;
+            };
+            let path = self.entry_path(&key);
+            fs::remove_file(&path)
+                .with_context(|| format!("Failed to evict cache entry '{}'.", path.display()))?;
+            freed += metadata.len();
+        }
+        Ok(freed)
+    }
+}
+
+/// Mark the cache `entry` as just used, so that `Cache::clear` with an age
+/// threshold only evicts entries nothing has linked in that long. An entry that
+/// can't be touched, e.g. in a cache owned by another user, is still usable.
+fn touch(entry: &Path) {
+    let touched = File::options()
+        .write(true)
+        .open(entry)
+        .and_then(|file| file.set_modified(SystemTime::now()));
+    if let Err(err) = touched {
+        tracing::warn!(entry = %entry.display(), error = %err, "failed to touch cache entry");
+    }
+}
+
+/// Replace `target` with a symbolic link to the cache `entry`.
+fn link(entry: &Path, target: &Path) -> Result<()> {
+    let entry = std::path::absolute(entry)?;
+    if fs::symlink_metadata(target).is_ok() {
+        fs::remove_file(target)?;
+    }
+    #[cfg(unix)]
+    let linked = std::os::unix::fs::symlink(&entry, target);
+    #[cfg(windows)]
+    let linked = std::os::windows::fs::symlink_file(&entry, target);
+    linked.with_context(|| {
+        format!(
+            "Failed to link '{}' to the cache entry '{}'.",
+            target.display(),
+            entry.display()
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clear_skips_locked_entries() {
+        let root = env::temp_dir().join("py-refman-cache-test");
+        let _ = fs::remove_dir_all(&root);
+        let cache = Cache::open(Some(root.clone())).unwrap();
+        fs::write(cache.entry_path("aaaa"), b"ACGT").unwrap();
+        fs::write(cache.entry_path("bbbb"), b"ACGTACGT").unwrap();
+
+        let lock = cache.try_lock("bbbb").unwrap();
+        assert!(lock.is_some());
+        assert_eq!(cache.info().unwrap().entries, 2);
+        assert_eq!(cache.clear(None).unwrap(), 4);
+        drop(lock);
+
+        assert_eq!(cache.info().unwrap().total_size, 8);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_adopt_dedups_content() {
+        let root = env::temp_dir().join("py-refman-cache-adopt");
+        let _ = fs::remove_dir_all(&root);
+        let cache = Cache::open(Some(root.join("cache"))).unwrap();
+        let (a, b) = (root.join("a.fa"), root.join("b.fa"));
+        fs::write(&a, ">chr1\nACGT\n").unwrap();
+        fs::write(&b, ">chr1\nACGT\n").unwrap();
+
+        let key = cache.adopt(&a).unwrap().unwrap();
+        assert_eq!(cache.adopt(&b).unwrap().as_deref(), Some(key.as_str()));
+        assert_eq!(cache.info().unwrap().entries, 1);
+        assert!(fs::symlink_metadata(&b).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_to_string(&b).unwrap(), ">chr1\nACGT\n");
+
+        let c = root.join("c.fa");
+        assert!(cache.link_entry(&key, &c).unwrap());
+        assert!(!cache.link_entry("0000", &c).unwrap());
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_stale_lock_is_reclaimed() {
+        let root = env::temp_dir().join("py-refman-cache-stale-lock");
+        let _ = fs::remove_dir_all(&root);
+        let cache = Cache::open(Some(root.clone())).unwrap();
+        // what a process killed while holding the lock leaves behind
+        fs::write(root.join(format!("aaaa.{LOCK_EXTENSION}")), b"").unwrap();
+
+        let lock = cache.try_lock("aaaa").unwrap();
+        assert!(lock.is_some());
+        assert!(cache.try_lock("aaaa").unwrap().is_none());
+        drop(lock);
+        assert!(cache.try_lock("aaaa").unwrap().is_some());
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_linking_keeps_entry() {
+        let root = env::temp_dir().join("py-refman-cache-touch");
+        let _ = fs::remove_dir_all(&root);
+        let cache = Cache::open(Some(root.join("cache"))).unwrap();
+        let entry = cache.entry_path("aaaa");
+        fs::write(&entry, b"ACGT").unwrap();
+        let age_entry = || {
+            let long_ago = SystemTime::now() - Duration::from_hours(10 * 24);
+            File::options()
+                .write(true)
+                .open(&entry)
+                .and_then(|file| file.set_modified(long_ago))
+                .unwrap();
+        };
+        let day = Duration::from_hours(24);
+
+        age_entry();
+        assert!(cache.link_entry("aaaa", &root.join("genome.fa")).unwrap());
+        assert_eq!(cache.clear(Some(day)).unwrap(), 0);
+        assert!(entry.is_file());
+
+        age_entry();
+        assert_eq!(cache.clear(Some(day)).unwrap(), 4);
+        assert!(!entry.exists());
+        let _ = fs::remove_dir_all(&root);
+    }
+}
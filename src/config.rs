@@ -0,0 +1,93 @@
+//! The config submodule.
+//!
+//! Defaults that would otherwise be passed on every call, like which registry
+//! to use, can be set once in a TOML file: `$REFMAN_CONFIG` if set, otherwise
+//! `refman/config.toml` under the XDG config home. Arguments passed explicitly
+//! always take precedence over the config, which in turn takes precedence over
+//! the built-in defaults.
+
+use std::{env, fs, path::PathBuf};
+
+use serde::Deserialize;
+
+use crate::errors::OptionsError;
+
+/// User-wide defaults, e.g.
+///
+/// ```toml
+/// registry = "/data/refs/refman.toml"
+/// max_concurrent_downloads = 8
+/// lock_timeout_secs = 300
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// The registry to use when neither a path nor the global registry is
+    /// requested.
+    pub registry: Option<String>,
+
+    /// Use the global registry when neither a path nor the global registry is
+    /// requested.
+    pub global_project: bool,
+
+    /// How many of a dataset's files `download` fetches at once unless told
+    /// otherwise.
+    pub max_concurrent_downloads: Option<usize>,
+
+    /// How many seconds to wait for another process to release a registry
+    /// before giving up.
+    pub lock_timeout_secs: Option<u64>,
+}
+
+impl Config {
+    /// Where the config file is looked for, if a home directory can be found.
+    pub fn path() -> Option<PathBuf> {
+        if let Some(path) = env::var_os("REFMAN_CONFIG") {
+            return Some(PathBuf::from(path));
+        }
+        let config_home = env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config_home.join("refman").join("config.toml"))
+    }
+
+    /// Read the config file, which is empty when it doesn't exist. A file that
+    /// exists but can't be parsed is an error rather than being ignored.
+    pub fn load() -> Result<Self, OptionsError> {
+        let Some(path) = Self::path().filter(|path| path.exists()) else {
+            return Ok(Self::default());
+        };
+        let invalid = |message: String| OptionsError::InvalidConfig {
+            path: path.clone(),
+            message,
+        };
+        let contents = fs::read_to_string(&path).map_err(|err| invalid(err.to_string()))?;
+        Self::parse(&contents).map_err(invalid)
+    }
+
+    fn parse(contents: &str) -> Result<Self, String> {
+        let config: Self = toml::from_str(contents).map_err(|err| err.to_string())?;
+        if config.registry.is_some() && config.global_project {
+            return Err("`registry` and `global_project` can't both be set.".to_string());
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let contents = "registry = \"/refs/refman.toml\"\nmax_concurrent_downloads = 8\n";
+        let config = Config::parse(contents).unwrap();
+        assert_eq!(config.registry.as_deref(), Some("/refs/refman.toml"));
+        assert_eq!(config.max_concurrent_downloads, Some(8));
+        assert!(!config.global_project);
+
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+        assert!(Config::parse("registy = \"typo.toml\"\n").is_err());
+        assert!(Config::parse("registry = \"a.toml\"\nglobal_project = true\n").is_err());
+    }
+}
@@ -0,0 +1,203 @@
+//! The content submodule.
+//!
+//! Sniffs whether a local file looks like the format its dataset field claims,
+//! e.g. that a `fasta` file starts with a `>` header followed by sequence, so
+//! that an HTML error page or a mixed-up path is caught at registration rather
+//! than by a downstream tool. Only the first few KB of each file are read, and
+//! gzip and bgzip files are read through their compression. This is a sanity
+//! check, not a full parse.
+
+use std::{fs::File, io::Read, path::Path};
+
+use crate::{
+    dataset::FileKind,
+    downloads::{self, Scheme},
+    errors::ContentError,
+};
+use flate2::read::MultiGzDecoder;
+
+/// How much of each file is inspected.
+const SNIFF_BYTES: u64 = 8 * 1024;
+
+/// The characters a FASTA sequence line may hold: IUPAC nucleotide and amino
+/// acid codes in either case, gaps, and stop codons.
+fn is_sequence_line(line: &str) -> bool {
+    line.bytes()
+        .all(|byte| byte.is_ascii_alphabetic() || matches!(byte, b'-' | b'*' | b'.'))
+}
+
+/// Whether `field` parses as a one-based coordinate.
+fn is_coordinate(field: &str) -> bool {
+    !field.is_empty() && field.bytes().all(|byte| byte.is_ascii_digit())
+}
+
+/// Check one data line of a GFF or GTF file, i.e. nine tab-separated columns
+/// with numeric coordinates.
+fn check_feature_line(line: &str) -> Result<(), String> {
+    let columns: Vec<&str> = line.split('\t').collect();
+    if columns.len() != 9 {
+        return Err(format!(
+            "expected 9 tab-separated columns, found {} in '{line}'",
+            columns.len()
+        ));
+    }
+    if !is_coordinate(columns[3]) || !is_coordinate(columns[4]) {
+        return Err(format!("the start and end of '{line}' aren't numeric"));
+    }
+    Ok(())
+}
+
+/// Check that `lines`, the beginning of a file, look like a file of `kind`,
+/// returning why not if they don't.
+pub fn check_lines(kind: FileKind, lines: &[String]) -> Result<(), String> {
+    let mut meaningful = lines
+        .iter()
+        .map(|line| line.trim_end())
+        .filter(|line| !line.is_empty());
+    let Some(first) = meaningful.clone().next() else {
+        return Err("the file is empty".to_string());
+    };
+    match kind {
+        FileKind::Fasta => {
+            if !first.starts_with('>') {
+                return Err("FASTA files must start with a '>' header line".to_string());
+            }
+            match meaningful.find(|line| !line.starts_with('>') && !is_sequence_line(line)) {
+                Some(line) => Err(format!("'{line}' is neither a header nor sequence")),
+                None => Ok(()),
+            }
+        }
+        FileKind::Genbank => {
+            if first.starts_with("LOCUS") {
+                Ok(())
+            } else {
+                Err("GenBank files must start with a LOCUS line".to_string())
+            }
+        }
+        FileKind::Gfa => match meaningful.find(|line| {
+            !line.starts_with('#')
+                && !matches!(
+                    line.split('\t').next(),
+                    Some("H" | "S" | "L" | "C" | "P" | "W" | "J")
+                )
+        }) {
+            Some(line) => Err(format!("'{line}' isn't a GFA record")),
+            None => Ok(()),
+        },
+        FileKind::Gff => {
+            if !first.starts_with("##gff-version") {
+                return Err("GFF3 files must start with a '##gff-version 3' header".to_string());
+            }
+            meaningful
+                .filter(|line| !line.starts_with('#'))
+                .try_for_each(check_feature_line)
+        }
+        FileKind::Gtf => meaningful
+            .filter(|line| !line.starts_with('#'))
+            .try_for_each(check_feature_line),
+        FileKind::Bed => meaningful
+            .filter(|line| {
+                !line.starts_with('#') && !line.starts_with("track") && !line.starts_with("browser")
+            })
+            .try_for_each(|line| {
+                let columns: Vec<&str> =
+                    line.split(['\t', ' ']).filter(|c| !c.is_empty()).collect();
+                if columns.len() >= 3 && is_coordinate(columns[1]) && is_coordinate(columns[2]) {
+                    Ok(())
+                } else {
+                    Err(format!("'{line}' isn't a BED interval"))
+                }
+            }),
+        FileKind::Vcf => {
+            if first.starts_with("##fileformat=VCF") {
+                Ok(())
+            } else {
+                Err("VCF files must start with a '##fileformat=VCF' line".to_string())
+            }
+        }
+        // binary formats have no lines to check
+        FileKind::TwoBit | FileKind::Nib | FileKind::VcfIndex => Ok(()),
+    }
+}
+
+/// Read the complete lines among the first few KB of `path`, decompressing it
+/// if it is gzipped.
+fn head(path: &Path) -> std::io::Result<Vec<String>> {
+    let mut magic = [0; 2];
+    let gzipped = File::open(path)?.read_exact(&mut magic).is_ok() && magic == [0x1f, 0x8b];
+    let file = File::open(path)?;
+    let reader: Box<dyn Read> = if gzipped {
+        Box::new(MultiGzDecoder::new(file))
+    } else {
+        Box::new(file)
+    };
+    let mut window = Vec::new();
+    reader.take(SNIFF_BYTES).read_to_end(&mut window)?;
+    let mut lines: Vec<String> = String::from_utf8_lossy(&window)
+        .split('\n')
+        .map(str::to_string)
+        .collect();
+    // a full window most likely cut its last line short
+    if window.len() as u64 == SNIFF_BYTES && lines.len() > 1 {
+        lines.pop();
+    }
+    Ok(lines)
+}
+
+/// Check that every local file among `files` looks like the format of its
+/// field. Remote sources are skipped, since checking them would mean
+/// downloading them, as are sources referring to unset environment variables.
+pub fn check_files(files: &[(FileKind, &str)]) -> Result<(), ContentError> {
+    for &(kind, url) in files {
+        let Ok(url) = downloads::expand_vars(url) else {
+            continue;
+        };
+        let url = &*url;
+        if Scheme::of(url) != Scheme::Local {
+            continue;
+        }
+        let path = downloads::local_path(url);
+        let lines = head(path).map_err(|source| ContentError::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        check_lines(kind, &lines).map_err(|reason| ContentError::InvalidFormat {
+            kind,
+            path: path.to_path_buf(),
+            reason,
+        })?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.split('\n').map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_check_lines() {
+        assert!(check_lines(FileKind::Fasta, &lines(">chr1\nACGTN\nacgt\n>chr2\nGG\n")).is_ok());
+        assert!(check_lines(FileKind::Fasta, &lines("<!DOCTYPE html>\n<html>\n")).is_err());
+        assert!(check_lines(FileKind::Fasta, &lines(">chr1\nAC GT\nAC\n")).is_err());
+        assert!(check_lines(
+            FileKind::Gff,
+            &lines("##gff-version 3\nchr1\tsrc\tgene\t1\t100\t.\t+\t.\tID=g1\n")
+        )
+        .is_ok());
+        assert!(check_lines(
+            FileKind::Gff,
+            &lines("chr1\tsrc\tgene\t1\t100\t.\t+\t.\tID=g1\n")
+        )
+        .is_err());
+        assert!(check_lines(FileKind::Bed, &lines("track name=x\nchr1\t10\t20\tpeak\n")).is_ok());
+        assert!(check_lines(FileKind::Bed, &lines("chr1\tten\ttwenty\n")).is_err());
+        assert!(check_lines(FileKind::Genbank, &lines("LOCUS       NC_045512\n")).is_ok());
+        assert!(check_lines(FileKind::Gfa, &lines("H\tVN:Z:1.0\nS\t1\tACGT\n")).is_ok());
+        assert!(check_lines(FileKind::Fasta, &lines("")).is_err());
+        assert!(check_lines(FileKind::Vcf, &lines("##fileformat=VCFv4.2\n#CHROM\tPOS\n")).is_ok());
+    }
+}
@@ -0,0 +1,296 @@
+//! The dataset submodule.
+//!
+//! `refman`'s `RefDataset` stores each supported file format as its own
+//! optional field. This module provides a uniform view over those fields so
+//! that features which need to walk "every file in a dataset" don't each have
+//! to enumerate the six formats by hand. Formats `refman` has no field for,
+//! like UCSC's `.2bit`, are supplementary kinds whose sources the bindings keep
+//! in the metadata sidecar instead.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    str::FromStr,
+};
+
+use refman::prelude::RefDataset;
+
+use crate::errors::DatasetError;
+
+/// The file formats a reference dataset can hold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum FileKind {
+    Fasta,
+    Genbank,
+    Gfa,
+    Gff,
+    Gtf,
+    Bed,
+    TwoBit,
+    Nib,
+    Vcf,
+    VcfIndex,
+}
+
+impl FileKind {
+    /// The kinds `refman`'s `RefDataset` has a field for.
+    pub const ALL: [FileKind; 6] = [
+        FileKind::Fasta,
+        FileKind::Genbank,
+        FileKind::Gfa,
+        FileKind::Gff,
+        FileKind::Gtf,
+        FileKind::Bed,
+    ];
+
+    /// The kinds `refman`'s registry has no field for, whose sources are kept in
+    /// the metadata sidecar.
+    pub const SUPPLEMENTARY: [FileKind; 4] = [
+        FileKind::TwoBit,
+        FileKind::Nib,
+        FileKind::Vcf,
+        FileKind::VcfIndex,
+    ];
+
+    /// Every kind, registry fields first.
+    pub fn every() -> impl Iterator<Item = FileKind> {
+        FileKind::ALL.into_iter().chain(FileKind::SUPPLEMENTARY)
+    }
+
+    pub fn is_supplementary(self) -> bool {
+        FileKind::SUPPLEMENTARY.contains(&self)
+    }
+
+    /// The name of the dataset field holding files of this kind.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FileKind::Fasta => "fasta",
+            FileKind::Genbank => "genbank",
+            FileKind::Gfa => "gfa",
+            FileKind::Gff => "gff",
+            FileKind::Gtf => "gtf",
+            FileKind::Bed => "bed",
+            FileKind::TwoBit => "twobit",
+            FileKind::Nib => "nib",
+            FileKind::Vcf => "vcf",
+            FileKind::VcfIndex => "vcf_index",
+        }
+    }
+}
+
+impl FromStr for FileKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        FileKind::every()
+            .find(|kind| kind.as_str().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                let supported: Vec<_> = FileKind::every().map(FileKind::as_str).collect();
+                format!(
+                    "Unsupported file kind '{s}'. Expected one of: {}.",
+                    supported.join(", ")
+                )
+            })
+    }
+}
+
+impl Display for FileKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Extension methods for `refman`'s `RefDataset`.
+pub trait RefDatasetExt {
+    /// Each file field of the dataset alongside its kind, populated or not.
+    fn fields(&self) -> [(FileKind, Option<&str>); 6];
+
+    /// Only the populated file fields of the dataset.
+    fn files(&self) -> Vec<(FileKind, &str)> {
+        self.fields()
+            .into_iter()
+            .filter_map(|(kind, value)| value.map(|value| (kind, value)))
+            .collect()
+    }
+
+    /// The populated file fields of the dataset followed by the non-empty
+    /// `supplementary` files, e.g. as kept in the metadata sidecar.
+    fn files_with<'a>(
+        &'a self,
+        supplementary: &'a [(FileKind, String)],
+    ) -> Vec<(FileKind, &'a str)> {
+        let mut files = self.files();
+        files.extend(
+            supplementary
+                .iter()
+                .filter(|(_, source)| !source.is_empty())
+                .map(|(kind, source)| (*kind, source.as_str())),
+        );
+        files
+    }
+
+    /// Whether two datasets carry the same label and exactly the same files.
+    fn same_content(&self, other: &RefDataset) -> bool;
+}
+
+impl RefDatasetExt for RefDataset {
+    fn fields(&self) -> [(FileKind, Option<&str>); 6] {
+        [
+            (FileKind::Fasta, self.fasta.as_deref()),
+            (FileKind::Genbank, self.genbank.as_deref()),
+            (FileKind::Gfa, self.gfa.as_deref()),
+            (FileKind::Gff, self.gff.as_deref()),
+            (FileKind::Gtf, self.gtf.as_deref()),
+            (FileKind::Bed, self.bed.as_deref()),
+        ]
+    }
+
+    fn same_content(&self, other: &RefDataset) -> bool {
+        self.label == other.label && self.fields() == other.fields()
+    }
+}
+
+/// Whether `c` may appear in a label. Labels name download directories, so
+/// they are kept to characters that are safe in a path on every platform.
+pub fn is_label_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.')
+}
+
+/// Check that `label` is non-empty, made only of ASCII letters, digits, `_`,
+/// `-`, and `.`, and isn't a relative path component like `..`.
+pub fn validate_label(label: &str) -> Result<(), DatasetError> {
+    let reason = if label.is_empty() {
+        "labels must not be empty".to_string()
+    } else if let Some(c) = label.chars().find(|&c| !is_label_char(c)) {
+        format!("'{c}' is not allowed; use only letters, digits, '_', '-', and '.'")
+    } else if label.chars().all(|c| c == '.') {
+        "labels must not consist only of dots".to_string()
+    } else {
+        return Ok(());
+    };
+    Err(DatasetError::InvalidLabel {
+        label: label.to_string(),
+        reason,
+    })
+}
+
+/// Turn `label` into one `validate_label` accepts by replacing every character
+/// it doesn't allow with `_`, e.g. `E. coli K-12` becomes `E._coli_K-12`.
+pub fn normalize_label(label: &str) -> String {
+    let normalized: String = label
+        .trim()
+        .chars()
+        .map(|c| if is_label_char(c) { c } else { '_' })
+        .collect();
+    if normalized.chars().all(|c| c == '.') {
+        normalized.replace('.', "_")
+    } else {
+        normalized
+    }
+}
+
+/// Refuse to register `label` without any `files`, which is almost always a
+/// registration that forgot to pass its sources.
+pub fn require_files(label: &str, files: &[(FileKind, &str)]) -> Result<(), DatasetError> {
+    if files.is_empty() {
+        return Err(DatasetError::NoFilesProvided {
+            label: label.to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// A dataset's label and file sources before they have been validated into a
+/// `RefDataset`, e.g. one entry of a bulk registration.
+#[derive(Debug, Clone, Default)]
+pub struct DatasetSpec {
+    pub label: String,
+    pub files: BTreeMap<FileKind, String>,
+}
+
+impl DatasetSpec {
+    /// Build a spec from a mapping with a `label` key and one key per file kind.
+    /// Only the kinds the registry has a field for are accepted.
+    pub fn from_map(mut map: HashMap<String, String>) -> Result<Self, String> {
+        let label = map
+            .remove("label")
+            .ok_or_else(|| "Each dataset must provide a 'label'.".to_string())?;
+        let files = map
+            .into_iter()
+            .filter(|(_, source)| !source.is_empty())
+            .map(|(kind, source)| match kind.parse::<FileKind>()? {
+                kind if kind.is_supplementary() => Err(format!(
+                    "'{kind}' files can only be registered one dataset at a time, with `register`."
+                )),
+                kind => Ok((kind, source)),
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Self { label, files })
+    }
+
+    /// A spec for the files `dataset` is currently registered with.
+    pub fn from_dataset(dataset: &RefDataset) -> Self {
+        let files = dataset
+            .files()
+            .into_iter()
+            .map(|(kind, source)| (kind, source.to_string()))
+            .collect();
+        Self {
+            label: dataset.label.clone(),
+            files,
+        }
+    }
+
+    /// Overwrite the given files, leaving the rest alone, where an empty source
+    /// clears that kind of file. Returns the kinds whose source changed.
+    pub fn update(
+        &mut self,
+        changes: impl IntoIterator<Item = (FileKind, Option<String>)>,
+    ) -> Vec<FileKind> {
+        let mut updated = Vec::new();
+        for (kind, source) in changes {
+            let Some(source) = source else {
+                continue;
+            };
+            let differs = if source.is_empty() {
+                self.files.remove(&kind).is_some()
+            } else {
+                self.files.insert(kind, source.clone()).as_ref() != Some(&source)
+            };
+            if differs {
+                updated.push(kind);
+            }
+        }
+        updated
+    }
+
+    /// Whether `dataset` already holds exactly the files this spec describes.
+    pub fn matches(&self, dataset: &RefDataset) -> bool {
+        self.label == dataset.label
+            && dataset
+                .fields()
+                .into_iter()
+                .all(|(kind, value)| self.files.get(&kind).map(String::as_str) == value)
+    }
+
+    /// Validate the spec into a `RefDataset`.
+    pub async fn build(mut self) -> anyhow::Result<RefDataset> {
+        validate_label(&self.label)?;
+        if self.files.is_empty() {
+            return Err(DatasetError::NoFilesProvided { label: self.label }.into());
+        }
+        let mut take = |kind| self.files.remove(&kind);
+        let (fasta, genbank, gfa) = (
+            take(FileKind::Fasta),
+            take(FileKind::Genbank),
+            take(FileKind::Gfa),
+        );
+        let (gff, gtf, bed) = (
+            take(FileKind::Gff),
+            take(FileKind::Gtf),
+            take(FileKind::Bed),
+        );
+        let dataset = RefDataset::try_new(self.label, fasta, genbank, gfa, gff, gtf, bed).await?;
+        Ok(dataset)
+    }
+}
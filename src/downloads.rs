@@ -0,0 +1,2864 @@
+//! The downloads submodule.
+//!
+//! This module fetches a dataset's files into a destination directory,
+//! dispatching on each source's URL scheme, and layers the bookkeeping that
+//! pipelines care about on top: working out which files a download should have
+//! produced, checking that they actually landed, and recording a completion
+//! sentinel that workflow engines like Snakemake or Make can depend on, or a
+//! provenance record for audits.
+
+use std::{
+    borrow::Cow,
+    collections::{hash_map::RandomState, HashMap},
+    fs,
+    hash::{BuildHasher, Hasher},
+    io::{self, IsTerminal, Read, Seek, Write},
+    iter,
+    path::{Path, PathBuf},
+    process::Stdio,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use flate2::read::MultiGzDecoder;
+use refman::prelude::{Project, RefDataset};
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::AsyncWriteExt,
+    process::Command,
+    sync::mpsc::{self, UnboundedSender},
+    task::JoinSet,
+};
+
+use crate::{
+    cache::Cache,
+    dataset::{FileKind, RefDatasetExt},
+    errors::FetchError,
+    ftp,
+    hashing::{self, Algorithm},
+    logging::Verbosity,
+    metadata::{DatasetMeta, Metadata},
+    network::{host_of, redact, Network},
+    s3,
+};
+
+/// The transfer mechanisms a dataset's sources can be fetched with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scheme {
+    Http,
+    Ftp,
+    Rsync,
+    S3,
+    Local,
+}
+
+impl Scheme {
+    pub fn of(url: &str) -> Self {
+        if url.starts_with("rsync://") {
+            Scheme::Rsync
+        } else if url.starts_with("s3://") {
+            Scheme::S3
+        } else if url.starts_with("ftp://") {
+            Scheme::Ftp
+        } else if url.starts_with("file://") || !url.contains("://") {
+            Scheme::Local
+        } else {
+            Scheme::Http
+        }
+    }
+}
+
+/// Expand references to environment variables in a source, written either as
+/// `${VAR}` or `$VAR`, so that one registry can point at locations that differ
+/// between machines, e.g. `${REFS_MIRROR}/hg38.fa`. A `$` that doesn't start a
+/// variable name is left as is. Referring to a variable that isn't set is an
+/// error rather than an empty expansion.
+pub fn expand_vars(url: &str) -> Result<Cow<'_, str>, FetchError> {
+    if !url.contains('$') {
+        return Ok(Cow::Borrowed(url));
+    }
+    let mut expanded = String::with_capacity(url.len());
+    let mut rest = url;
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let (var, remainder) = if let Some(braced) = after.strip_prefix('{') {
+            braced
+                .find('}')
+                .map_or(("", after), |end| (&braced[..end], &braced[end + 1..]))
+        } else {
+            let end = after
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(after.len());
+            (&after[..end], &after[end..])
+        };
+        if var.is_empty() {
+            expanded.push('$');
+        } else {
+            let value = std::env::var(var).map_err(|_| FetchError::UnresolvedVariable {
+                var: var.to_string(),
+                url: url.to_string(),
+            })?;
+            expanded.push_str(&value);
+        }
+        rest = remainder;
+    }
+    expanded.push_str(rest);
+    Ok(Cow::Owned(expanded))
+}
+
+/// The filesystem path a local source points at.
+pub fn local_path(url: &str) -> &Path {
+    Path::new(url.strip_prefix("file://").unwrap_or(url))
+}
+
+/// Check whether a source can currently be fetched without downloading it,
+/// returning why not if it can't. Remote sources are checked with a HEAD
+/// request, FTP files with `SIZE`, S3 objects with `head-object`, and local
+/// sources with an existence check. `rsync` offers no cheap equivalent, so those sources are assumed to be
+/// available. Checking a remote source in offline mode is an error rather than a
+/// reason to skip it, as is one referring to an unset environment variable.
+pub async fn unavailable_reason(
+    network: &Network,
+    url: &str,
+) -> Result<Option<String>, FetchError> {
+    let url = &*expand_vars(url)?;
+    let reason = match Scheme::of(url) {
+        Scheme::Local => {
+            let path = local_path(url);
+            (!path.exists()).then(|| format!("'{}' does not exist", path.display()))
+        }
+        Scheme::Rsync => None,
+        Scheme::Ftp => {
+            network.guard(url)?;
+            ftp::size(url).await.err().map(|err| err.to_string())
+        }
+        Scheme::S3 => {
+            network.guard(url)?;
+            s3::head(url).await.err().map(|err| err.to_string())
+        }
+        Scheme::Http => match network.head(url)?.send().await {
+            Ok(response) => {
+                let status = response.status();
+                (!status.is_success()).then(|| format!("HTTP status {status}"))
+            }
+            Err(err) => Some(err.to_string()),
+        },
+    };
+    Ok(reason)
+}
+
+/// The first of `urls` that is unavailable, along with why, going by
+/// `unavailable_reason`.
+pub async fn first_unavailable<'a>(
+    network: &Network,
+    urls: impl IntoIterator<Item = &'a String>,
+) -> Result<Option<(String, String)>, FetchError> {
+    for url in urls {
+        if let Some(reason) = unavailable_reason(network, url).await? {
+            return Ok(Some((url.clone(), reason)));
+        }
+    }
+    Ok(None)
+}
+
+/// Check that every local source among `files`, i.e. a `file://` URL or a
+/// plain path, exists. Sources referring to environment variables that aren't
+/// set here can't be located and are skipped.
+pub fn check_local_sources(files: &[(FileKind, &str)]) -> Result<(), FetchError> {
+    for &(kind, url) in files {
+        let Ok(url) = expand_vars(url) else {
+            continue;
+        };
+        let url = &*url;
+        let path = local_path(url);
+        if Scheme::of(url) == Scheme::Local && !path.exists() {
+            return Err(FetchError::LocalFileMissing {
+                kind,
+                path: path.to_path_buf(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Check that every source among `files` can currently be fetched, e.g. before
+/// a dataset is accepted into a registry, naming the first one that can't.
+/// Every environment variable a source refers to must be set. Remote sources
+/// are left unchecked in offline mode.
+pub async fn check_sources(
+    network: &Network,
+    files: &[(FileKind, &str)],
+) -> Result<(), FetchError> {
+    check_local_sources(files)?;
+    for &(kind, url) in files {
+        if Scheme::of(&expand_vars(url)?) == Scheme::Local || network.is_offline() {
+            continue;
+        }
+        if let Some(reason) = unavailable_reason(network, url).await? {
+            return Err(FetchError::UnreachableUrl {
+                kind,
+                url: url.to_string(),
+                reason,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Derive the name a remote file will be written under from its URL, i.e. the
+/// last path segment with any query string or fragment removed.
+pub fn file_name_from_url(url: &str) -> Option<&str> {
+    let without_query = url.split(['?', '#']).next().unwrap_or(url);
+    without_query
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .filter(|name| !name.is_empty() && !name.ends_with(':'))
+}
+
+/// The name the file at `url` is written under in a download destination.
+pub fn target_name(url: &str) -> Result<&str, FetchError> {
+    file_name_from_url(url).ok_or_else(|| FetchError::UnnamedFile {
+        url: url.to_string(),
+    })
+}
+
+/// The name a `kind` file from `url` is written under: the one `file_names`
+/// gives for its kind, or otherwise the one its URL ends in.
+pub fn file_name_for<'a>(
+    file_names: &'a HashMap<FileKind, String>,
+    kind: FileKind,
+    url: &'a str,
+) -> Result<&'a str, FetchError> {
+    match file_names.get(&kind) {
+        Some(name) => Ok(name),
+        None => target_name(url),
+    }
+}
+
+/// Check that every name in `file_names` is a plain file name, and that no two
+/// of `files` would be written under the same name once they are applied.
+pub fn check_file_names(
+    files: &[(FileKind, &str)],
+    file_names: &HashMap<FileKind, String>,
+) -> Result<(), FetchError> {
+    for (&kind, name) in file_names {
+        if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+            return Err(FetchError::InvalidFileName {
+                kind,
+                name: name.clone(),
+            });
+        }
+    }
+    let mut seen: HashMap<&str, FileKind> = HashMap::new();
+    for &(kind, url) in files {
+        let name = file_name_for(file_names, kind, url)?;
+        if let Some(&first) = seen.get(name) {
+            return Err(FetchError::FileNameCollision {
+                name: name.to_string(),
+                first,
+                second: kind,
+            });
+        }
+        seen.insert(name, kind);
+    }
+    Ok(())
+}
+
+/// How the files of downloaded datasets are laid out under a destination.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Layout {
+    /// Every file goes straight into the destination, whichever dataset it
+    /// belongs to.
+    #[default]
+    Flat,
+    /// Each dataset's files go into a subdirectory named after its label.
+    PerLabel,
+}
+
+impl Layout {
+    /// The directory the files of the dataset labelled `label` go into.
+    pub fn dest_for(self, dest: &Path, label: &str) -> PathBuf {
+        match self {
+            Layout::Flat => dest.to_path_buf(),
+            Layout::PerLabel => dest.join(label),
+        }
+    }
+}
+
+impl FromStr for Layout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().replace('-', "_").as_str() {
+            "flat" => Ok(Layout::Flat),
+            "per_label" => Ok(Layout::PerLabel),
+            _ => Err(format!(
+                "Unsupported layout '{s}'. Expected one of: flat, per_label."
+            )),
+        }
+    }
+}
+
+/// Check that no two files across `project`'s datasets, along with the
+/// supplementary files `metadata` holds for them, would be written under the
+/// same name, as they would be when downloaded with a flat layout.
+pub fn check_flat_layout(
+    project: &Project,
+    metadata: &Metadata,
+    file_names: &HashMap<FileKind, String>,
+) -> Result<(), FetchError> {
+    let mut seen: HashMap<String, &str> = HashMap::new();
+    for dataset in project.datasets() {
+        for name in flat_names(dataset, metadata, file_names)? {
+            if let Some(first) = seen.insert(name.clone(), &dataset.label) {
+                return Err(FetchError::LabelFileNameCollision {
+                    name,
+                    first: first.to_string(),
+                    second: dataset.label.clone(),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check that none of the files of the dataset labelled `label`, named as
+/// `file_names` says, would be written under the same name as a file of any
+/// other of `project`'s datasets, as they would be when downloaded into the
+/// same directory with a flat layout.
+pub fn check_flat_target(
+    project: &Project,
+    metadata: &Metadata,
+    label: &str,
+    file_names: &HashMap<FileKind, String>,
+) -> Result<(), FetchError> {
+    let Some(dataset) = project
+        .datasets()
+        .iter()
+        .find(|dataset| dataset.label == label)
+    else {
+        return Ok(());
+    };
+    let names = flat_names(dataset, metadata, file_names)?;
+    for other in project
+        .datasets()
+        .iter()
+        .filter(|other| other.label != label)
+    {
+        // a dataset whose own files can't be named can't be downloaded either,
+        // so it can't be in the way
+        let other_names = flat_names(other, metadata, &HashMap::new()).unwrap_or_default();
+        if let Some(name) = other_names.into_iter().find(|name| names.contains(name)) {
+            return Err(FetchError::LabelFileNameCollision {
+                name,
+                first: other.label.clone(),
+                second: label.to_string(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// The names the files of `dataset`, along with the supplementary files
+/// `metadata` holds for it, are written under, where `file_names` overrides
+/// the names `metadata` records for it.
+fn flat_names(
+    dataset: &RefDataset,
+    metadata: &Metadata,
+    file_names: &HashMap<FileKind, String>,
+) -> Result<Vec<String>, FetchError> {
+    let supplementary = metadata.supplementary(&dataset.label);
+    let files = dataset.files_with(&supplementary);
+    let file_names = metadata.file_names(&dataset.label, file_names);
+    check_file_names(&files, &file_names)?;
+    files
+        .into_iter()
+        .map(|(kind, url)| Ok(file_name_for(&file_names, kind, url)?.to_string()))
+        .collect()
+}
+
+/// The `(url, target path)` pair each of `dataset`'s files would be fetched to
+/// under `dest`, along with its `supplementary` files, without touching the
+/// network or the filesystem. Files are named as `file_names` say, and those
+/// that are decompressed after fetching end up without their `.gz` suffix.
+pub fn plan_download(
+    dataset: &RefDataset,
+    supplementary: &[(FileKind, String)],
+    file_names: &HashMap<FileKind, String>,
+    dest: &Path,
+) -> Result<Vec<(String, PathBuf)>, FetchError> {
+    let files = dataset.files_with(supplementary);
+    check_file_names(&files, file_names)?;
+    files
+        .into_iter()
+        .map(|(kind, url)| {
+            let name = file_name_for(file_names, kind, url)?;
+            Ok((url.to_string(), dest.join(name)))
+        })
+        .collect()
+}
+
+/// Per-call settings that tune how a dataset is downloaded.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Clone, Default)]
+pub struct DownloadOptions {
+    /// Files of a given kind smaller than this many bytes are rejected as
+    /// probably truncated.
+    pub min_sizes: HashMap<FileKind, u64>,
+
+    /// Files of a given kind larger than this many bytes are rejected as
+    /// probably not what was intended, e.g. an HTML error page or the wrong
+    /// assembly.
+    pub max_sizes: HashMap<FileKind, u64>,
+
+    /// Unix permission bits, e.g. `0o644`, applied to each written file.
+    pub mode: Option<u32>,
+
+    /// A group name or numeric ID that written files are handed to on Unix.
+    pub group: Option<String>,
+
+    /// Fail when the destination directory doesn't exist yet instead of
+    /// creating it, for workflows that expect strictly pre-created directories.
+    pub require_existing_dest: bool,
+
+    /// Verify each file against a `.sha256` or `.md5` checksum published next to
+    /// its source, when the provider offers one.
+    pub auto_verify: bool,
+
+    /// Fail rather than skip verification when no published checksum is found.
+    /// Implies `auto_verify`.
+    pub require_checksum: bool,
+
+    /// Refuse any network access, so that only local sources can be fetched.
+    pub offline: bool,
+
+    /// SHA-256 digests registered for the dataset's files. A downloaded file
+    /// that doesn't match its digest is rejected.
+    pub sha256: HashMap<FileKind, String>,
+
+    /// Continue partial HTTP downloads left by an interrupted run instead of
+    /// starting them over.
+    pub resume: bool,
+
+    /// How many of the dataset's files are fetched at once. At least one file is
+    /// always in flight.
+    pub max_concurrent_downloads: usize,
+
+    /// Hard-link local sources into the destination instead of copying them
+    /// when both are on the same filesystem. Files that `mode` or `group` apply
+    /// to are always copied, so that the source itself is left alone.
+    pub link_local: bool,
+
+    /// Sizes recorded for the dataset's files by an earlier download. A file
+    /// already in the destination with its recorded size is kept rather than
+    /// fetched again, unless its source now reports a different size.
+    pub sizes: HashMap<FileKind, u64>,
+
+    /// Fetch every file even when an earlier download is already in place.
+    pub force: bool,
+
+    /// Decompress gzip and bgzip files after they are fetched and verified,
+    /// dropping their `.gz` suffix.
+    pub decompress: bool,
+
+    /// Where to report `(downloaded, total)` byte counts for the dataset as a
+    /// whole while it downloads. See `download_dataset_with_progress`.
+    pub progress: Option<UnboundedSender<(u64, Option<u64>)>>,
+
+    /// How many more times a file is fetched after a transient failure, e.g. a
+    /// dropped connection or a 5xx response, before giving up on it.
+    pub max_retries: u32,
+
+    /// The delay before the first retry, which doubles with each further one.
+    pub retry_base_delay_ms: u64,
+
+    /// How long fetching any one file may take before it is abandoned. Unset,
+    /// transfers may take as long as they need.
+    pub timeout_secs: Option<u64>,
+
+    /// Extra headers, e.g. `Authorization`, sent with every HTTP request to a
+    /// source's own host. Values are marked sensitive so they aren't logged.
+    pub headers: HeaderMap,
+
+    /// Files `refman` has no dataset field for, like `.2bit` sequence, fetched
+    /// alongside the dataset's own. See `DatasetMeta::supplementary`.
+    pub supplementary: Vec<(FileKind, String)>,
+
+    /// Fallback sources for the dataset's files, by file kind, tried in order
+    /// when the registered source fails. See `DatasetMeta::mirrors`.
+    pub mirrors: HashMap<FileKind, Vec<String>>,
+
+    /// Also fetch the companion indexes that `companion_urls` names for each
+    /// file, when its source has them. Companions the source doesn't report,
+    /// and those of files that are decompressed, are skipped.
+    pub fetch_indexes: bool,
+
+    /// Names to write files of a given kind under instead of the one their URL
+    /// ends in, e.g. `genome.fa` for a FASTA with an unwieldy URL. See
+    /// `check_file_names`.
+    pub file_names: HashMap<FileKind, String>,
+
+    /// Start downloading without first checking that the files will fit on the
+    /// destination's filesystem. See `check_space`.
+    pub skip_space_check: bool,
+
+    /// How much is printed to stderr while downloading besides errors.
+    pub verbosity: Verbosity,
+
+    /// Whether the dataset's files go straight into the destination or into a
+    /// subdirectory of it named after the dataset's label.
+    pub layout: Layout,
+
+    /// A shared cache that fetched files are moved into and linked back from,
+    /// and that files with a known SHA-256 digest are linked from instead of
+    /// being fetched again. Files that are decompressed aren't cached.
+    pub cache: Option<Cache>,
+}
+
+impl DownloadOptions {
+    fn timeout(&self) -> Option<Duration> {
+        self.timeout_secs.map(Duration::from_secs)
+    }
+
+    /// Print where a file came from, where it went, and how big it is, when
+    /// verbose.
+    fn report(&self, fetched: &Fetched) {
+        if self.verbosity.is_verbose() {
+            let kept = if fetched.reused { ", kept" } else { "" };
+            eprintln!(
+                "{}: {} -> {} ({} bytes{kept})",
+                fetched.kind,
+                redact(&fetched.url),
+                fetched.path.display(),
+                fetched.size
+            );
+        }
+    }
+
+    /// Link the cached copy of a `kind` file to `path` instead of fetching it,
+    /// when its digest is known and the cache holds it.
+    fn link_cached(
+        &self,
+        kind: FileKind,
+        url: &str,
+        path: &Path,
+    ) -> Result<Option<Fetched>, FetchError> {
+        let (Some(cache), Some(digest)) = (&self.cache, self.sha256.get(&kind)) else {
+            return Ok(None);
+        };
+        let digest = digest.to_ascii_lowercase();
+        let path = path.to_path_buf();
+        match cache.link_entry(&digest, &path) {
+            Ok(true) => {}
+            Ok(false) => return Ok(None),
+            Err(err) => {
+                tracing::warn!(%kind, error = %err, "could not link cached file");
+                return Ok(None);
+            }
+        }
+        let size = fs::metadata(&path)
+            .map_err(|source| FetchError::Io {
+                path: path.clone(),
+                source,
+            })?
+            .len();
+        Ok(Some(Fetched {
+            kind,
+            url: url.to_string(),
+            path,
+            redirects: Vec::new(),
+            etag: None,
+            downloaded_at: Utc::now().to_rfc3339(),
+            size,
+            reused: true,
+            companion: false,
+            cache_key: Some(digest),
+        }))
+    }
+
+    /// Move a freshly fetched file into the cache, leaving a link to it in its
+    /// place. A file that can't be cached stays where it landed.
+    fn cache_fetched(&self, mut fetched: Fetched) -> Fetched {
+        let Some(cache) = &self.cache else {
+            return fetched;
+        };
+        match cache.adopt(&fetched.path) {
+            Ok(key) => fetched.cache_key = key,
+            Err(err) => {
+                tracing::warn!(path = %fetched.path.display(), error = %err, "could not cache file");
+            }
+        }
+        fetched
+    }
+
+    /// Apply the size sanity thresholds for `kind` to a downloaded file. This is
+    /// a heuristic guard for when neither an exact size nor a checksum is known.
+    fn check_size(&self, kind: FileKind, path: &Path) -> Result<(), FetchError> {
+        let size = fs::metadata(path)
+            .map_err(|source| FetchError::Io {
+                path: path.to_path_buf(),
+                source,
+            })?
+            .len();
+        if let Some(&minimum) = self.min_sizes.get(&kind) {
+            if size < minimum {
+                return Err(FetchError::SuspiciouslySmall {
+                    kind,
+                    size,
+                    minimum,
+                });
+            }
+        }
+        if let Some(&maximum) = self.max_sizes.get(&kind) {
+            if size > maximum {
+                return Err(FetchError::SuspiciouslyLarge {
+                    kind,
+                    size,
+                    maximum,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Make sure the destination directory exists, creating it and any missing
+    /// parents unless a pre-existing destination is required. New directories
+    /// get the configured file mode plus the execute bits needed to enter them.
+    pub fn prepare_dest(&self, dest: &Path) -> Result<(), FetchError> {
+        if dest.is_dir() {
+            return Ok(());
+        }
+        if self.require_existing_dest {
+            return Err(FetchError::DestMissing {
+                path: dest.to_path_buf(),
+            });
+        }
+
+        let mut builder = fs::DirBuilder::new();
+        builder.recursive(true);
+        #[cfg(unix)]
+        if let Some(mode) = self.mode {
+            use std::os::unix::fs::DirBuilderExt;
+            builder.mode(mode | ((mode & 0o444) >> 2));
+        }
+        builder.create(dest).map_err(|source| FetchError::Io {
+            path: dest.to_path_buf(),
+            source,
+        })
+    }
+
+    /// Apply the configured permissions and group ownership to a written file,
+    /// which matters for reference stores shared between users on a cluster.
+    #[cfg(unix)]
+    fn apply_ownership(&self, path: &Path) -> Result<(), FetchError> {
+        use std::os::unix::fs::{chown, PermissionsExt};
+
+        let permissions_error = |message: String| FetchError::Permissions {
+            path: path.to_path_buf(),
+            message,
+        };
+
+        if let Some(mode) = self.mode {
+            fs::set_permissions(path, fs::Permissions::from_mode(mode))
+                .map_err(|err| permissions_error(err.to_string()))?;
+        }
+        if let Some(group) = &self.group {
+            let gid = match group.parse::<u32>() {
+                Ok(gid) => gid,
+                Err(_) => nix::unistd::Group::from_name(group)
+                    .map_err(|err| permissions_error(err.to_string()))?
+                    .ok_or_else(|| permissions_error(format!("no group named '{group}' exists")))?
+                    .gid
+                    .as_raw(),
+            };
+            chown(path, None, Some(gid)).map_err(|err| permissions_error(err.to_string()))?;
+        }
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    #[allow(clippy::unnecessary_wraps)]
+    fn apply_ownership(&self, path: &Path) -> Result<(), FetchError> {
+        if (self.mode.is_some() || self.group.is_some()) && !self.verbosity.is_quiet() {
+            eprintln!(
+                "Warning: file modes and group ownership are only supported on Unix; leaving '{}' as written.",
+                path.display()
+            );
+        }
+        Ok(())
+    }
+}
+
+/// How many HTTP redirects a single fetch follows before giving up, matching
+/// `reqwest`'s own default.
+const MAX_REDIRECTS: usize = 10;
+
+/// A file fetched from one of a dataset's sources.
+#[derive(Debug, Clone)]
+pub struct Fetched {
+    pub kind: FileKind,
+    pub url: String,
+    pub path: PathBuf,
+
+    /// Each URL an HTTP source redirected through, in order, ending with the
+    /// one the file was actually served from.
+    pub redirects: Vec<String>,
+
+    /// The `ETag` an HTTP source served the file with, if any.
+    pub etag: Option<String>,
+
+    /// When the file was fetched, or last written for one reused from an
+    /// earlier download, as an RFC 3339 timestamp.
+    pub downloaded_at: String,
+
+    /// The size of the file as fetched, before any decompression.
+    pub size: u64,
+
+    /// Whether the file was already in place from an earlier download, and so
+    /// wasn't fetched again.
+    pub reused: bool,
+
+    /// Whether this is a companion index of the `kind` file, e.g. its `.fai`,
+    /// rather than the file itself. See `companion_urls`.
+    pub companion: bool,
+
+    /// The SHA-256 digest the file is stored under in the shared cache, when it
+    /// was downloaded through one.
+    pub cache_key: Option<String>,
+}
+
+/// Where each of a dataset's files landed, keyed by field name, e.g. to hand
+/// them to the next tool without reconstructing their paths. Companion indexes
+/// are left out, and decompressed files are listed under their final name.
+pub fn landed_paths(fetched: &[Fetched]) -> Vec<(String, PathBuf)> {
+    fetched
+        .iter()
+        .filter(|file| !file.companion)
+        .map(|file| (file.kind.as_str().to_string(), file.path.clone()))
+        .collect()
+}
+
+/// Hand the path of every file in `fetched`, companions included, to
+/// `on_complete` once the download of the dataset labelled `label` has
+/// finished, e.g. to index them. The files are left in place when it fails,
+/// since they were downloaded and checked fine.
+pub fn run_on_complete(
+    label: &str,
+    fetched: &[Fetched],
+    on_complete: impl FnOnce(&[PathBuf]) -> Result<()>,
+) -> Result<()> {
+    let paths: Vec<PathBuf> = fetched.iter().map(|file| file.path.clone()).collect();
+    on_complete(&paths).with_context(|| {
+        format!("The post-download hook for '{label}' failed; its downloaded files were kept.")
+    })
+}
+
+/// The well-known companion files that may sit next to a file of `kind` at
+/// `url`: a `.fai` for FASTA, plus a `.gzi` when it is bgzipped, and a `.tbi`
+/// for VCF. Any query string stays at the end of each URL.
+pub fn companion_urls(kind: FileKind, url: &str) -> Vec<String> {
+    let (base, query) = url
+        .find(['?', '#'])
+        .map_or((url, ""), |at| url.split_at(at));
+    let suffixes: &[&str] = match kind {
+        FileKind::Fasta if is_gzip_name(base) => &["fai", "gzi"],
+        FileKind::Fasta => &["fai"],
+        FileKind::Vcf => &["tbi"],
+        _ => &[],
+    };
+    suffixes
+        .iter()
+        .map(|suffix| format!("{base}.{suffix}{query}"))
+        .collect()
+}
+
+/// Whether a file name or URL path carries a `.gz` or `.bgz` suffix.
+fn is_gzip_name(name: &str) -> bool {
+    Path::new(name).extension().is_some_and(|extension| {
+        extension.eq_ignore_ascii_case("gz") || extension.eq_ignore_ascii_case("bgz")
+    })
+}
+
+/// The size of the file at `url` when it can be learned without downloading it:
+/// the `Content-Length` of a HEAD request for HTTP sources, the server-reported
+/// size for FTP and S3 ones, or the file size for local ones.
+pub async fn known_size(network: &Network, url: &str) -> Option<u64> {
+    let url = &*expand_vars(url).ok()?;
+    match Scheme::of(url) {
+        Scheme::Http => {
+            let response = network.head(url).ok()?.send().await.ok()?;
+            if !response.status().is_success() {
+                return None;
+            }
+            response
+                .headers()
+                .get(reqwest::header::CONTENT_LENGTH)?
+                .to_str()
+                .ok()?
+                .parse()
+                .ok()
+        }
+        Scheme::Local => tokio::fs::metadata(local_path(url))
+            .await
+            .ok()
+            .map(|metadata| metadata.len()),
+        Scheme::Ftp => {
+            network.guard(url).ok()?;
+            ftp::size(url).await.ok()
+        }
+        Scheme::S3 => {
+            network.guard(url).ok()?;
+            s3::head(url).await.ok()
+        }
+        Scheme::Rsync => None,
+    }
+}
+
+/// Order pending transfers so the largest known files start first and smaller
+/// ones fill in around them, which keeps a limited number of download slots
+/// busy until the end. Files of unknown size go last, in their original order.
+pub fn largest_first<T>(mut pending: Vec<(T, Option<u64>)>) -> Vec<T> {
+    pending.sort_by_key(|(_, size)| (size.is_none(), std::cmp::Reverse(*size)));
+    pending.into_iter().map(|(item, _)| item).collect()
+}
+
+/// Progress is reported at most this often...
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// ...unless at least this many bytes were written since the last report.
+const PROGRESS_BYTES: u64 = 1024 * 1024;
+
+/// The bytes written so far across all of a dataset's files, reported over a
+/// channel at a throttled rate.
+#[derive(Debug)]
+struct Progress {
+    sender: UnboundedSender<(u64, Option<u64>)>,
+    total: Option<u64>,
+    state: Mutex<ProgressState>,
+}
+
+#[derive(Debug)]
+struct ProgressState {
+    downloaded: u64,
+    reported_bytes: u64,
+    reported_at: Instant,
+}
+
+impl Progress {
+    fn new(sender: UnboundedSender<(u64, Option<u64>)>, total: Option<u64>) -> Self {
+        Self {
+            sender,
+            total,
+            state: Mutex::new(ProgressState {
+                downloaded: 0,
+                reported_bytes: 0,
+                reported_at: Instant::now(),
+            }),
+        }
+    }
+
+    fn advance(&self, bytes: u64) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.downloaded += bytes;
+        if state.downloaded - state.reported_bytes >= PROGRESS_BYTES
+            || state.reported_at.elapsed() >= PROGRESS_INTERVAL
+        {
+            state.reported_bytes = state.downloaded;
+            state.reported_at = Instant::now();
+            // the receiving end going away only means nobody is watching anymore
+            let _ = self.sender.send((state.downloaded, self.total));
+        }
+    }
+
+    /// Take back bytes that were reported but will be written again, e.g. by a
+    /// retry. They aren't reported as such, only left out of the next report.
+    fn retract(&self, bytes: u64) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.downloaded = state.downloaded.saturating_sub(bytes);
+        state.reported_bytes = state.reported_bytes.min(state.downloaded);
+    }
+
+    /// Start accounting for one attempt at fetching a file.
+    fn attempt(&self) -> AttemptProgress<'_> {
+        AttemptProgress {
+            progress: self,
+            reported: AtomicU64::new(0),
+        }
+    }
+
+    /// Report completion, with the total being whatever was actually written.
+    fn finish(&self) {
+        let state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let _ = self.sender.send((state.downloaded, Some(state.downloaded)));
+    }
+}
+
+/// The bytes one attempt at fetching a file added to a dataset's `Progress`,
+/// which are taken back when the attempt fails so that the next attempt can
+/// count them again without going past the total.
+#[derive(Debug)]
+struct AttemptProgress<'a> {
+    progress: &'a Progress,
+    reported: AtomicU64,
+}
+
+impl AttemptProgress<'_> {
+    fn advance(&self, bytes: u64) {
+        self.reported.fetch_add(bytes, Ordering::Relaxed);
+        self.progress.advance(bytes);
+    }
+
+    /// Take back everything the attempt reported.
+    fn roll_back(self) {
+        self.progress.retract(self.reported.into_inner());
+    }
+}
+
+/// How many of a dataset's files are fetched at once unless configured
+/// otherwise.
+pub const DEFAULT_MAX_CONCURRENT_DOWNLOADS: usize = 4;
+
+/// How many times a file is retried after a transient failure unless configured
+/// otherwise.
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+
+/// The delay before the first retry unless configured otherwise.
+pub const DEFAULT_RETRY_BASE_DELAY_MS: u64 = 500;
+
+/// The longest a single backoff may last, however many retries came before.
+const MAX_RETRY_DELAY: Duration = Duration::from_mins(1);
+
+/// Download every file in `dataset` into `dest`, or the subdirectory of it that
+/// `layout` names, returning what was written. Up to `max_concurrent_downloads`
+/// files are fetched at once, and the first file to fail cancels the rest.
+#[tracing::instrument(skip_all, fields(label = %dataset.label, dest = %dest.display()))]
+pub async fn download_dataset(
+    dataset: &RefDataset,
+    dest: &Path,
+    options: &DownloadOptions,
+) -> Result<Vec<Fetched>, FetchError> {
+    let dest = &options.layout.dest_for(dest, &dataset.label);
+    options.prepare_dest(dest)?;
+    let files = dataset.files_with(&options.supplementary);
+    // custom headers, e.g. credentials, only go to the hosts of the sources
+    let hosts = files
+        .iter()
+        .map(|&(_, url)| url)
+        .chain(options.mirrors.values().flatten().map(String::as_str))
+        .filter_map(|url| host_of(&expand_vars(url).ok()?))
+        .collect::<Vec<_>>();
+    // redirects are followed by hand so that the chain can be recorded
+    let network = Network::without_redirects(options.offline, options.timeout())
+        .with_headers(options.headers.clone(), hosts);
+    check_file_names(&files, &options.file_names)?;
+    let mut pending = Vec::new();
+    for &(kind, url) in &files {
+        let size = known_size(&network, url).await;
+        pending.push(((kind, url.to_string(), size, false), size));
+    }
+    if options.fetch_indexes {
+        for &(kind, url) in &files {
+            if options.decompress && is_gzip_name(url) {
+                continue;
+            }
+            for companion in companion_urls(kind, url) {
+                // a companion registered in its own right is fetched already
+                if files.iter().any(|(_, registered)| *registered == companion) {
+                    continue;
+                }
+                if let Some(size) = known_size(&network, &companion).await {
+                    pending.push(((kind, companion, Some(size), true), Some(size)));
+                }
+            }
+        }
+    }
+    if !options.skip_space_check {
+        let files = pending
+            .iter()
+            .map(|((_, url, ..), size)| (url.as_str(), *size));
+        check_space(dest, files)?;
+    }
+    let total = pending.iter().map(|(_, size)| *size).sum();
+    let progress = options
+        .progress
+        .clone()
+        .map(|sender| Arc::new(Progress::new(sender, total)));
+
+    let limit = options.max_concurrent_downloads.max(1);
+    let options = Arc::new(options.clone());
+    // returning early drops the set, which aborts every transfer still running
+    let mut transfers = JoinSet::new();
+    let mut written = Vec::new();
+    for (kind, url, size, companion) in largest_first(pending) {
+        if transfers.len() >= limit {
+            if let Some(result) = transfers.join_next().await {
+                written.push(unwrap_transfer(result)?);
+            }
+        }
+        let (network, dest, options) = (network.clone(), dest.clone(), Arc::clone(&options));
+        if companion {
+            transfers.spawn(download_companion(
+                network,
+                kind,
+                url,
+                dest,
+                options,
+                progress.clone(),
+            ));
+        } else {
+            transfers.spawn(download_file(
+                network,
+                kind,
+                url,
+                size,
+                dest,
+                options,
+                progress.clone(),
+            ));
+        }
+    }
+    while let Some(result) = transfers.join_next().await {
+        written.push(unwrap_transfer(result)?);
+    }
+
+    written.sort_by_key(|fetched| (fetched.kind, fetched.companion));
+    if let Some(progress) = progress {
+        progress.finish();
+    }
+    Ok(written)
+}
+
+/// Like `download_dataset`, but hands `on_progress` the bytes written so far
+/// across the dataset and, when every file's size was known up front, the total.
+/// It is called at most every 100 ms or every MiB, and once more when the
+/// download completes. Unlike the transfers themselves, `on_progress` always
+/// runs on the calling task, and an error from it cancels the download.
+pub async fn download_dataset_with_progress(
+    dataset: &RefDataset,
+    dest: &Path,
+    options: &DownloadOptions,
+    mut on_progress: impl FnMut(u64, Option<u64>) -> Result<()>,
+) -> Result<Vec<Fetched>> {
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let options = DownloadOptions {
+        progress: Some(sender),
+        ..options.clone()
+    };
+    let download = download_dataset(dataset, dest, &options);
+    tokio::pin!(download);
+    let written = loop {
+        tokio::select! {
+            written = &mut download => break written?,
+            Some((downloaded, total)) = receiver.recv() => on_progress(downloaded, total)?,
+        }
+    };
+    while let Ok((downloaded, total)) = receiver.try_recv() {
+        on_progress(downloaded, total)?;
+    }
+    Ok(written)
+}
+
+/// Download every dataset in `project` into `dest`, laid out as `layout` says,
+/// carrying on past datasets that fail unless `fail_fast` is set, in which case
+/// the first failure is the last outcome returned. Each dataset is checked against the
+/// SHA-256 digests `metadata` holds for it, and its supplementary files are
+/// fetched along with it. With a flat layout, nothing is fetched if any two
+/// files would be written under the same name.
+pub async fn download_all(
+    project: &Project,
+    metadata: &Metadata,
+    dest: &Path,
+    options: &DownloadOptions,
+    fail_fast: bool,
+) -> Result<Vec<(String, Result<Vec<Fetched>, FetchError>)>, FetchError> {
+    if options.layout == Layout::Flat {
+        check_flat_layout(project, metadata, &options.file_names)?;
+    }
+    let mut outcomes = Vec::new();
+    for dataset in project.datasets() {
+        let meta = metadata.get(&dataset.label);
+        let options = DownloadOptions {
+            sha256: meta.map(DatasetMeta::sha256).unwrap_or_default(),
+            sizes: meta.map(DatasetMeta::sizes).unwrap_or_default(),
+            supplementary: meta.map(DatasetMeta::supplementary).unwrap_or_default(),
+            mirrors: meta.map(DatasetMeta::mirrors).unwrap_or_default(),
+            file_names: metadata.file_names(&dataset.label, &options.file_names),
+            ..options.clone()
+        };
+        let outcome = download_dataset(dataset, dest, &options).await;
+        let failed = outcome.is_err();
+        outcomes.push((dataset.label.clone(), outcome));
+        if failed && fail_fast {
+            break;
+        }
+    }
+    Ok(outcomes)
+}
+
+/// Check that the filesystem `dest` is on has room for `files`, given as
+/// `(url, size)` pairs, before anything is written. Only sizes known up front
+/// count, less whatever is already in place under each file's name, so the
+/// check can pass for a download that later runs out of space, e.g. when files
+/// are decompressed.
+fn check_space<'a>(
+    dest: &Path,
+    files: impl IntoIterator<Item = (&'a str, Option<u64>)>,
+) -> Result<(), FetchError> {
+    let needed: u64 = files
+        .into_iter()
+        .filter_map(|(url, size)| {
+            let existing = target_name(url)
+                .ok()
+                .and_then(|name| fs::metadata(dest.join(name)).ok())
+                .map_or(0, |metadata| metadata.len());
+            Some(size?.saturating_sub(existing))
+        })
+        .sum();
+    let available = fs2::available_space(dest).map_err(|source| FetchError::Io {
+        path: dest.to_path_buf(),
+        source,
+    })?;
+    if needed > available {
+        return Err(FetchError::InsufficientSpace {
+            path: dest.to_path_buf(),
+            needed,
+            available,
+        });
+    }
+    Ok(())
+}
+
+/// Fetch a single file of a dataset and run the checks `options` ask for. When
+/// the file has mirrors, each is tried in turn after the registered source
+/// fails, and the download only fails once every one of them has.
+async fn download_file(
+    network: Network,
+    kind: FileKind,
+    url: String,
+    size: Option<u64>,
+    dest: PathBuf,
+    options: Arc<DownloadOptions>,
+    progress: Option<Arc<Progress>>,
+) -> Result<Fetched, FetchError> {
+    let target = dest.join(file_name_for(&options.file_names, kind, &url)?);
+    if !options.force {
+        let recorded = options.sizes.get(&kind).copied();
+        if let Some(fetched) = already_present(kind, &url, &target, recorded, size).await? {
+            tracing::info!(%kind, path = %fetched.path.display(), "kept file from an earlier download");
+            // the file was checked when it was first fetched
+            if let Some(progress) = &progress {
+                progress.advance(fetched.size);
+            }
+            options.report(&fetched);
+            return Ok(fetched);
+        }
+    }
+    if let Some(fetched) = options.link_cached(kind, &url, &target)? {
+        tracing::info!(%kind, path = %fetched.path.display(), "linked file from the cache");
+        if let Some(progress) = &progress {
+            progress.advance(fetched.size);
+        }
+        options.report(&fetched);
+        return Ok(fetched);
+    }
+    tracing::debug!(%kind, url = %redact(&url), ?size, "fetching");
+    let mirrors = options.mirrors.get(&kind).map_or(&[][..], Vec::as_slice);
+    let mut failures = Vec::new();
+    for source in iter::once(&url).chain(mirrors) {
+        let progress = progress.as_deref();
+        match fetch_checked(&network, kind, source, &target, &options, progress).await {
+            Ok(fetched) => {
+                let fetched = if options.decompress {
+                    decompress(fetched)?
+                } else {
+                    options.cache_fetched(fetched)
+                };
+                options.apply_ownership(&fetched.path)?;
+                options.report(&fetched);
+                return Ok(fetched);
+            }
+            Err(err) if mirrors.is_empty() => return Err(err),
+            Err(err) => {
+                tracing::warn!(%kind, url = %redact(source), error = %err, "source failed");
+                failures.push((source.clone(), err.to_string()));
+            }
+        }
+    }
+    Err(FetchError::MirrorsExhausted { kind, failures })
+}
+
+/// Fetch `source`, which is either the registered URL of a dataset's `kind`
+/// file or one of its mirrors, and check it the way `options` ask. The file is
+/// written straight to `target` even when the source's own name differs, e.g.
+/// for a mirror or a renamed file, so that it lands where later downloads and
+/// the sentinel expect it without passing through a name another file uses.
+async fn fetch_checked(
+    network: &Network,
+    kind: FileKind,
+    source: &str,
+    target: &Path,
+    options: &DownloadOptions,
+    progress: Option<&Progress>,
+) -> Result<Fetched, FetchError> {
+    let fetched = fetch_with_retries(network, kind, source, target, options, progress).await?;
+    tracing::info!(%kind, path = %fetched.path.display(), bytes = fetched.size, "fetched");
+    let checked = check_fetched(network, kind, source, &fetched.path, options).await;
+    if let (Err(_), Some(progress)) = (&checked, progress) {
+        // the next mirror, if there is one, counts the file again
+        progress.retract(fetched.size);
+    }
+    checked.map(|()| fetched)
+}
+
+/// Run the checks `options` ask for on the `kind` file fetched from `source`
+/// to `path`.
+async fn check_fetched(
+    network: &Network,
+    kind: FileKind,
+    source: &str,
+    path: &Path,
+    options: &DownloadOptions,
+) -> Result<(), FetchError> {
+    // a file that fails its checks, or whose checks are cancelled, is as
+    // untrustworthy as a truncated one
+    let in_progress = InProgress::new(path);
+    options.check_size(kind, path)?;
+    if let Some(expected) = options.sha256.get(&kind) {
+        verify_digest(path, Algorithm::Sha256, expected)?;
+    }
+    if options.auto_verify || options.require_checksum {
+        verify_published(network, source, path, options).await?;
+    }
+    in_progress.complete();
+    Ok(())
+}
+
+/// Fetch a companion index of a dataset's `kind` file. Companions carry no
+/// registered digest or size limits, so only ownership is applied, and they are
+/// never decompressed.
+async fn download_companion(
+    network: Network,
+    kind: FileKind,
+    url: String,
+    dest: PathBuf,
+    options: Arc<DownloadOptions>,
+    progress: Option<Arc<Progress>>,
+) -> Result<Fetched, FetchError> {
+    tracing::debug!(%kind, url = %redact(&url), "fetching companion");
+    // a companion follows its file's custom name, e.g. `genome.fa.fai`, so that
+    // tools still find it next to the file
+    let own_name = target_name(&url)?;
+    let extension = Path::new(own_name)
+        .extension()
+        .map(|ext| ext.to_string_lossy());
+    let target = match (options.file_names.get(&kind), extension) {
+        (Some(name), Some(extension)) => dest.join(format!("{name}.{extension}")),
+        _ => dest.join(own_name),
+    };
+    let mut fetched =
+        fetch_with_retries(&network, kind, &url, &target, &options, progress.as_deref()).await?;
+    options.apply_ownership(&fetched.path)?;
+    fetched.companion = true;
+    options.report(&fetched);
+    Ok(fetched)
+}
+
+/// Whether a failed fetch is worth trying again: dropped connections, timeouts,
+/// rate limiting, server errors, and transfers cut short. Anything else, e.g. a
+/// 404, would fail the same way again.
+fn is_transient(err: &FetchError) -> bool {
+    match err {
+        // a body cut off mid-transfer surfaces as a decode error
+        FetchError::Http { source, .. } => {
+            source.is_timeout()
+                || source.is_connect()
+                || source.is_request()
+                || source.is_body()
+                || source.is_decode()
+        }
+        FetchError::Status { status, .. } => *status == 429 || (500..600).contains(status),
+        FetchError::Incomplete { .. } | FetchError::Timeout { .. } => true,
+        _ => false,
+    }
+}
+
+/// How long to wait before retry number `retry`, counting from zero: the base
+/// delay doubled for each earlier retry, capped, with up to half of it replaced
+/// by random jitter so that concurrent downloads don't retry in lockstep.
+fn backoff(base_delay_ms: u64, retry: u32) -> Duration {
+    let delay = Duration::from_millis(base_delay_ms.saturating_mul(1 << retry.min(16)))
+        .min(MAX_RETRY_DELAY);
+    let jitter = RandomState::new().build_hasher().finish() % 1000;
+    delay / 2 + delay / 2 * u32::try_from(jitter).unwrap_or(0) / 1000
+}
+
+/// Fetch a file within the configured timeout, which applies to each attempt.
+async fn fetch_within_timeout(
+    network: &Network,
+    kind: FileKind,
+    url: &str,
+    target: &Path,
+    options: &DownloadOptions,
+    progress: Option<&AttemptProgress<'_>>,
+) -> Result<Fetched, FetchError> {
+    let Some(timeout) = options.timeout() else {
+        return fetch(network, kind, url, target, options, progress).await;
+    };
+    let timed_out = || FetchError::Timeout {
+        url: url.to_string(),
+        seconds: timeout.as_secs(),
+    };
+    // dropping the transfer on expiry also stops any rsync or aws child process
+    let transfer = fetch(network, kind, url, target, options, progress);
+    match tokio::time::timeout(timeout, transfer).await {
+        Err(_) => Err(timed_out()),
+        Ok(Err(FetchError::Http { source, .. })) if source.is_timeout() => Err(timed_out()),
+        Ok(outcome) => outcome,
+    }
+}
+
+/// Fetch a file, retrying transient failures with exponential backoff. An
+/// interrupted HTTP transfer is continued rather than restarted when `resume` is
+/// set.
+async fn fetch_with_retries(
+    network: &Network,
+    kind: FileKind,
+    url: &str,
+    target: &Path,
+    options: &DownloadOptions,
+    progress: Option<&Progress>,
+) -> Result<Fetched, FetchError> {
+    let mut retry = 0;
+    loop {
+        let attempt = progress.map(Progress::attempt);
+        let outcome =
+            fetch_within_timeout(network, kind, url, target, options, attempt.as_ref()).await;
+        if let (Err(_), Some(attempt)) = (&outcome, attempt) {
+            // whatever the attempt reported is reported again by the next one,
+            // whether it resumes the transfer or starts over
+            attempt.roll_back();
+        }
+        match outcome {
+            Err(err) if is_transient(&err) => {
+                if retry == options.max_retries {
+                    return Err(if retry == 0 {
+                        err
+                    } else {
+                        FetchError::RetriesExhausted {
+                            url: url.to_string(),
+                            attempts: retry + 1,
+                            source: Box::new(err),
+                        }
+                    });
+                }
+                let delay = backoff(options.retry_base_delay_ms, retry);
+                tracing::warn!(
+                    url = %redact(url),
+                    attempt = retry + 1,
+                    delay_ms = delay.as_millis(),
+                    "transient failure, retrying"
+                );
+                tokio::time::sleep(delay).await;
+                retry += 1;
+            }
+            outcome => return outcome,
+        }
+    }
+}
+
+/// The magic bytes every gzip member, and so every bgzip block, starts with.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Replace a fetched gzip or bgzip file with its decompressed contents, named
+/// without the `.gz` or `.bgz` suffix. Files that aren't gzipped are returned
+/// untouched.
+fn decompress(mut fetched: Fetched) -> Result<Fetched, FetchError> {
+    let io_error = |path: &Path| {
+        let path = path.to_path_buf();
+        move |source: io::Error| FetchError::Io { path, source }
+    };
+    let compressed = fetched.path.clone();
+    let mut magic = [0; 2];
+    let mut file = fs::File::open(&compressed).map_err(io_error(&compressed))?;
+    if file.read_exact(&mut magic).is_err() || magic != GZIP_MAGIC {
+        return Ok(fetched);
+    }
+    file.rewind().map_err(io_error(&compressed))?;
+
+    let decompressed = match compressed.extension() {
+        Some(extension) if extension == "gz" || extension == "bgz" => compressed.with_extension(""),
+        _ => compressed.clone(),
+    };
+    let mut scratch = decompressed.as_os_str().to_owned();
+    scratch.push(".decompressing");
+    let scratch = PathBuf::from(scratch);
+
+    // bgzip files are a series of gzip members, all of which must be read
+    let mut decoder = MultiGzDecoder::new(io::BufReader::new(file));
+    let mut output = io::BufWriter::new(fs::File::create(&scratch).map_err(io_error(&scratch))?);
+    if let Err(source) = io::copy(&mut decoder, &mut output).and_then(|_| output.flush()) {
+        drop(output);
+        let _ = fs::remove_file(&scratch);
+        return Err(FetchError::DecompressionFailed {
+            path: compressed,
+            message: source.to_string(),
+        });
+    }
+    drop(output);
+    fs::rename(&scratch, &decompressed).map_err(io_error(&decompressed))?;
+    if decompressed != compressed {
+        fs::remove_file(&compressed).map_err(io_error(&compressed))?;
+    }
+
+    fetched.path = decompressed;
+    Ok(fetched)
+}
+
+/// The file an earlier download of `url` left at `path`, if it still has the
+/// `recorded` size from then and the source doesn't now report a different one.
+async fn already_present(
+    kind: FileKind,
+    url: &str,
+    path: &Path,
+    recorded: Option<u64>,
+    reported: Option<u64>,
+) -> Result<Option<Fetched>, FetchError> {
+    let Some(recorded) = recorded else {
+        return Ok(None);
+    };
+    if reported.is_some_and(|reported| reported != recorded) {
+        return Ok(None);
+    }
+    let path = path.to_path_buf();
+    let Ok(metadata) = tokio::fs::metadata(&path).await else {
+        return Ok(None);
+    };
+    if metadata.len() != recorded {
+        return Ok(None);
+    }
+
+    let fetched_at = metadata
+        .modified()
+        .map_or_else(|_| Utc::now(), chrono::DateTime::<Utc>::from);
+    Ok(Some(Fetched {
+        kind,
+        url: url.to_string(),
+        path,
+        redirects: Vec::new(),
+        etag: None,
+        downloaded_at: fetched_at.to_rfc3339(),
+        size: recorded,
+        reused: true,
+        companion: false,
+        cache_key: None,
+    }))
+}
+
+/// How many sources `verify_sources` checks at once unless configured otherwise.
+pub const DEFAULT_MAX_CONCURRENT_CHECKS: usize = 16;
+
+/// A registered source alongside why it can't currently be fetched, if it can't.
+pub type SourceStatus = (FileKind, Option<String>);
+
+/// Check every source of every dataset in `project`, including the
+/// supplementary files `metadata` holds, without downloading any of them, up to
+/// `limit` at once, returning the status of each dataset's files in registry
+/// order. See `unavailable_reason` for how each scheme is checked.
+pub async fn verify_sources(
+    network: &Network,
+    project: &Project,
+    metadata: &Metadata,
+    limit: usize,
+) -> Vec<(String, Vec<SourceStatus>)> {
+    let datasets = project.datasets();
+    let mut report: Vec<(String, Vec<SourceStatus>)> = datasets
+        .iter()
+        .map(|dataset| (dataset.label.clone(), Vec::new()))
+        .collect();
+    let mut record = |result: Result<(usize, SourceStatus), tokio::task::JoinError>| {
+        let (index, status) =
+            result.unwrap_or_else(|err| std::panic::resume_unwind(err.into_panic()));
+        report[index].1.push(status);
+    };
+
+    let mut checks = JoinSet::new();
+    for (index, dataset) in datasets.iter().enumerate() {
+        let supplementary = metadata.supplementary(&dataset.label);
+        for (kind, url) in dataset.files_with(&supplementary) {
+            if checks.len() >= limit.max(1) {
+                if let Some(result) = checks.join_next().await {
+                    record(result);
+                }
+            }
+            let (network, url) = (network.clone(), url.to_string());
+            checks.spawn(async move {
+                let reason = unavailable_reason(&network, &url)
+                    .await
+                    .unwrap_or_else(|err| Some(err.to_string()));
+                (index, (kind, reason))
+            });
+        }
+    }
+    while let Some(result) = checks.join_next().await {
+        record(result);
+    }
+
+    for (_, statuses) in &mut report {
+        statuses.sort_by_key(|(kind, _)| *kind);
+    }
+    report
+}
+
+/// The combined size of a set of sources, as far as they report it.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct DownloadSize {
+    /// The sum of every size that could be learned.
+    pub known: u64,
+
+    /// Sources whose size couldn't be learned without downloading them, e.g.
+    /// `rsync` ones or HTTP servers that send no `Content-Length`.
+    pub unknown: Vec<String>,
+}
+
+/// Add up the sizes `known_size` reports for `urls`, checking up to `limit` at
+/// once. Sources that report no size are listed rather than counted as empty.
+pub async fn total_size(network: &Network, urls: Vec<String>, limit: usize) -> DownloadSize {
+    let mut total = DownloadSize::default();
+    let mut record = |result: Result<(String, Option<u64>), tokio::task::JoinError>| {
+        let (url, size) = result.unwrap_or_else(|err| std::panic::resume_unwind(err.into_panic()));
+        match size {
+            Some(size) => total.known += size,
+            None => total.unknown.push(url),
+        }
+    };
+
+    let mut checks = JoinSet::new();
+    for url in urls {
+        if checks.len() >= limit.max(1) {
+            if let Some(result) = checks.join_next().await {
+                record(result);
+            }
+        }
+        let network = network.clone();
+        checks.spawn(async move {
+            let size = known_size(&network, &url).await;
+            (url, size)
+        });
+    }
+    while let Some(result) = checks.join_next().await {
+        record(result);
+    }
+
+    total.unknown.sort();
+    total
+}
+
+/// The outcome of a finished transfer task, re-raising its panic if it had one.
+fn unwrap_transfer(
+    result: Result<Result<Fetched, FetchError>, tokio::task::JoinError>,
+) -> Result<Fetched, FetchError> {
+    result.unwrap_or_else(|err| std::panic::resume_unwind(err.into_panic()))
+}
+
+/// Fetch a single source to the file `target` with the transfer mechanism its
+/// scheme calls for, after expanding any environment variables it refers to.
+/// Remote sources are refused outright in offline mode.
+async fn fetch(
+    network: &Network,
+    kind: FileKind,
+    url: &str,
+    target: &Path,
+    options: &DownloadOptions,
+    progress: Option<&AttemptProgress<'_>>,
+) -> Result<Fetched, FetchError> {
+    let target = target.to_path_buf();
+    let io_error = |source| FetchError::Io {
+        path: target.clone(),
+        source,
+    };
+
+    // the registered form is kept for naming and provenance, so that records
+    // match the registry on every machine
+    let source = expand_vars(url)?;
+    // HTTP sources only reach the target once complete, and rsync keeps its
+    // partial files aside, but the other transfers write the target in place
+    let in_progress = match Scheme::of(&source) {
+        Scheme::Http | Scheme::Rsync => None,
+        Scheme::Local if local_path(&source) == target => None,
+        Scheme::Ftp | Scheme::S3 | Scheme::Local => Some(InProgress::new(&target)),
+    };
+    let (redirects, etag) = match Scheme::of(&source) {
+        Scheme::Http => fetch_http(network, &source, &target, options.resume, progress).await?,
+        Scheme::Rsync => {
+            network.guard(&source)?;
+            fetch_rsync(&source, &target).await?;
+            if let Some(progress) = progress {
+                let size = tokio::fs::metadata(&target).await.map_err(io_error)?.len();
+                progress.advance(size);
+            }
+            (Vec::new(), None)
+        }
+        Scheme::Ftp => {
+            network.guard(&source)?;
+            let size = ftp::fetch(&source, &target).await?;
+            if let Some(progress) = progress {
+                progress.advance(size);
+            }
+            (Vec::new(), None)
+        }
+        Scheme::S3 => {
+            network.guard(&source)?;
+            s3::fetch(&source, &target).await?;
+            if let Some(progress) = progress {
+                let size = tokio::fs::metadata(&target).await.map_err(io_error)?.len();
+                progress.advance(size);
+            }
+            (Vec::new(), None)
+        }
+        Scheme::Local => {
+            let size = copy_local(local_path(&source), &target, options)
+                .await
+                .map_err(io_error)?;
+            if let Some(progress) = progress {
+                progress.advance(size);
+            }
+            (Vec::new(), None)
+        }
+    };
+    let size = tokio::fs::metadata(&target).await.map_err(io_error)?.len();
+    if let Some(in_progress) = in_progress {
+        in_progress.complete();
+    }
+
+    Ok(Fetched {
+        kind,
+        url: url.to_string(),
+        path: target,
+        redirects,
+        etag,
+        downloaded_at: Utc::now().to_rfc3339(),
+        size,
+        reused: false,
+        companion: false,
+        cache_key: None,
+    })
+}
+
+/// Copy a local source to `target`, or hard-link it when `options` allow,
+/// returning its size.
+async fn copy_local(
+    source: &Path,
+    target: &Path,
+    options: &DownloadOptions,
+) -> std::io::Result<u64> {
+    if options.link_local && options.mode.is_none() && options.group.is_none() {
+        // a link can't replace an existing file the way a copy does
+        if tokio::fs::symlink_metadata(target).await.is_ok() {
+            tokio::fs::remove_file(target).await?;
+        }
+        // linking across filesystems fails, in which case the file is copied
+        if tokio::fs::hard_link(source, target).await.is_ok() {
+            return Ok(tokio::fs::metadata(target).await?.len());
+        }
+    }
+    tokio::fs::copy(source, target).await
+}
+
+/// A file being written under its final name, which is removed again unless it
+/// is marked complete. Dropping it unmarked, whether because the transfer
+/// failed or because its future was dropped when `async_runner` saw a Ctrl+C,
+/// keeps a truncated file from being mistaken for a complete one later.
+struct InProgress {
+    path: Option<PathBuf>,
+}
+
+impl InProgress {
+    fn new(path: &Path) -> Self {
+        Self {
+            path: Some(path.to_path_buf()),
+        }
+    }
+
+    /// Keep the file.
+    fn complete(mut self) {
+        self.path = None;
+    }
+}
+
+impl Drop for InProgress {
+    fn drop(&mut self) {
+        if let Some(path) = self.path.take() {
+            if fs::remove_file(&path).is_ok() {
+                tracing::info!(path = %path.display(), "removed incomplete file");
+            }
+        }
+    }
+}
+
+/// Where an HTTP source is streamed to until it is complete, so that an
+/// interrupted transfer never leaves a truncated file under the final name.
+fn partial_path(target: &Path) -> PathBuf {
+    let mut name = target.as_os_str().to_owned();
+    name.push(".part");
+    PathBuf::from(name)
+}
+
+/// Request `url`, asking for everything from byte `offset` onward when it is
+/// non-zero, and follow any redirects by hand.
+async fn send_following_redirects(
+    network: &Network,
+    url: &str,
+    offset: u64,
+) -> Result<(reqwest::Response, Vec<String>), FetchError> {
+    let http_error = |source| FetchError::Http {
+        url: url.to_string(),
+        source,
+    };
+    let get = |url: &str| {
+        let request = network.get(url)?;
+        Ok::<_, FetchError>(if offset > 0 {
+            request.header(reqwest::header::RANGE, format!("bytes={offset}-"))
+        } else {
+            request
+        })
+    };
+
+    let mut redirects = Vec::new();
+    let mut response = get(url)?.send().await.map_err(http_error)?;
+    while response.status().is_redirection() && redirects.len() < MAX_REDIRECTS {
+        let Some(next) = response
+            .headers()
+            .get(reqwest::header::LOCATION)
+            .and_then(|location| location.to_str().ok())
+            .and_then(|location| response.url().join(location).ok())
+        else {
+            break;
+        };
+        redirects.push(next.to_string());
+        response = get(next.as_str())?.send().await.map_err(http_error)?;
+    }
+    Ok((response, redirects))
+}
+
+/// Stream an HTTP source to `target`, returning the redirects followed along
+/// the way and the `ETag` the file was served with. The body is written chunk
+/// by chunk as it arrives, so memory use doesn't grow with the size of the
+/// file. With `resume`, a partial
+/// file left by an interrupted run is continued with a `Range` request when the
+/// server supports it, and restarted from scratch when it doesn't.
+async fn fetch_http(
+    network: &Network,
+    url: &str,
+    target: &Path,
+    resume: bool,
+    progress: Option<&AttemptProgress<'_>>,
+) -> Result<(Vec<String>, Option<String>), FetchError> {
+    let http_error = |source| FetchError::Http {
+        url: url.to_string(),
+        source,
+    };
+    let partial = partial_path(target);
+    let io_error = |source| FetchError::Io {
+        path: partial.clone(),
+        source,
+    };
+
+    let mut offset = if resume {
+        tokio::fs::metadata(&partial)
+            .await
+            .map_or(0, |metadata| metadata.len())
+    } else {
+        0
+    };
+    let (mut response, mut redirects) = send_following_redirects(network, url, offset).await?;
+    if offset > 0 && response.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+        // the partial file is no shorter than the source, so it can't be a
+        // prefix of it and is fetched again in full
+        offset = 0;
+        (response, redirects) = send_following_redirects(network, url, offset).await?;
+    }
+    let status = response.status();
+    if matches!(
+        status,
+        reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN
+    ) {
+        return Err(FetchError::Unauthorized {
+            url: url.to_string(),
+            status: status.as_u16(),
+        });
+    }
+    if !status.is_success() {
+        return Err(FetchError::Status {
+            url: url.to_string(),
+            status: status.as_u16(),
+        });
+    }
+
+    let etag = response
+        .headers()
+        .get(reqwest::header::ETAG)
+        .and_then(|etag| etag.to_str().ok())
+        .map(str::to_string);
+
+    // a server that ignores the range sends the whole file, which replaces
+    // whatever was there
+    let appending = offset > 0 && status == reqwest::StatusCode::PARTIAL_CONTENT;
+    let expected = response
+        .content_length()
+        .map(|length| if appending { offset + length } else { length });
+    let mut file = if appending {
+        tokio::fs::OpenOptions::new()
+            .append(true)
+            .open(&partial)
+            .await
+    } else {
+        tokio::fs::File::create(&partial).await
+    }
+    .map_err(io_error)?;
+    if let (true, Some(progress)) = (appending, progress) {
+        progress.advance(offset);
+    }
+    while let Some(chunk) = response.chunk().await.map_err(http_error)? {
+        file.write_all(&chunk).await.map_err(io_error)?;
+        if let Some(progress) = progress {
+            progress.advance(chunk.len() as u64);
+        }
+    }
+    file.flush().await.map_err(io_error)?;
+
+    let actual = file.metadata().await.map_err(io_error)?.len();
+    drop(file);
+    if let Some(expected) = expected.filter(|&expected| expected != actual) {
+        return Err(FetchError::Incomplete {
+            path: partial,
+            expected,
+            actual,
+        });
+    }
+    tokio::fs::rename(&partial, target)
+        .await
+        .map_err(|source| FetchError::Io {
+            path: target.to_path_buf(),
+            source,
+        })?;
+
+    Ok((redirects, etag))
+}
+
+async fn fetch_rsync(url: &str, target: &Path) -> Result<(), FetchError> {
+    let mut command = Command::new("rsync");
+    command
+        // an interrupted transfer is kept aside for the next attempt to resume,
+        // rather than left under the target's name
+        .arg("--partial-dir=.rsync-partial")
+        .arg("--times")
+        .stdin(Stdio::null())
+        .stderr(Stdio::piped())
+        // dropping the child, e.g. when `async_runner` sees a Ctrl+C, should stop
+        // the transfer rather than leave rsync running in the background
+        .kill_on_drop(true);
+    if std::io::stdout().is_terminal() {
+        command.arg("--progress").stdout(Stdio::inherit());
+    } else {
+        command.arg("--quiet").stdout(Stdio::null());
+    }
+    command.arg(url).arg(target);
+
+    let output = command.output().await.map_err(|source| {
+        if source.kind() == std::io::ErrorKind::NotFound {
+            FetchError::RsyncMissing {
+                url: url.to_string(),
+            }
+        } else {
+            FetchError::Rsync {
+                url: url.to_string(),
+                message: source.to_string(),
+            }
+        }
+    })?;
+
+    if !output.status.success() {
+        return Err(FetchError::Rsync {
+            url: url.to_string(),
+            message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Look for a checksum the provider publishes next to `url`, trying a `.sha256`
+/// file before an `.md5` one.
+async fn published_checksum(network: &Network, url: &str) -> Option<(Algorithm, String)> {
+    let url = &*expand_vars(url).ok()?;
+    for algorithm in [Algorithm::Sha256, Algorithm::Md5] {
+        let sidecar = format!("{url}.{}", algorithm.as_str());
+        let contents = match Scheme::of(url) {
+            Scheme::Http => {
+                let Ok(request) = network.get(&sidecar) else {
+                    continue;
+                };
+                let Ok(response) = request.send().await else {
+                    continue;
+                };
+                if !response.status().is_success() {
+                    continue;
+                }
+                response.text().await.ok()
+            }
+            Scheme::Local => tokio::fs::read_to_string(local_path(&sidecar)).await.ok(),
+            // a missing sidecar isn't distinguishable from a failed transfer
+            // without shelling out per candidate, so these sources aren't probed
+            Scheme::Ftp | Scheme::Rsync | Scheme::S3 => None,
+        };
+        if let Some(digest) = contents.and_then(|contents| algorithm.parse_digest(&contents)) {
+            return Some((algorithm, digest));
+        }
+    }
+    None
+}
+
+/// Check a downloaded file against the checksum published next to its source.
+/// A source without one is skipped with a warning unless a checksum is required.
+async fn verify_published(
+    network: &Network,
+    url: &str,
+    path: &Path,
+    options: &DownloadOptions,
+) -> Result<(), FetchError> {
+    let Some((algorithm, expected)) = published_checksum(network, url).await else {
+        if options.require_checksum {
+            return Err(FetchError::ChecksumMissing {
+                url: url.to_string(),
+            });
+        }
+        if !options.verbosity.is_quiet() {
+            eprintln!(
+                "Warning: no published checksum was found for '{url}'; skipping verification."
+            );
+        }
+        return Ok(());
+    };
+    verify_digest(path, algorithm, &expected)
+}
+
+/// Check a downloaded file against an expected hex digest.
+fn verify_digest(path: &Path, algorithm: Algorithm, expected: &str) -> Result<(), FetchError> {
+    let actual = algorithm.hash_file(path).map_err(|source| FetchError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    if actual != expected {
+        return Err(FetchError::ChecksumMismatch {
+            path: path.to_path_buf(),
+            algorithm: algorithm.as_str(),
+            expected: expected.to_string(),
+            actual,
+        });
+    }
+    Ok(())
+}
+
+/// Confirm that every downloaded file exists and is non-empty.
+pub fn verify_written(files: &[PathBuf]) -> Result<()> {
+    for file in files {
+        let metadata = fs::metadata(file).with_context(|| {
+            format!("Expected downloaded file '{}' is missing.", file.display())
+        })?;
+        if metadata.len() == 0 {
+            bail!("Downloaded file '{}' is empty.", file.display());
+        }
+    }
+    Ok(())
+}
+
+/// The outcome of moving a dataset's downloaded files between directories.
+#[derive(Debug, Default)]
+pub struct Relocation {
+    pub moved: Vec<PathBuf>,
+    pub missing: Vec<PathBuf>,
+}
+
+/// Move the downloaded files for `dataset` and its `supplementary` files, named
+/// as `file_names` say, from `from_dir` to `to_dir`, along with its sentinel if
+/// there is one. Files are renamed where possible; files
+/// that can't be renamed, e.g. because the directories are on different
+/// filesystems, are copied, and their originals are only deleted once every copy
+/// has succeeded. Files that were never downloaded to `from_dir` are reported
+/// rather than treated as an error.
+pub fn relocate_files(
+    dataset: &RefDataset,
+    supplementary: &[(FileKind, String)],
+    file_names: &HashMap<FileKind, String>,
+    from_dir: &Path,
+    to_dir: &Path,
+) -> Result<Relocation> {
+    fs::create_dir_all(to_dir)
+        .with_context(|| format!("Failed to create directory '{}'.", to_dir.display()))?;
+
+    let mut relocation = Relocation::default();
+    let mut copied = Vec::new();
+    for (_, source) in plan_download(dataset, supplementary, file_names, from_dir)? {
+        let target = to_dir.join(source.file_name().unwrap_or_default());
+        if !source.exists() {
+            relocation.missing.push(source);
+            continue;
+        }
+        if fs::rename(&source, &target).is_err() {
+            if let Err(err) = fs::copy(&source, &target) {
+                // roll back the copies made so far so that no file ends up in
+                // both places
+                for (_, partial) in &copied {
+                    let _ = fs::remove_file(partial);
+                }
+                let _ = fs::remove_file(&target);
+                return Err(err).with_context(|| {
+                    format!(
+                        "Failed to copy '{}' to '{}'.",
+                        source.display(),
+                        target.display()
+                    )
+                });
+            }
+            copied.push((source, target.clone()));
+        }
+        relocation.moved.push(target);
+    }
+
+    for (original, _) in copied {
+        fs::remove_file(&original)
+            .with_context(|| format!("Failed to remove original '{}'.", original.display()))?;
+    }
+
+    if let Some(mut sentinel) = Sentinel::read(from_dir, &dataset.label)? {
+        for file in &mut sentinel.files {
+            if let Ok(relative) = file.strip_prefix(from_dir) {
+                *file = to_dir.join(relative);
+            }
+        }
+        sentinel.write(to_dir)?;
+        fs::remove_file(Sentinel::path(from_dir, &dataset.label))?;
+    }
+
+    Ok(relocation)
+}
+
+/// The suffixes of the indexes that may sit next to a downloaded file, whether
+/// they were fetched alongside it or built afterwards.
+const INDEX_SUFFIXES: [&str; 4] = ["fai", "gzi", "tbi", "csi"];
+
+/// Every path a file downloaded to `target` may have left behind: the file
+/// itself, its decompressed form if it was gzipped, and the indexes of either.
+fn left_behind(target: &Path) -> Vec<PathBuf> {
+    let mut files = vec![target.to_path_buf()];
+    if is_gzip_name(&target.to_string_lossy()) {
+        files.push(target.with_extension(""));
+    }
+    let indexes: Vec<PathBuf> = files
+        .iter()
+        .flat_map(|file| {
+            INDEX_SUFFIXES.iter().map(move |suffix| {
+                let mut index = file.clone().into_os_string();
+                index.push(format!(".{suffix}"));
+                PathBuf::from(index)
+            })
+        })
+        .collect();
+    files.extend(indexes);
+    files
+}
+
+/// Delete what a download of `dataset` and its `supplementary` files into
+/// `dest` wrote: each file under the name `file_names` gives it, its
+/// decompressed form and indexes, and the dataset's sentinel and provenance
+/// record. Nothing else in `dest` is touched, and files that are already gone
+/// are skipped. Returns the paths that were deleted.
+pub fn delete_files(
+    dataset: &RefDataset,
+    supplementary: &[(FileKind, String)],
+    file_names: &HashMap<FileKind, String>,
+    dest: &Path,
+) -> Result<Vec<PathBuf>> {
+    let mut candidates = vec![
+        Sentinel::path(dest, &dataset.label),
+        Provenance::path(dest, &dataset.label),
+    ];
+    for (_, target) in plan_download(dataset, supplementary, file_names, dest)? {
+        candidates.extend(left_behind(&target));
+    }
+
+    let mut deleted = Vec::new();
+    for path in candidates {
+        match fs::remove_file(&path) {
+            Ok(()) => deleted.push(path),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => {
+                return Err(err).with_context(|| format!("Failed to delete '{}'.", path.display()));
+            }
+        }
+    }
+    Ok(deleted)
+}
+
+/// How a dataset file compares between two download directories.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileComparison {
+    Identical,
+    Differs,
+    MissingInA,
+    MissingInB,
+}
+
+impl FileComparison {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FileComparison::Identical => "identical",
+            FileComparison::Differs => "differs",
+            FileComparison::MissingInA => "missing_in_a",
+            FileComparison::MissingInB => "missing_in_b",
+        }
+    }
+}
+
+/// Compare the downloaded files for `dataset` and its `supplementary` files,
+/// named as `file_names` say, in `dir_a` against those in `dir_b`, e.g. to
+/// check a mirror against a trusted source. Files are compared
+/// by size first and only hashed when their sizes match.
+pub fn diff_downloads(
+    dataset: &RefDataset,
+    supplementary: &[(FileKind, String)],
+    file_names: &HashMap<FileKind, String>,
+    dir_a: &Path,
+    dir_b: &Path,
+) -> Result<Vec<(String, FileComparison)>> {
+    let mut comparisons = Vec::new();
+    for (_, path_a) in plan_download(dataset, supplementary, file_names, dir_a)? {
+        let name = path_a.file_name().unwrap_or_default().to_string_lossy();
+        let path_b = dir_b.join(&*name);
+        let comparison = match (fs::metadata(&path_a), fs::metadata(&path_b)) {
+            (Err(_), _) => FileComparison::MissingInA,
+            (_, Err(_)) => FileComparison::MissingInB,
+            (Ok(a), Ok(b)) if a.len() != b.len() => FileComparison::Differs,
+            (Ok(_), Ok(_)) => {
+                if hashing::sha256_file(&path_a)? == hashing::sha256_file(&path_b)? {
+                    FileComparison::Identical
+                } else {
+                    FileComparison::Differs
+                }
+            }
+        };
+        comparisons.push((name.to_string(), comparison));
+    }
+    Ok(comparisons)
+}
+
+/// A marker written to `<dest>/.<label>.done` once all of a dataset's files have
+/// been downloaded and verified, giving workflow engines a single file to key on.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Sentinel {
+    pub label: String,
+    pub completed_at: String,
+    pub files: Vec<PathBuf>,
+}
+
+impl Sentinel {
+    pub fn new(label: &str, files: Vec<PathBuf>) -> Self {
+        Self {
+            label: label.to_string(),
+            completed_at: Utc::now().to_rfc3339(),
+            files,
+        }
+    }
+
+    /// The path of the sentinel for the dataset `label` in the directory `dest`.
+    pub fn path(dest: &Path, label: &str) -> PathBuf {
+        dest.join(format!(".{label}.done"))
+    }
+
+    /// Remove a stale sentinel, if one is present, before a new download starts.
+    pub fn clear(dest: &Path, label: &str) -> Result<()> {
+        let path = Self::path(dest, label);
+        if path.exists() {
+            fs::remove_file(&path).with_context(|| {
+                format!("Failed to remove stale sentinel '{}'.", path.display())
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Read the sentinel for `label` in `dest`, if one has been written.
+    pub fn read(dest: &Path, label: &str) -> Result<Option<Self>> {
+        let path = Self::path(dest, label);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read sentinel '{}'.", path.display()))?;
+        let sentinel = serde_json::from_str(&contents)
+            .with_context(|| format!("Sentinel '{}' is malformed.", path.display()))?;
+        Ok(Some(sentinel))
+    }
+
+    pub fn write(&self, dest: &Path) -> Result<PathBuf> {
+        let path = Self::path(dest, &self.label);
+        let contents = serde_json::to_string_pretty(self)?;
+        fs::write(&path, contents)
+            .with_context(|| format!("Failed to write sentinel '{}'.", path.display()))?;
+        Ok(path)
+    }
+}
+
+/// The lineage of a single downloaded file.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FileProvenance {
+    pub kind: String,
+    pub source_url: String,
+    pub redirects: Vec<String>,
+    pub path: PathBuf,
+    pub sha256: String,
+    pub size: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    pub downloaded_at: String,
+
+    /// Whether the file is a companion index of the `kind` file rather than
+    /// the file itself.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub companion: bool,
+}
+
+/// A record written to `<dest>/<label>.provenance.json` after a verified
+/// download, capturing where each file came from and what was received, for
+/// reproducibility and compliance audits.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Provenance {
+    pub label: String,
+    pub refman_version: String,
+    pub files: Vec<FileProvenance>,
+}
+
+impl Provenance {
+    /// Build the record for `fetched`, hashing each file as it now sits on disk.
+    pub fn collect(label: &str, fetched: &[Fetched]) -> Result<Self> {
+        let files = fetched
+            .iter()
+            .map(|file| {
+                let size = fs::metadata(&file.path)?.len();
+                let sha256 = hashing::sha256_file(&file.path)?;
+                Ok(FileProvenance {
+                    kind: file.kind.to_string(),
+                    source_url: file.url.clone(),
+                    redirects: file.redirects.clone(),
+                    path: file.path.clone(),
+                    sha256,
+                    size,
+                    etag: file.etag.clone(),
+                    downloaded_at: file.downloaded_at.clone(),
+                    companion: file.companion,
+                })
+            })
+            .collect::<Result<_, std::io::Error>>()
+            .context("Failed to inspect downloaded files for the provenance record.")?;
+        Ok(Self {
+            label: label.to_string(),
+            refman_version: env!("CARGO_PKG_VERSION").to_string(),
+            files,
+        })
+    }
+
+    pub fn path(dest: &Path, label: &str) -> PathBuf {
+        dest.join(format!("{label}.provenance.json"))
+    }
+
+    /// Read every provenance record in `dir` and its subdirectories.
+    pub fn find_under(dir: &Path) -> Result<Vec<(PathBuf, Self)>> {
+        let mut records = Vec::new();
+        let mut pending = vec![dir.to_path_buf()];
+        while let Some(current) = pending.pop() {
+            let listing = fs::read_dir(&current)
+                .with_context(|| format!("Failed to read directory '{}'.", current.display()))?;
+            for entry in listing {
+                let path = entry?.path();
+                if path.is_dir() {
+                    pending.push(path);
+                    continue;
+                }
+                let is_record = path
+                    .file_name()
+                    .is_some_and(|name| name.to_string_lossy().ends_with(".provenance.json"));
+                if !is_record {
+                    continue;
+                }
+                let contents = fs::read_to_string(&path)?;
+                let record = serde_json::from_str(&contents).with_context(|| {
+                    format!("Provenance record '{}' is malformed.", path.display())
+                })?;
+                records.push((path, record));
+            }
+        }
+        records.sort_by(|(a, _), (b, _)| a.cmp(b));
+        Ok(records)
+    }
+
+    pub fn write(&self, dest: &Path) -> Result<PathBuf> {
+        let path = Self::path(dest, &self.label);
+        let contents = serde_json::to_string_pretty(self)?;
+        fs::write(&path, contents)
+            .with_context(|| format!("Failed to write provenance record '{}'.", path.display()))?;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_name_from_url() {
+        assert_eq!(
+            file_name_from_url("https://example.org/refs/MN908947.3.fasta?dl=1"),
+            Some("MN908947.3.fasta")
+        );
+        assert_eq!(
+            file_name_from_url("rsync://ftp.ensembl.org/pub/genome.fa.gz"),
+            Some("genome.fa.gz")
+        );
+        assert_eq!(file_name_from_url("https://"), None);
+    }
+
+    #[test]
+    fn test_companion_urls() {
+        assert_eq!(
+            companion_urls(FileKind::Fasta, "https://example.org/hg38.fa.gz?dl=1"),
+            [
+                "https://example.org/hg38.fa.gz.fai?dl=1",
+                "https://example.org/hg38.fa.gz.gzi?dl=1",
+            ]
+        );
+        assert_eq!(
+            companion_urls(FileKind::Vcf, "/refs/dbsnp.vcf.gz"),
+            ["/refs/dbsnp.vcf.gz.tbi"]
+        );
+        assert!(companion_urls(FileKind::Gff, "/refs/genes.gff3").is_empty());
+    }
+
+    #[test]
+    fn test_left_behind() {
+        let names: Vec<_> = left_behind(Path::new("/refs/genome.fa.gz"))
+            .into_iter()
+            .map(|path| path.to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(&names[..2], ["/refs/genome.fa.gz", "/refs/genome.fa"]);
+        assert!(names.contains(&"/refs/genome.fa.gz.gzi".to_string()));
+        assert!(names.contains(&"/refs/genome.fa.fai".to_string()));
+        assert_eq!(left_behind(Path::new("/refs/genes.bed")).len(), 5);
+    }
+
+    #[test]
+    fn test_mirror_fallback() {
+        let dir = std::env::temp_dir().join("py-refman-mirrors");
+        let _ = fs::remove_dir_all(&dir);
+        let dest = dir.join("dest");
+        fs::create_dir_all(&dest).unwrap();
+        let mirror = dir.join("mirror").join("genome-copy.fa");
+        fs::create_dir_all(mirror.parent().unwrap()).unwrap();
+        fs::write(&mirror, ">chr1\nACGT\n").unwrap();
+        let primary = dir.join("missing").join("genome.fa");
+
+        let download = |mirrors: Vec<String>| {
+            let options = DownloadOptions {
+                mirrors: HashMap::from([(FileKind::Fasta, mirrors)]),
+                ..DownloadOptions::default()
+            };
+            let url = primary.to_string_lossy().to_string();
+            crate::async_handling::async_runner(|| async {
+                Ok(download_file(
+                    Network::new(false),
+                    FileKind::Fasta,
+                    url,
+                    None,
+                    dest.clone(),
+                    Arc::new(options),
+                    None,
+                )
+                .await)
+            })
+            .unwrap()
+        };
+
+        let fetched = download(vec![mirror.to_string_lossy().to_string()]).unwrap();
+        assert_eq!(fetched.path, dest.join("genome.fa"));
+        assert_eq!(fetched.url, mirror.to_string_lossy());
+
+        let missing = dir.join("missing").join("elsewhere.fa");
+        let err = download(vec![missing.to_string_lossy().to_string()]).unwrap_err();
+        let FetchError::MirrorsExhausted { failures, .. } = &err else {
+            panic!("expected every source to fail, got {err}");
+        };
+        assert_eq!(failures.len(), 2);
+    }
+
+    #[test]
+    fn test_landed_paths() {
+        let dir = std::env::temp_dir().join("py-refman-landed-paths");
+        let _ = fs::remove_dir_all(&dir);
+        let dest = dir.join("dest");
+        let fasta = dir.join("genome.fa");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&fasta, ">chr1\nACGT\n").unwrap();
+
+        let dataset = RefDataset {
+            label: "genome".to_string(),
+            fasta: Some(fasta.to_string_lossy().to_string()),
+            ..RefDataset::default()
+        };
+        let fetched = crate::async_handling::async_runner(|| async {
+            Ok(download_dataset(&dataset, &dest, &DownloadOptions::default()).await?)
+        })
+        .unwrap();
+        assert_eq!(
+            landed_paths(&fetched),
+            vec![("fasta".to_string(), dest.join("genome.fa"))]
+        );
+    }
+
+    #[test]
+    fn test_failed_hook_keeps_files() {
+        let dir = std::env::temp_dir().join("py-refman-failed-hook");
+        let _ = fs::remove_dir_all(&dir);
+        let dest = dir.join("dest");
+        let fasta = dir.join("genome.fa");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&fasta, ">chr1\nACGT\n").unwrap();
+
+        let dataset = RefDataset {
+            label: "genome".to_string(),
+            fasta: Some(fasta.to_string_lossy().to_string()),
+            ..RefDataset::default()
+        };
+        let fetched = crate::async_handling::async_runner(|| async {
+            Ok(download_dataset(&dataset, &dest, &DownloadOptions::default()).await?)
+        })
+        .unwrap();
+        let mut seen = Vec::new();
+        let result = run_on_complete("genome", &fetched, |paths| {
+            seen.extend_from_slice(paths);
+            bail!("samtools exited with status 1")
+        });
+        assert!(result.is_err());
+        assert_eq!(seen, vec![dest.join("genome.fa")]);
+        assert!(dest.join("genome.fa").exists());
+    }
+
+    #[test]
+    fn test_failed_checks_remove_file() {
+        let dir = std::env::temp_dir().join("py-refman-failed-checks");
+        let _ = fs::remove_dir_all(&dir);
+        let dest = dir.join("dest");
+        fs::create_dir_all(&dest).unwrap();
+        let source = dir.join("genome.fa");
+        fs::write(&source, ">chr1\nACGT\n").unwrap();
+
+        let options = DownloadOptions {
+            min_sizes: HashMap::from([(FileKind::Fasta, 1024)]),
+            ..DownloadOptions::default()
+        };
+        let url = source.to_string_lossy().to_string();
+        let result = crate::async_handling::async_runner(|| async {
+            Ok(download_file(
+                Network::new(false),
+                FileKind::Fasta,
+                url,
+                None,
+                dest.clone(),
+                Arc::new(options),
+                None,
+            )
+            .await)
+        })
+        .unwrap();
+        assert!(matches!(result, Err(FetchError::SuspiciouslySmall { .. })));
+        assert!(!dest.join("genome.fa").exists());
+        assert!(source.exists());
+    }
+
+    #[test]
+    fn test_file_names() {
+        let files = [
+            (FileKind::Fasta, "https://example.org/GCF_1.fna.gz"),
+            (FileKind::Gff, "https://example.org/genome.fa"),
+        ];
+        let renamed = HashMap::from([(FileKind::Fasta, "e_coli.fna.gz".to_string())]);
+        assert!(check_file_names(&files, &renamed).is_ok());
+        assert_eq!(
+            file_name_for(&renamed, FileKind::Fasta, files[0].1).unwrap(),
+            "e_coli.fna.gz"
+        );
+        assert_eq!(
+            file_name_for(&renamed, FileKind::Gff, files[1].1).unwrap(),
+            "genome.fa"
+        );
+
+        let colliding = HashMap::from([(FileKind::Fasta, "genome.fa".to_string())]);
+        assert!(matches!(
+            check_file_names(&files, &colliding),
+            Err(FetchError::FileNameCollision { .. })
+        ));
+        let nested = HashMap::from([(FileKind::Fasta, "refs/genome.fa".to_string())]);
+        assert!(matches!(
+            check_file_names(&files, &nested),
+            Err(FetchError::InvalidFileName { .. })
+        ));
+    }
+
+    #[test]
+    fn test_renamed_files_sharing_a_name() {
+        let dir = std::env::temp_dir().join("py-refman-shared-names");
+        let _ = fs::remove_dir_all(&dir);
+        let dest = dir.join("dest");
+        let sources = [("fasta", ">chr1\nACGT\n"), ("gff", "##gff-version 3\n")];
+        for (subdir, contents) in sources {
+            fs::create_dir_all(dir.join(subdir)).unwrap();
+            fs::write(dir.join(subdir).join("genome.txt"), contents).unwrap();
+        }
+        let source = |subdir: &str| {
+            let path = dir.join(subdir).join("genome.txt");
+            Some(path.to_string_lossy().to_string())
+        };
+        let dataset = RefDataset {
+            label: "genome".to_string(),
+            fasta: source("fasta"),
+            gff: source("gff"),
+            ..RefDataset::default()
+        };
+        let options = DownloadOptions {
+            file_names: HashMap::from([
+                (FileKind::Fasta, "genome.fa".to_string()),
+                (FileKind::Gff, "genome.gff".to_string()),
+            ]),
+            ..DownloadOptions::default()
+        };
+
+        crate::async_handling::async_runner(|| async {
+            Ok(download_dataset(&dataset, &dest, &options).await?)
+        })
+        .unwrap();
+        for (name, (_, contents)) in ["genome.fa", "genome.gff"].into_iter().zip(sources) {
+            assert_eq!(fs::read_to_string(dest.join(name)).unwrap(), contents);
+        }
+        assert!(!dest.join("genome.txt").exists());
+    }
+
+    #[test]
+    fn test_layout() {
+        let dir = std::env::temp_dir().join("py-refman-layout");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let fasta = dir.join("genome.fa");
+        fs::write(&fasta, ">chr1\nACGT\n").unwrap();
+        let options = DownloadOptions {
+            layout: Layout::PerLabel,
+            ..DownloadOptions::default()
+        };
+
+        let mut project = Project::new(None, None, false);
+        for label in ["first", "second"] {
+            let dataset = RefDataset {
+                label: label.to_string(),
+                fasta: Some(fasta.to_string_lossy().to_string()),
+                ..RefDataset::default()
+            };
+            project = project.register(dataset).unwrap();
+        }
+        let fetched = crate::async_handling::async_runner(|| async {
+            let dataset = &project.datasets()[0];
+            Ok(download_dataset(dataset, &dir, &options).await?)
+        })
+        .unwrap();
+        assert_eq!(fetched[0].path, dir.join("first").join("genome.fa"));
+
+        let no_names = HashMap::new();
+        assert!(matches!(
+            check_flat_layout(&project, &Metadata::default(), &no_names),
+            Err(FetchError::LabelFileNameCollision { .. })
+        ));
+        assert!(matches!(
+            check_flat_target(&project, &Metadata::default(), "second", &no_names),
+            Err(FetchError::LabelFileNameCollision { .. })
+        ));
+        let renamed = HashMap::from([(FileKind::Fasta, "second.fa".to_string())]);
+        assert!(check_flat_target(&project, &Metadata::default(), "second", &renamed).is_ok());
+        assert_eq!("per-label".parse::<Layout>(), Ok(Layout::PerLabel));
+    }
+
+    #[test]
+    fn test_http_streams_to_disk() {
+        use std::net::TcpListener;
+
+        const HALF: usize = 4 * 1024 * 1024;
+        let dir = std::env::temp_dir().join("py-refman-streaming");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/genome.fa", listener.local_addr().unwrap());
+        let partial = partial_path(&dir.join("genome.fa"));
+
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 4096];
+            let _ = stream.read(&mut request).unwrap();
+            let header = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", 2 * HALF);
+            stream.write_all(header.as_bytes()).unwrap();
+            stream.write_all(&vec![b'A'; HALF]).unwrap();
+            // the rest is only sent once the first half is on disk, which never
+            // happens if the client buffers the whole body before writing it
+            let deadline = Instant::now() + Duration::from_secs(30);
+            while fs::metadata(&partial).map_or(0, |metadata| metadata.len()) < HALF as u64 {
+                assert!(Instant::now() < deadline, "body wasn't streamed");
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            stream.write_all(&vec![b'A'; HALF]).unwrap();
+        });
+
+        let fetched = crate::async_handling::async_runner(|| async {
+            let network = Network::without_redirects(false, None);
+            let options = DownloadOptions::default();
+            let target = dir.join(target_name(&url)?);
+            Ok(fetch(&network, FileKind::Fasta, &url, &target, &options, None).await?)
+        })
+        .unwrap();
+        server.join().unwrap();
+        assert_eq!(fetched.size, 2 * HALF as u64);
+    }
+
+    #[test]
+    fn test_retry_progress() {
+        use std::net::TcpListener;
+
+        for resume in [false, true] {
+            let dir = std::env::temp_dir().join(format!("py-refman-retry-progress-{resume}"));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}/genome.fa", listener.local_addr().unwrap());
+
+            let server = std::thread::spawn(move || {
+                // the first response is cut off halfway through its body
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = [0; 4096];
+                let _ = stream.read(&mut request).unwrap();
+                stream
+                    .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 8\r\n\r\nACGT")
+                    .unwrap();
+                drop(stream);
+
+                let (mut stream, _) = listener.accept().unwrap();
+                let read = stream.read(&mut request).unwrap();
+                let request = String::from_utf8_lossy(&request[..read]).to_ascii_lowercase();
+                let response: &[u8] = if request.contains("range: bytes=4-") {
+                    b"HTTP/1.1 206 Partial Content\r\nContent-Range: bytes 4-7/8\r\nContent-Length: 4\r\n\r\nACGT"
+                } else {
+                    b"HTTP/1.1 200 OK\r\nContent-Length: 8\r\n\r\nACGTACGT"
+                };
+                stream.write_all(response).unwrap();
+            });
+
+            let (sender, mut receiver) = mpsc::unbounded_channel();
+            let progress = Progress::new(sender, Some(8));
+            let fetched = crate::async_handling::async_runner(|| async {
+                let network = Network::without_redirects(false, None);
+                let options = DownloadOptions {
+                    resume,
+                    max_retries: 1,
+                    ..DownloadOptions::default()
+                };
+                let target = dir.join(target_name(&url)?);
+                let fetched = fetch_with_retries(
+                    &network,
+                    FileKind::Fasta,
+                    &url,
+                    &target,
+                    &options,
+                    Some(&progress),
+                )
+                .await?;
+                Ok(fetched)
+            })
+            .unwrap();
+            server.join().unwrap();
+            assert_eq!(fetched.size, 8);
+
+            progress.finish();
+            let mut last = None;
+            while let Ok((downloaded, total)) = receiver.try_recv() {
+                assert!(
+                    downloaded <= 8,
+                    "{downloaded} bytes reported with resume={resume}"
+                );
+                last = Some((downloaded, total));
+            }
+            assert_eq!(last, Some((8, Some(8))));
+            let _ = fs::remove_dir_all(&dir);
+        }
+    }
+
+    #[test]
+    fn test_prepare_dest_creates_nested_dirs() {
+        let root = std::env::temp_dir().join("py-refman-nested-dest");
+        let _ = fs::remove_dir_all(&root);
+        let dest = root.join("a").join("b").join("c");
+
+        DownloadOptions::default().prepare_dest(&dest).unwrap();
+        assert!(dest.is_dir());
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_prepare_dest_strict_missing() {
+        let dest = std::env::temp_dir().join("py-refman-missing-dest");
+        let _ = fs::remove_dir_all(&dest);
+        let options = DownloadOptions {
+            require_existing_dest: true,
+            ..DownloadOptions::default()
+        };
+
+        let result = options.prepare_dest(&dest);
+        assert!(matches!(result, Err(FetchError::DestMissing { .. })));
+        assert!(!dest.exists());
+    }
+
+    #[test]
+    fn test_decompress_multi_member_gzip() {
+        use flate2::{write::GzEncoder, Compression};
+
+        let dir = std::env::temp_dir().join("py-refman-decompress");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("genome.fa.gz");
+        // two concatenated members, the way bgzip lays out its blocks
+        let mut contents = Vec::new();
+        for part in [">chr1\nACGT\n", ">chr2\nGGCC\n"] {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(part.as_bytes()).unwrap();
+            contents.extend(encoder.finish().unwrap());
+        }
+        fs::write(&path, contents).unwrap();
+        let fetched = Fetched {
+            kind: FileKind::Fasta,
+            url: "https://example.org/genome.fa.gz".to_string(),
+            path: path.clone(),
+            redirects: Vec::new(),
+            etag: None,
+            downloaded_at: String::new(),
+            size: 0,
+            reused: false,
+            companion: false,
+            cache_key: None,
+        };
+
+        let decompressed = decompress(fetched).unwrap();
+        assert_eq!(decompressed.path, dir.join("genome.fa"));
+        assert_eq!(
+            fs::read_to_string(&decompressed.path).unwrap(),
+            ">chr1\nACGT\n>chr2\nGGCC\n"
+        );
+        assert!(!path.exists());
+
+        let plain = decompress(decompressed).unwrap();
+        assert_eq!(plain.path, dir.join("genome.fa"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_largest_first() {
+        let pending = vec![
+            ("bed", Some(10)),
+            ("gtf", None),
+            ("fasta", Some(3_000)),
+            ("gff", Some(40)),
+        ];
+        assert_eq!(largest_first(pending), vec!["fasta", "gff", "bed", "gtf"]);
+    }
+
+    /// A simulated benchmark: the wall-clock time for transfers of the given
+    /// durations when `slots` of them can run at once, each slot picking up the
+    /// next pending transfer as soon as it frees up.
+    fn makespan(durations: &[u64], slots: usize) -> u64 {
+        let mut busy_until = vec![0; slots];
+        for duration in durations {
+            let slot = busy_until.iter_mut().min().unwrap();
+            *slot += duration;
+        }
+        busy_until.into_iter().max().unwrap()
+    }
+
+    #[test]
+    fn bench_largest_first_against_naive_order() {
+        // a typical dataset: a few small annotation files registered before one
+        // large genome FASTA
+        let naive = vec![5, 8, 3, 6, 100, 4];
+        let scheduled = largest_first(naive.iter().map(|&size| (size, Some(size))).collect());
+
+        let naive_time = makespan(&naive, 2);
+        let scheduled_time = makespan(&scheduled, 2);
+        assert_eq!((naive_time, scheduled_time), (108, 100));
+    }
+
+    #[test]
+    fn test_retry_policy() {
+        let status = |status| FetchError::Status {
+            url: "https://example.org/x.fasta".to_string(),
+            status,
+        };
+        assert!(is_transient(&status(503)));
+        assert!(is_transient(&status(429)));
+        assert!(!is_transient(&status(404)));
+
+        let first = backoff(500, 0);
+        assert!(first >= Duration::from_millis(250) && first <= Duration::from_millis(500));
+        assert!(backoff(500, 3) >= Duration::from_secs(2));
+        assert!(backoff(500, 40) <= MAX_RETRY_DELAY);
+    }
+
+    #[test]
+    fn test_scheme_dispatch() {
+        assert_eq!(
+            Scheme::of("rsync://hgdownload.soe.ucsc.edu/x.2bit"),
+            Scheme::Rsync
+        );
+        assert_eq!(Scheme::of("https://example.org/x.fasta"), Scheme::Http);
+        assert_eq!(Scheme::of("s3://refs/genomes/x.fasta"), Scheme::S3);
+        assert_eq!(Scheme::of("ftp://ftp.ensembl.org/pub/x.fa.gz"), Scheme::Ftp);
+        assert_eq!(Scheme::of("/mnt/refs/x.fasta"), Scheme::Local);
+        assert_eq!(Scheme::of("file:///mnt/refs/x.fasta"), Scheme::Local);
+    }
+
+    #[test]
+    fn test_expand_vars() {
+        std::env::set_var("PY_REFMAN_TEST_MIRROR", "https://mirror.example.org");
+        std::env::remove_var("PY_REFMAN_TEST_UNSET");
+
+        assert_eq!(
+            expand_vars("${PY_REFMAN_TEST_MIRROR}/hg38.fa").unwrap(),
+            "https://mirror.example.org/hg38.fa"
+        );
+        assert_eq!(
+            expand_vars("$PY_REFMAN_TEST_MIRROR/hg38.fa").unwrap(),
+            "https://mirror.example.org/hg38.fa"
+        );
+        assert_eq!(expand_vars("/refs/$/x.fa").unwrap(), "/refs/$/x.fa");
+        assert!(matches!(
+            expand_vars("${PY_REFMAN_TEST_UNSET}/x.fa"),
+            Err(FetchError::UnresolvedVariable { var, .. }) if var == "PY_REFMAN_TEST_UNSET"
+        ));
+    }
+}
@@ -0,0 +1,146 @@
+//! The hashing submodule.
+//!
+//! Streaming checksum helpers for files on disk. Reference files are often
+//! many gigabytes, so files are hashed in fixed-size chunks rather than read
+//! into memory whole.
+
+use std::{fs::File, io::Read, path::Path, str::FromStr};
+
+use md5::Md5;
+use sha2::{Digest, Sha256};
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// The checksum algorithms reference providers commonly publish.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    Sha256,
+    Md5,
+}
+
+impl Algorithm {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Algorithm::Sha256 => "sha256",
+            Algorithm::Md5 => "md5",
+        }
+    }
+
+    /// The number of hex digits in a digest from this algorithm.
+    pub fn hex_len(self) -> usize {
+        match self {
+            Algorithm::Sha256 => 64,
+            Algorithm::Md5 => 32,
+        }
+    }
+
+    /// Compute the hex-encoded digest of the file at `path`.
+    pub fn hash_file(self, path: &Path) -> std::io::Result<String> {
+        match self {
+            Algorithm::Sha256 => hash_file::<Sha256>(path),
+            Algorithm::Md5 => hash_file::<Md5>(path),
+        }
+    }
+
+    /// Validate a hex digest given on its own, e.g. by a user, returning it in
+    /// lowercase.
+    pub fn normalize(self, digest: &str) -> Option<String> {
+        let digest = digest.trim();
+        (digest.len() == self.hex_len() && digest.chars().all(|c| c.is_ascii_hexdigit()))
+            .then(|| digest.to_ascii_lowercase())
+    }
+
+    /// Pull a digest for this algorithm out of a checksum file, which may be in
+    /// the GNU `<digest>  <name>` layout, the BSD `MD5 (<name>) = <digest>`
+    /// layout, or hold a bare digest.
+    pub fn parse_digest(self, contents: &str) -> Option<String> {
+        contents
+            .split_whitespace()
+            .find(|token| {
+                token.len() == self.hex_len() && token.chars().all(|c| c.is_ascii_hexdigit())
+            })
+            .map(str::to_ascii_lowercase)
+    }
+}
+
+impl FromStr for Algorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().replace('-', "").as_str() {
+            "sha256" => Ok(Algorithm::Sha256),
+            "md5" => Ok(Algorithm::Md5),
+            _ => Err(format!(
+                "Unsupported checksum algorithm '{s}'. Expected one of: sha256, md5."
+            )),
+        }
+    }
+}
+
+fn hash_file<D: Digest>(path: &Path) -> std::io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = D::new();
+    let mut buffer = vec![0; CHUNK_SIZE];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Compute the hex-encoded SHA-256 digest of the file at `path`.
+pub fn sha256_file(path: &Path) -> std::io::Result<String> {
+    Algorithm::Sha256.hash_file(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use super::*;
+
+    #[test]
+    fn test_hash_file() {
+        let dir = env::temp_dir().join("py-refman-hash-file");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("genome.fa");
+        fs::write(&path, ">chr1\nACGT\n").unwrap();
+
+        let algorithm = |name: &str| name.parse::<Algorithm>().unwrap();
+        assert_eq!(
+            algorithm("MD5").hash_file(&path).unwrap(),
+            "6c94fea13e26a4be9be3c54b9fd33dce"
+        );
+        assert_eq!(
+            algorithm("sha-256").hash_file(&path).unwrap(),
+            sha256_file(&path).unwrap()
+        );
+        assert!("crc32".parse::<Algorithm>().is_err());
+    }
+
+    #[test]
+    fn test_parse_digest() {
+        let md5 = "d41d8cd98f00b204e9800998ecf8427e";
+        assert_eq!(
+            Algorithm::Md5.parse_digest(&format!("{md5}  genome.fa.gz\n")),
+            Some(md5.to_string())
+        );
+        assert_eq!(
+            Algorithm::Md5.parse_digest(&format!("MD5 (genome.fa.gz) = {}", md5.to_uppercase())),
+            Some(md5.to_string())
+        );
+        assert_eq!(Algorithm::Sha256.parse_digest(md5), None);
+        assert_eq!(Algorithm::Md5.parse_digest("<html>Not Found</html>"), None);
+        assert_eq!(
+            Algorithm::Md5.normalize(&md5.to_uppercase()),
+            Some(md5.to_string())
+        );
+        assert_eq!(
+            Algorithm::Md5.normalize(&format!("{md5}  genome.fa.gz")),
+            None
+        );
+    }
+}
@@ -0,0 +1,282 @@
+//! The index submodule.
+//!
+//! Builds the index files that downstream tools expect to sit next to reference
+//! files: a `.fai` for FASTA, and a `.tbi` (or `.csi`) for bgzipped annotation
+//! files. Plain FASTA is indexed natively; bgzipped files need BGZF virtual
+//! offsets, so those are handed to `samtools` and `tabix`, which must be on PATH.
+
+use std::{
+    fs::{self, File},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{bail, Context, Result};
+use refman::prelude::RefDataset;
+
+use crate::{
+    dataset::{FileKind, RefDatasetExt},
+    downloads::{self, Fetched},
+    errors::FetchError,
+};
+
+/// What happened to a single file when its dataset was indexed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexOutcome {
+    Built,
+    Current,
+    Missing,
+}
+
+impl IndexOutcome {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            IndexOutcome::Built => "built",
+            IndexOutcome::Current => "current",
+            IndexOutcome::Missing => "missing",
+        }
+    }
+}
+
+fn is_bgzipped(file: &Path) -> bool {
+    file.extension()
+        .is_some_and(|extension| extension == "gz" || extension == "bgz")
+}
+
+/// The index a file of `kind` gets, or `None` for files that aren't indexable.
+/// Only FASTA can be indexed uncompressed; annotation tracks must be bgzipped.
+pub fn index_path(file: &Path, kind: FileKind, csi: bool) -> Option<PathBuf> {
+    let extension = match kind {
+        FileKind::Fasta => "fai",
+        FileKind::Gff | FileKind::Gtf | FileKind::Bed if is_bgzipped(file) => {
+            if csi {
+                "csi"
+            } else {
+                "tbi"
+            }
+        }
+        _ => return None,
+    };
+    let mut name = file.as_os_str().to_owned();
+    name.push(format!(".{extension}"));
+    Some(PathBuf::from(name))
+}
+
+/// Whether `index` exists and is at least as new as the file it indexes.
+fn is_current(file: &Path, index: &Path) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|metadata| metadata.modified());
+    match (modified(file), modified(index)) {
+        (Ok(file_time), Ok(index_time)) => index_time >= file_time,
+        _ => false,
+    }
+}
+
+/// Write a `samtools`-compatible `.fai` for an uncompressed FASTA, recording
+/// each sequence's name, length, byte offset, bases per line, and bytes per line.
+pub fn write_fai(fasta: &Path, fai: &Path) -> Result<()> {
+    struct Record {
+        name: String,
+        length: u64,
+        offset: u64,
+        line_bases: u64,
+        line_width: u64,
+        short_line_seen: bool,
+    }
+
+    let mut reader = BufReader::new(
+        File::open(fasta).with_context(|| format!("Failed to open '{}'.", fasta.display()))?,
+    );
+    let mut records: Vec<Record> = Vec::new();
+    let mut offset = 0;
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line)? as u64;
+        if read == 0 {
+            break;
+        }
+        let content = line.trim_ascii_end();
+        if let Some(header) = content.strip_prefix(b">") {
+            let name = String::from_utf8_lossy(header)
+                .split_whitespace()
+                .next()
+                .unwrap_or_default()
+                .to_string();
+            records.push(Record {
+                name,
+                length: 0,
+                offset: offset + read,
+                line_bases: 0,
+                line_width: 0,
+                short_line_seen: false,
+            });
+        } else if !content.is_empty() {
+            let Some(record) = records.last_mut() else {
+                bail!(
+                    "'{}' has sequence data before its first header.",
+                    fasta.display()
+                );
+            };
+            let bases = content.len() as u64;
+            if record.line_bases == 0 {
+                record.line_bases = bases;
+                record.line_width = read;
+            } else if record.short_line_seen || bases > record.line_bases {
+                bail!(
+                    "Sequence '{}' in '{}' has inconsistent line lengths.",
+                    record.name,
+                    fasta.display()
+                );
+            }
+            record.short_line_seen |= bases < record.line_bases;
+            record.length += bases;
+        }
+        offset += read;
+    }
+
+    let mut writer = BufWriter::new(
+        File::create(fai).with_context(|| format!("Failed to create '{}'.", fai.display()))?,
+    );
+    for record in records {
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}\t{}",
+            record.name, record.length, record.offset, record.line_bases, record.line_width
+        )?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn run_tool(program: &str, args: &[&str], file: &Path) -> Result<()> {
+    let output = Command::new(program)
+        .args(args)
+        .arg(file)
+        .output()
+        .with_context(|| {
+            format!("Unable to run '{program}'; make sure it is installed and on PATH.")
+        })?;
+    if !output.status.success() {
+        bail!(
+            "'{program}' failed to index '{}': {}",
+            file.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Build the index for a single file, unless an up-to-date one already exists.
+pub fn build_index(file: &Path, kind: FileKind, csi: bool) -> Result<Option<IndexOutcome>> {
+    let Some(index) = index_path(file, kind, csi) else {
+        return Ok(None);
+    };
+    if !file.exists() {
+        return Ok(Some(IndexOutcome::Missing));
+    }
+    if is_current(file, &index) {
+        return Ok(Some(IndexOutcome::Current));
+    }
+
+    match kind {
+        FileKind::Fasta if is_bgzipped(file) => run_tool("samtools", &["faidx"], file)?,
+        FileKind::Fasta => write_fai(file, &index)?,
+        _ => {
+            let preset = if kind == FileKind::Bed { "bed" } else { "gff" };
+            let mut args = vec!["-f", "-p", preset];
+            if csi {
+                args.push("--csi");
+            }
+            run_tool("tabix", &args, file)?;
+        }
+    }
+    Ok(Some(IndexOutcome::Built))
+}
+
+/// Index the already-downloaded files of `dataset` in `dir`, optionally only
+/// those of the given kinds. Files that can't be indexed are left out.
+pub fn build_indexes(
+    dataset: &RefDataset,
+    dir: &Path,
+    kinds: &[FileKind],
+    csi: bool,
+) -> Result<Vec<(PathBuf, IndexOutcome)>> {
+    let mut outcomes = Vec::new();
+    for (kind, url) in dataset.files() {
+        if !kinds.is_empty() && !kinds.contains(&kind) {
+            continue;
+        }
+        let file = dir.join(downloads::target_name(url)?);
+        if let Some(outcome) = build_index(&file, kind, csi)? {
+            outcomes.push((file, outcome));
+        }
+    }
+    Ok(outcomes)
+}
+
+/// Build the `.fai` of a downloaded FASTA, and the `.gzi` of a bgzipped one,
+/// unless current ones already exist. The FASTA itself is never touched, so a
+/// failure leaves it in place.
+pub fn index_fasta(file: &Path) -> Result<IndexOutcome, FetchError> {
+    let outcome =
+        build_index(file, FileKind::Fasta, false).map_err(|err| FetchError::IndexBuildFailed {
+            path: file.to_path_buf(),
+            message: format!("{err:#}"),
+        })?;
+    Ok(outcome.unwrap_or(IndexOutcome::Missing))
+}
+
+/// Index each FASTA among the files a download just wrote.
+pub fn index_fetched_fasta(
+    fetched: &[Fetched],
+) -> Result<Vec<(PathBuf, IndexOutcome)>, FetchError> {
+    fetched
+        .iter()
+        .filter(|file| file.kind == FileKind::Fasta && !file.companion)
+        .map(|file| Ok((file.path.clone(), index_fasta(&file.path)?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_fai() {
+        let dir = std::env::temp_dir().join("py-refman-write-fai");
+        fs::create_dir_all(&dir).unwrap();
+        let fasta = dir.join("two.fa");
+        let fai = dir.join("two.fa.fai");
+        fs::write(&fasta, ">chr1 first\nACGT\nACGT\nAC\n>chr2\nGGGGG\n").unwrap();
+
+        write_fai(&fasta, &fai).unwrap();
+        assert_eq!(
+            fs::read_to_string(&fai).unwrap(),
+            "chr1\t10\t12\t4\t5\nchr2\t5\t31\t5\t6\n"
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_index_path() {
+        let fasta = Path::new("/refs/genome.fa");
+        assert_eq!(
+            index_path(fasta, FileKind::Fasta, false),
+            Some(PathBuf::from("/refs/genome.fa.fai"))
+        );
+        let gff = Path::new("/refs/genes.gff3.gz");
+        assert_eq!(
+            index_path(gff, FileKind::Gff, true),
+            Some(PathBuf::from("/refs/genes.gff3.gz.csi"))
+        );
+        assert_eq!(
+            index_path(Path::new("/refs/genes.gff3"), FileKind::Gff, false),
+            None
+        );
+        assert_eq!(
+            index_path(Path::new("/refs/x.gbk"), FileKind::Genbank, false),
+            None
+        );
+    }
+}
@@ -42,13 +42,59 @@
 //! refman.remove("e_coli")
 //! ```
 //!
+//! ## Configuration
+//!
+//! Defaults for the registry location and download concurrency can be set in a
+//! TOML config file at `$REFMAN_CONFIG` or `$XDG_CONFIG_HOME/refman/config.toml`.
+//! Explicit arguments override the config, which overrides built-in defaults.
+//!
+//! ## Concurrency
+//!
+//! Functions that change a registry hold an advisory lock on it from reading it
+//! to writing it back, so that concurrent processes serialize rather than lose
+//! updates. Waiting longer than `lock_timeout_secs` from the config raises an
+//! error.
+//!
+//! ## Registry Formats
+//!
+//! Registries are TOML unless their path ends in `.yaml`, `.yml`, or `.json`,
+//! in which case they are read and written in that format instead.
+//!
 //! ## Python API
 //!
-//! - `init(title=None, description=None, requested_path=None, global_project=False)` - Initialize a new registry
-//! - `register(label, fasta=None, genbank=None, gfa=None, gff=None, gtf=None, bed=None, registry=None, global_project=False)` - Register a dataset
-//! - `download(label, dest=None, registry=None, global_project=False)` - Download a registered dataset
-//! - `remove(label, registry=None, global_project=False)` - Remove a registered dataset
-//! - `list(label=None, registry=None, global_project=False)` - List registered datasets
+//! - `init(title=None, description=None, requested_path=None, global_project=False, force=False)` - Initialize a new registry
+//! - `registry_path(registry=None, global_project=False)` - The absolute path of the registry file, without touching it
+//! - `register(label, fasta=None, genbank=None, gfa=None, gff=None, gtf=None, bed=None, registry=None, global_project=False, force=False, aliases=None, extra=None, sha256=None, validate_urls=True, tags=None, description=None, validate_content=False, twobit=None, nib=None, vcf=None, vcf_index=None)` - Register a dataset
+//! - `register_batch(datasets, registry=None, global_project=False, skip_unavailable=False, flush_every=None, strict=False, fail_fast=False, offline=False)` - Register many datasets at once
+//! - `register_manifest(path, registry=None, global_project=False)` - Register every dataset listed in a TSV or CSV manifest
+//! - `export_manifest(path, checksums=False, registry=None, global_project=False)` - Write every registered dataset to a TSV or CSV manifest
+//! - `resolve_source(source)` - Resolve a provider identifier like `ncbi:NC_045512.2` into file URLs
+//! - `register_accession(label, accession, source="ncbi", registry=None, global_project=False, validate_urls=True)` - Register a dataset from an NCBI or Ensembl accession
+//! - `download(label, dest=None, registry=None, global_project=False, sentinel=False, min_sizes=None, max_sizes=None, mode=None, group=None, create_dest=True, provenance=False, auto_verify=False, require_checksum=False, offline=False, resume=True, max_concurrent_downloads=None, progress=None, decompress=False, link_local=False, force=False, max_retries=3, retry_base_delay_ms=500, timeout=None, headers=None, dry_run=False, fetch_indexes=False, build_index=False, skip_space_check=False, verbosity="normal", return_paths=False, cache_dir=None, rename=None, layout="flat", on_complete=None)` - Download a registered dataset
+//! - `download_all(dest=None, registry=None, global_project=False, verbosity="normal", layout="per_label", offline=False, fail_fast=False)` - Download every registered dataset
+//! - `remove(label, registry=None, global_project=False, delete_files=False, dest=None, confirm=None)` - Remove a registered dataset, and optionally its downloaded files
+//! - `clear(registry=None, global_project=False)` - Remove every dataset, keeping the registry's title and description
+//! - `update(label, fasta=None, genbank=None, gfa=None, gff=None, gtf=None, bed=None, registry=None, global_project=False)` - Replace some of a dataset's files
+//! - `rename(old_label, new_label, registry=None, global_project=False)` - Relabel a registered dataset
+//! - `list(label=None, registry=None, global_project=False, format="text", return_value=False, tag=None, verbosity="normal")` - List registered datasets
+//! - `labels(registry=None, global_project=False)` - List the labels of registered datasets
+//! - `relocate(label, from_dir, to_dir, registry=None, global_project=False, rename=None, layout="flat")` - Move a dataset's downloaded files
+//! - `diff_downloads(label, dir_a, dir_b, registry=None, global_project=False, rename=None, layout="flat")` - Compare two downloaded copies of a dataset
+//! - `reconcile(dest, registry=None, global_project=False)` - Fold observed checksums and sizes from provenance records back into the registry
+//! - `merge(source_registry, dest_registry, strategy="error")` - Fold the datasets of one registry into another
+//! - `diff(registry_a, registry_b)` - Print the datasets added, removed, and changed between two registries
+//! - `stats(registry=None, global_project=False, format=None)` - Count the registered datasets and the files each field holds
+//! - `validate(registry=None, global_project=False)` - Check a registry for consistency problems without fetching anything
+//! - `verify(registry=None, global_project=False, max_concurrent_checks=16, format="text")` - Check that every registered source is still reachable
+//! - `download_size(label=None, registry=None, global_project=False)` - Estimate how many bytes a download would fetch
+//! - `build_indexes(label, dest=None, kinds=None, registry=None, global_project=False, csi=False)` - Index a dataset's already-downloaded files
+//! - `scan_dir(dir, recursive=True, registry=None, global_project=False, kind_map=None, strict=False, max_depth=None, include=None, exclude=None, follow_symlinks=False)` - Rebuild a registry from files on disk
+//! - `cache_info(cache_dir=None)` - Summarize the shared download cache
+//! - `cache_clear(older_than_days=None, cache_dir=None)` - Evict entries from the shared download cache
+//! - `compute_checksum(path, algorithm="sha256")` - Hash a local file with SHA-256 or MD5
+//! - `validate_label(label)` - Check a label against the characters registrations accept
+//! - `normalize_label(label)` - Replace the characters a label may not contain with `_`
+//! - `set_log_level(level)` - Show log events at the given level on stderr
 //!
 //! ## Rust API
 //!
@@ -60,16 +106,43 @@
 //!
 //! The Python interface is implemented via `PyO3` bindings to these core Rust types.
 
-use std::{env, fmt::Display, path::PathBuf};
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    env,
+    fmt::Display,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use async_handling::async_runner;
+use cache::Cache;
+use config::Config;
+use dataset::{DatasetSpec, FileKind, RefDatasetExt};
+use downloads::{DownloadOptions, Fetched, Layout, Provenance, Sentinel};
 use errors::IntoPyResult;
-use pyo3::prelude::*;
+use hashing::Algorithm;
+use logging::Verbosity;
+use metadata::{DatasetMeta, Metadata};
+use network::Network;
+use project::{Merge, MergeStrategy, ProjectExt, Severity};
+use pyo3::{
+    basic::CompareOp,
+    exceptions::PyValueError,
+    prelude::*,
+    types::{PyDict, PyIterator, PyList, PyType},
+};
 use refman::prelude::*;
+use registry::RegistryFormat;
+use resolvers::{AccessionSource, Resolvers};
+use scan::{KindMap, ScanOptions};
+use serde::{de::DeserializeOwned, Serialize};
+use warnings::Warnings;
+use writers::Destination;
 
 #[pyclass]
 #[pyo3(name = "RegistryOptions")]
-struct RefmanOptions(RegistryOptions);
+struct RefmanOptions(RegistryOptions, PathBuf);
 
 #[pymethods]
 impl RefmanOptions {
@@ -81,32 +154,78 @@ impl RefmanOptions {
         requested_path: Option<String>,
         global_project: bool,
     ) -> PyResult<Self> {
+        let (requested_path, global_project) =
+            registry::locate(requested_path, global_project).into_pyresult()?;
+        let registry_path =
+            registry::resolve_path(requested_path.as_deref(), global_project).into_pyresult()?;
         let options = RegistryOptions::try_new(title, description, requested_path, global_project)
             .into_pyresult()?;
 
-        Ok(RefmanOptions(options))
+        Ok(RefmanOptions(options, registry_path))
+    }
+
+    #[pyo3(signature = (force = false))]
+    fn init_project(&self, force: bool) -> PyResult<()> {
+        registry::init(&self.0, &self.1, force).into_pyresult()
     }
 
-    fn init_project(&self) -> PyResult<()> {
-        self.0.init().into_pyresult()
+    fn registry_path(&self) -> PyResult<String> {
+        Ok(std::path::absolute(&self.1)?.display().to_string())
     }
 
     fn read_registry(&self) -> PyResult<RefmanProject> {
-        let project = self.0.read_registry().into_pyresult()?;
-        Ok(RefmanProject(project))
+        let _lock = registry::lock(&self.1).into_pyresult()?;
+        let project = registry::read(&self.0, &self.1).into_pyresult()?;
+        let metadata = Metadata::load(&self.1).into_pyresult()?;
+        Ok(RefmanProject(project, metadata))
     }
 
-    fn write_registry(&self, project: &mut RefmanProject) -> PyResult<()> {
-        let internal_project = &mut project.0;
-        self.0.write_registry(internal_project).into_pyresult()?;
+    fn write_registry(&self, project: &RefmanProject) -> PyResult<()> {
+        let _lock = registry::lock(&self.1).into_pyresult()?;
+        registry::write_atomic(&self.1, &project.0).into_pyresult()?;
+        project.1.save(&self.1).into_pyresult()?;
         Ok(())
     }
 }
 
 #[allow(dead_code)]
-#[pyclass]
+#[pyclass(module = "refman")]
 #[pyo3(name = "RefDataset")]
-struct PyRefDataset(RefDataset);
+struct PyRefDataset(RefDataset, DatasetMeta);
+
+/// The pickled form of a dataset or project: its TOML as written to the
+/// registry, followed by the TOML of its metadata as written to the sidecar.
+type PickleState = (String, String);
+
+fn to_toml(value: &impl Serialize) -> PyResult<String> {
+    toml::to_string(value).map_err(|err| PyValueError::new_err(err.to_string()))
+}
+
+fn from_toml<T: DeserializeOwned>(text: &str) -> PyResult<T> {
+    toml::from_str(text).map_err(|err| PyValueError::new_err(err.to_string()))
+}
+
+impl PyRefDataset {
+    fn state(&self) -> PyResult<PickleState> {
+        Ok((to_toml(&self.0)?, to_toml(&self.1)?))
+    }
+
+    fn from_state((dataset, meta): (&str, &str)) -> PyResult<Self> {
+        Ok(Self(from_toml(dataset)?, from_toml(meta)?))
+    }
+
+    /// What two datasets must share to be equal: the label and every file,
+    /// supplementary ones included.
+    fn identity(&self) -> (String, Vec<(FileKind, String)>) {
+        let files = self
+            .0
+            .files_with(&self.1.supplementary())
+            .into_iter()
+            .map(|(kind, source)| (kind, source.to_string()))
+            .collect();
+        (self.0.label.clone(), files)
+    }
+}
 
 #[allow(clippy::similar_names)]
 #[pymethods]
@@ -128,8 +247,10 @@ impl PyRefDataset {
                 .map_err(anyhow::Error::from)
         })
         .into_pyresult()?;
+        dataset::validate_label(&new_dataset.label)?;
+        dataset::require_files(&new_dataset.label, &new_dataset.files())?;
 
-        Ok(PyRefDataset(new_dataset))
+        Ok(PyRefDataset(new_dataset, DatasetMeta::default()))
     }
 
     #[getter]
@@ -166,11 +287,116 @@ impl PyRefDataset {
     fn bed(&self) -> Option<&str> {
         self.0.bed.as_deref()
     }
+
+    #[getter]
+    fn twobit(&self) -> Option<&str> {
+        self.1.file(FileKind::TwoBit)
+    }
+
+    #[getter]
+    fn nib(&self) -> Option<&str> {
+        self.1.file(FileKind::Nib)
+    }
+
+    #[getter]
+    fn vcf(&self) -> Option<&str> {
+        self.1.file(FileKind::Vcf)
+    }
+
+    #[getter]
+    fn vcf_index(&self) -> Option<&str> {
+        self.1.file(FileKind::VcfIndex)
+    }
+
+    #[getter]
+    fn extra(&self) -> HashMap<String, String> {
+        self.1.extra.clone().into_iter().collect()
+    }
+
+    #[getter]
+    fn tags(&self) -> Vec<String> {
+        self.1.tags.clone()
+    }
+
+    #[getter]
+    fn description(&self) -> Option<&str> {
+        self.1.description.as_deref()
+    }
+
+    fn available_formats(&self) -> Vec<&'static str> {
+        self.0
+            .files_with(&self.1.supplementary())
+            .into_iter()
+            .map(|(kind, _)| kind.as_str())
+            .collect()
+    }
+
+    fn has_format(&self, format: &str) -> PyResult<bool> {
+        let kind: FileKind = format.parse().map_err(PyValueError::new_err)?;
+        Ok(self
+            .0
+            .files_with(&self.1.supplementary())
+            .iter()
+            .any(|(held, _)| *held == kind))
+    }
+
+    fn __reduce__(&self, py: Python<'_>) -> PyResult<(PyObject, (PickleState,))> {
+        let restore = py.get_type::<Self>().getattr("_from_state")?;
+        Ok((restore.into(), (self.state()?,)))
+    }
+
+    #[classmethod]
+    fn _from_state(_cls: &PyType, state: (&str, &str)) -> PyResult<Self> {
+        Self::from_state(state)
+    }
+
+    fn __richcmp__(&self, other: &PyAny, op: CompareOp, py: Python<'_>) -> PyObject {
+        let Ok(other) = other.extract::<PyRef<Self>>() else {
+            return py.NotImplemented();
+        };
+        match op {
+            CompareOp::Eq => (self.identity() == other.identity()).into_py(py),
+            CompareOp::Ne => (self.identity() != other.identity()).into_py(py),
+            _ => py.NotImplemented(),
+        }
+    }
+
+    fn __hash__(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.identity().hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn __repr__(&self) -> String {
+        let mut fields = vec![format!("label={}", py_repr(&self.0.label))];
+        fields.extend(
+            self.0
+                .files_with(&self.1.supplementary())
+                .into_iter()
+                .map(|(kind, source)| format!("{kind}={}", py_repr(source))),
+        );
+        format!("RefDataset({})", fields.join(", "))
+    }
 }
 
-#[pyclass]
+/// How Python would `repr` the string `text`, e.g. `'e_coli'`.
+fn py_repr(text: &str) -> String {
+    format!("'{}'", text.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+#[pyclass(module = "refman")]
 #[derive(Debug)]
-struct RefmanProject(Project);
+struct RefmanProject(Project, Metadata);
+
+impl RefmanProject {
+    fn state(&self) -> PyResult<PickleState> {
+        Ok((to_toml(&self.0)?, to_toml(&self.1)?))
+    }
+
+    fn from_state((project, metadata): (&str, &str)) -> PyResult<Self> {
+        Ok(Self(from_toml(project)?, from_toml(metadata)?))
+    }
+}
 
 impl Display for RefmanProject {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -185,31 +411,40 @@ impl RefmanProject {
     fn new(title: Option<String>, description: Option<String>, global_dataset: bool) -> Self {
         let internal_project = Project::new(title, description, global_dataset);
 
-        Self(internal_project)
+        Self(internal_project, Metadata::default())
     }
 
     fn datasets(&self) -> Vec<PyRefDataset> {
         let internal_datasets = self.0.datasets();
         let py_datasets: Vec<_> = internal_datasets
             .iter()
-            .map(|dataset| PyRefDataset(dataset.clone()))
+            .map(|dataset| {
+                let meta = self.1.get(&dataset.label).cloned().unwrap_or_default();
+                PyRefDataset(dataset.clone(), meta)
+            })
             .collect();
 
         py_datasets
     }
 
     fn get_dataset(&self, label: &str) -> PyResult<PyRefDataset> {
-        let dataset =
-            async_runner(|| async { self.0.get_dataset(label).await.map_err(anyhow::Error::from) })
-                .into_pyresult()?;
+        self.get_dataset_optional(label)
+            .ok_or_else(|| self.0.not_registered(label))
+            .into_pyresult()
+    }
 
-        Ok(PyRefDataset(dataset.clone()))
+    fn get_dataset_optional(&self, label: &str) -> Option<PyRefDataset> {
+        // this is a plain in-memory lookup, so there's no need to pay for spinning
+        // up an async runtime like `Project::get_dataset` requires
+        let dataset = self.0.find_dataset(self.1.resolve(label))?;
+        let meta = self.1.get(&dataset.label).cloned().unwrap_or_default();
+        Some(PyRefDataset(dataset.clone(), meta))
     }
 
     fn get_dataset_urls(&self, label: &str) -> PyResult<Vec<String>> {
         let urls = async_runner(|| async {
             self.0
-                .get_dataset_urls(label)
+                .get_dataset_urls(self.1.resolve(label))
                 .await
                 .map_err(anyhow::Error::from)
         })
@@ -219,29 +454,104 @@ impl RefmanProject {
     }
 
     fn is_registered(&self, label: &str) -> bool {
-        self.0.is_registered(label)
+        self.0.is_registered(self.1.resolve(label))
     }
 
-    #[allow(clippy::too_many_arguments, clippy::similar_names)]
-    #[pyo3(signature = (label, fasta=None, genbank=None, gfa=None, gff=None, gtf=None, bed=None))]
+    fn __reduce__(&self, py: Python<'_>) -> PyResult<(PyObject, (PickleState,))> {
+        let restore = py.get_type::<Self>().getattr("_from_state")?;
+        Ok((restore.into(), (self.state()?,)))
+    }
+
+    #[classmethod]
+    fn _from_state(_cls: &PyType, state: (&str, &str)) -> PyResult<Self> {
+        Self::from_state(state)
+    }
+
+    fn __repr__(&self) -> PyResult<String> {
+        let title = self.0.header_text("title").into_pyresult()?;
+        Ok(format!(
+            "RefmanProject(title={}, datasets={})",
+            title.as_deref().map_or_else(|| "None".to_string(), py_repr),
+            self.0.datasets().len()
+        ))
+    }
+
+    fn __len__(&self) -> usize {
+        self.0.datasets().len()
+    }
+
+    fn __contains__(&self, label: &str) -> bool {
+        self.is_registered(label)
+    }
+
+    fn __iter__<'py>(&self, py: Python<'py>) -> PyResult<&'py PyIterator> {
+        PyAny::iter(PyList::new(py, self.0.labels()))
+    }
+
+    #[allow(
+        clippy::too_many_arguments,
+        clippy::similar_names,
+        clippy::fn_params_excessive_bools
+    )]
+    #[pyo3(signature = (label, fasta=None, genbank=None, gfa=None, gff=None, gtf=None, bed=None, force=false, aliases=None, extra=None, sha256=None, validate_urls=true, tags=None, description=None, validate_content=false, twobit=None, nib=None, vcf=None, vcf_index=None))]
     fn register(
         &self,
         label: String,
-        fasta: Option<String>,
-        genbank: Option<String>,
-        gfa: Option<String>,
-        gff: Option<String>,
-        gtf: Option<String>,
-        bed: Option<String>,
+        fasta: Option<Sources>,
+        genbank: Option<Sources>,
+        gfa: Option<Sources>,
+        gff: Option<Sources>,
+        gtf: Option<Sources>,
+        bed: Option<Sources>,
+        force: bool,
+        aliases: Option<Vec<String>>,
+        extra: Option<HashMap<String, String>>,
+        sha256: Option<HashMap<String, String>>,
+        validate_urls: bool,
+        tags: Option<Vec<String>>,
+        description: Option<String>,
+        validate_content: bool,
+        twobit: Option<Sources>,
+        nib: Option<Sources>,
+        vcf: Option<Sources>,
+        vcf_index: Option<Sources>,
     ) -> PyResult<Self> {
-        let new_dataset = async_runner(|| async {
-            RefDataset::try_new(label, fasta, genbank, gfa, gff, gtf, bed)
-                .await
-                .map_err(anyhow::Error::from)
-        })
-        .into_pyresult()?;
-        let replacement_proj = self.0.clone().register(new_dataset).into_pyresult()?;
-        Ok(RefmanProject(replacement_proj))
+        let registration = Registration {
+            label,
+            files: [fasta, genbank, gfa, gff, gtf, bed],
+            supplementary: [twobit, nib, vcf, vcf_index],
+            force,
+            aliases,
+            extra,
+            sha256,
+            validate_urls,
+            tags,
+            description,
+            validate_content,
+        };
+        let mut metadata = self.1.clone();
+        let project = registration
+            .apply(&self.0, &mut metadata)?
+            .unwrap_or_else(|| self.0.clone());
+        Ok(RefmanProject(project, metadata))
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (text, format="toml"))]
+    fn from_str(text: &str, format: &str) -> PyResult<RefmanProject> {
+        let format = format
+            .parse::<RegistryFormat>()
+            .map_err(PyValueError::new_err)?;
+        let project = format.deserialize(text).into_pyresult()?;
+        Ok(RefmanProject(project, Metadata::default()))
+    }
+
+    #[pyo3(signature = (format="toml"))]
+    fn to_str(&self, format: &str) -> PyResult<String> {
+        let format = format
+            .parse::<RegistryFormat>()
+            .map_err(PyValueError::new_err)?;
+        format.serialize(&self.0).into_pyresult()
     }
 
     #[staticmethod]
@@ -252,25 +562,39 @@ impl RefmanProject {
         description: Option<String>,
         requested_path: Option<String>,
     ) -> PyResult<RefmanProject> {
+        registry::check_read_only(title.as_deref(), description.as_deref()).into_pyresult()?;
+        let (requested_path, global_dataset) =
+            registry::locate(requested_path, global_dataset).into_pyresult()?;
+        let registry_path =
+            registry::resolve_path(requested_path.as_deref(), global_dataset).into_pyresult()?;
         let options = RegistryOptions::try_new(title, description, requested_path, global_dataset)
             .into_pyresult()?;
-        let project = options.read_registry().into_pyresult()?;
-        Ok(RefmanProject(project))
+        let _lock = registry::lock(&registry_path).into_pyresult()?;
+        let project = registry::read(&options, &registry_path).into_pyresult()?;
+        let metadata = Metadata::load(&registry_path).into_pyresult()?;
+        Ok(RefmanProject(project, metadata))
     }
 
     #[staticmethod]
     #[pyo3(signature = (project, global_dataset=false, title=None, description=None, requested_path=None))]
     fn write_registry(
-        project: &mut RefmanProject,
+        project: &RefmanProject,
         global_dataset: bool,
         title: Option<String>,
         description: Option<String>,
         requested_path: Option<String>,
     ) -> PyResult<()> {
-        let options = RegistryOptions::try_new(title, description, requested_path, global_dataset)
+        let (requested_path, global_dataset) =
+            registry::locate(requested_path, global_dataset).into_pyresult()?;
+        let registry_path =
+            registry::resolve_path(requested_path.as_deref(), global_dataset).into_pyresult()?;
+        // the options aren't needed to write, but still reject the same
+        // arguments a write through `refman` would
+        RegistryOptions::try_new(title, description, requested_path, global_dataset)
             .into_pyresult()?;
-        let internal_project = &mut project.0;
-        options.write_registry(internal_project).into_pyresult()?;
+        let _lock = registry::lock(&registry_path).into_pyresult()?;
+        registry::write_atomic(&registry_path, &project.0).into_pyresult()?;
+        project.1.save(&registry_path).into_pyresult()?;
         Ok(())
     }
 }
@@ -279,137 +603,1759 @@ impl RefmanProject {
 // ---------------------
 
 #[pyfunction]
-#[pyo3(signature = (title = None, description = None, requested_path = None, global_project = false))]
+#[pyo3(signature = (registry = None, global_project = false))]
+fn registry_path(registry: Option<String>, global_project: bool) -> PyResult<String> {
+    let (requested_path, global_project) =
+        registry::locate(registry, global_project).into_pyresult()?;
+    let path = registry::resolve_path(requested_path.as_deref(), global_project).into_pyresult()?;
+    Ok(std::path::absolute(path)?.display().to_string())
+}
+
+#[pyfunction]
+#[pyo3(signature = (title = None, description = None, requested_path = None, global_project = false, force = false))]
 fn init(
     title: Option<String>,
     description: Option<String>,
     requested_path: Option<String>,
     global_project: bool,
+    force: bool,
+) -> PyResult<()> {
+    RefmanOptions::new(title, description, requested_path, global_project)?.init_project(force)
+}
+
+#[allow(
+    clippy::too_many_arguments,
+    clippy::similar_names,
+    clippy::fn_params_excessive_bools
+)]
+#[pyfunction]
+#[pyo3(signature = (label, fasta=None, genbank=None, gfa=None, gff=None, gtf=None, bed=None, registry=None, global_project=false, force=false, aliases=None, extra=None, sha256=None, validate_urls=true, tags=None, description=None, validate_content=false, twobit=None, nib=None, vcf=None, vcf_index=None))]
+fn register(
+    label: String,
+    fasta: Option<Sources>,
+    genbank: Option<Sources>,
+    gfa: Option<Sources>,
+    gff: Option<Sources>,
+    gtf: Option<Sources>,
+    bed: Option<Sources>,
+    registry: Option<String>,
+    global_project: bool,
+    force: bool,
+    aliases: Option<Vec<String>>,
+    extra: Option<HashMap<String, String>>,
+    sha256: Option<HashMap<String, String>>,
+    validate_urls: bool,
+    tags: Option<Vec<String>>,
+    description: Option<String>,
+    validate_content: bool,
+    twobit: Option<Sources>,
+    nib: Option<Sources>,
+    vcf: Option<Sources>,
+    vcf_index: Option<Sources>,
+) -> PyResult<()> {
+    let registration = Registration {
+        label,
+        files: [fasta, genbank, gfa, gff, gtf, bed],
+        supplementary: [twobit, nib, vcf, vcf_index],
+        force,
+        aliases,
+        extra,
+        sha256,
+        validate_urls,
+        tags,
+        description,
+        validate_content,
+    };
+    let (options, registry_path) = registry::open(registry, global_project).into_pyresult()?;
+    let _lock = registry::lock(&registry_path).into_pyresult()?;
+    let project = registry::read(&options, &registry_path).into_pyresult()?;
+    let mut metadata = Metadata::load(&registry_path).into_pyresult()?;
+    let loaded = metadata.clone();
+    if let Some(project) = registration.apply(&project, &mut metadata)? {
+        registry::write_atomic(&registry_path, &project).into_pyresult()?;
+    }
+    if metadata != loaded {
+        metadata.save(&registry_path).into_pyresult()?;
+    }
+    Ok(())
+}
+
+/// Everything given to `register` or `RefmanProject.register` for a single
+/// dataset.
+struct Registration {
+    label: String,
+    /// The sources given for each of `FileKind::ALL`, in that order.
+    files: [Option<Sources>; 6],
+    /// The sources given for each of `FileKind::SUPPLEMENTARY`, in that order.
+    supplementary: [Option<Sources>; 4],
+    force: bool,
+    aliases: Option<Vec<String>>,
+    extra: Option<HashMap<String, String>>,
+    sha256: Option<HashMap<String, String>>,
+    validate_urls: bool,
+    tags: Option<Vec<String>>,
+    description: Option<String>,
+    validate_content: bool,
+}
+
+impl Registration {
+    /// Check and register the dataset, recording what is given about it in
+    /// `metadata`. Returns the updated project, or `None` when `project` already
+    /// holds the dataset as given and `force` wasn't set.
+    fn apply(self, project: &Project, metadata: &mut Metadata) -> PyResult<Option<Project>> {
+        metadata
+            .check_names(
+                &self.label,
+                self.aliases.as_deref().unwrap_or_default(),
+                |name| project.is_registered(name),
+            )
+            .into_pyresult()?;
+        let mut mirrors = Vec::new();
+        let mut primary = |kind: FileKind, sources: Option<Sources>| -> PyResult<Option<String>> {
+            let Some(sources) = sources else {
+                return Ok(None);
+            };
+            let (source, rest) = sources.split(kind)?;
+            mirrors.push((kind, rest));
+            Ok(Some(source))
+        };
+        let [fasta, genbank, gfa, gff, gtf, bed] = self.files;
+        let fasta = primary(FileKind::Fasta, fasta)?;
+        let genbank = primary(FileKind::Genbank, genbank)?;
+        let gfa = primary(FileKind::Gfa, gfa)?;
+        let gff = primary(FileKind::Gff, gff)?;
+        let gtf = primary(FileKind::Gtf, gtf)?;
+        let bed = primary(FileKind::Bed, bed)?;
+        let [twobit, nib, vcf, vcf_index] = self.supplementary;
+        let twobit = primary(FileKind::TwoBit, twobit)?;
+        let nib = primary(FileKind::Nib, nib)?;
+        let vcf = primary(FileKind::Vcf, vcf)?;
+        let vcf_index = primary(FileKind::VcfIndex, vcf_index)?;
+        let label = self.label;
+        let supplementary = supplementary_files([twobit, nib, vcf, vcf_index]);
+
+        let new_dataset = async_runner(|| async {
+            let dataset = RefDataset::try_new(label.clone(), fasta, genbank, gfa, gff, gtf, bed)
+                .await
+                .map_err(anyhow::Error::from)?;
+            let files = dataset.files_with(&supplementary);
+            dataset::validate_label(&dataset.label)?;
+            dataset::require_files(&dataset.label, &files)?;
+            if self.validate_urls {
+                let network = Network::new(network::offline_requested(false));
+                downloads::check_sources(&network, &files).await?;
+            } else {
+                downloads::check_local_sources(&files)?;
+            }
+            if self.validate_content {
+                content::check_files(&files)?;
+            }
+            Ok(dataset)
+        })
+        .into_pyresult()?;
+        let checksums = parse_checksums(self.sha256, &new_dataset.files_with(&supplementary))?;
+
+        // re-registering identical content is a no-op so that the registry file and
+        // its timestamps aren't churned needlessly
+        let replaced = !project.already_holds(&new_dataset);
+        let updated = if self.force || replaced {
+            tracing::info!(label = %new_dataset.label, replaced, "registering dataset");
+            Some(project.clone().register(new_dataset).into_pyresult()?)
+        } else {
+            None
+        };
+
+        if replaced {
+            // digests and mirrors recorded for the previous sources no longer apply
+            metadata.invalidate(&label);
+        }
+        for (kind, source) in supplementary {
+            if metadata.set_file(&label, kind, source) {
+                metadata.invalidate_file(&label, kind);
+            }
+        }
+        for (kind, sources) in mirrors {
+            metadata.set_mirrors(&label, kind, sources);
+        }
+        if let Some(aliases) = self.aliases {
+            metadata.set_aliases(&label, aliases);
+        }
+        if let Some(extra) = self.extra {
+            metadata.set_extra(&label, extra.into_iter().collect());
+        }
+        if let Some(tags) = self.tags {
+            metadata.set_tags(&label, tags);
+        }
+        if let Some(description) = self.description {
+            metadata.set_description(&label, description);
+        }
+        for (kind, digest) in checksums {
+            metadata.set_sha256(&label, kind, digest);
+        }
+        Ok(updated)
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::fn_params_excessive_bools)]
+#[pyfunction]
+#[pyo3(signature = (datasets, registry = None, global_project = false, skip_unavailable = false, flush_every = None, strict = false, fail_fast = false, offline = false))]
+fn register_batch(
+    py: Python<'_>,
+    datasets: Vec<HashMap<String, String>>,
+    registry: Option<String>,
+    global_project: bool,
+    skip_unavailable: bool,
+    flush_every: Option<usize>,
+    strict: bool,
+    fail_fast: bool,
+    offline: bool,
+) -> PyResult<&PyDict> {
+    let specs = datasets
+        .into_iter()
+        .map(DatasetSpec::from_map)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(PyValueError::new_err)?;
+    let (options, registry_path) = registry::open(registry, global_project).into_pyresult()?;
+    let _lock = registry::lock(&registry_path).into_pyresult()?;
+    let project = registry::read(&options, &registry_path).into_pyresult()?;
+    let mut metadata = Metadata::load(&registry_path).into_pyresult()?;
+
+    let mut warnings = Warnings::new(strict);
+    let (project, skipped, successes, failures) = async_runner(|| async {
+        let network = Network::new(network::offline_requested(offline));
+        let mut project = project;
+        let mut skipped = Vec::new();
+        let mut successes = Vec::new();
+        let mut failures = Vec::new();
+        let mut registered = 0;
+        for spec in specs {
+            // entries persisted by an earlier, interrupted run are already in
+            // place, which lets a rerun of the same batch resume where it left off
+            if project
+                .find_dataset(&spec.label)
+                .is_some_and(|existing| spec.matches(existing))
+            {
+                successes.push(spec.label);
+                continue;
+            }
+            let label = spec.label.clone();
+            let names = metadata.check_names(&label, &[], |name| project.is_registered(name));
+            let checked = match names {
+                Ok(()) if skip_unavailable => {
+                    downloads::first_unavailable(&network, spec.files.values())
+                        .await
+                        .map_err(anyhow::Error::from)
+                }
+                Ok(()) => Ok(None),
+                Err(err) => Err(err),
+            };
+            let outcome = match checked {
+                Ok(Some((url, reason))) => {
+                    warnings.push(
+                        "unavailable-source",
+                        format!("Skipped '{label}': '{url}' is unavailable ({reason})."),
+                    )?;
+                    skipped.push((label, url));
+                    continue;
+                }
+                Ok(None) => match spec.build().await {
+                    Ok(dataset) => {
+                        let replaced = !project.already_holds(&dataset);
+                        project
+                            .clone()
+                            .register(dataset)
+                            .map(|updated| (updated, replaced))
+                            .map_err(anyhow::Error::from)
+                    }
+                    Err(err) => Err(err),
+                },
+                Err(err) => Err(err),
+            };
+            match outcome {
+                Ok((updated, replaced)) => {
+                    project = updated;
+                    if replaced {
+                        // digests and mirrors recorded for the previous sources no longer apply
+                        metadata.invalidate(&label);
+                    }
+                    successes.push(label);
+                    registered += 1;
+                    if flush_every.is_some_and(|every| every > 0 && registered % every == 0) {
+                        registry::write_atomic(&registry_path, &project)?;
+                        metadata.save(&registry_path)?;
+                    }
+                }
+                Err(err) => {
+                    failures.push((label, err.to_string()));
+                    if fail_fast {
+                        break;
+                    }
+                }
+            }
+        }
+        Ok((project, skipped, successes, failures))
+    })
+    .into_pyresult()?;
+
+    // whatever did register is kept even when other entries failed
+    registry::write_atomic(&registry_path, &project).into_pyresult()?;
+    metadata.save(&registry_path).into_pyresult()?;
+    if !failures.is_empty() {
+        return Err(errors::batch_error(py, successes, failures));
+    }
+
+    let dict = PyDict::new(py);
+    dict.set_item("registered", successes)?;
+    dict.set_item("skipped", skipped)?;
+    dict.set_item("warnings", warnings.into_vec().into_py(py))?;
+    Ok(dict)
+}
+
+#[allow(clippy::needless_pass_by_value)]
+#[pyfunction]
+#[pyo3(signature = (path, registry = None, global_project = false))]
+fn register_manifest(
+    path: PathBuf,
+    registry: Option<String>,
+    global_project: bool,
+) -> PyResult<Vec<String>> {
+    let entries = manifest::read(&path).into_pyresult()?;
+    let labels: Vec<String> = entries
+        .iter()
+        .map(|entry| entry.spec.label.clone())
+        .collect();
+    let (options, registry_path) = registry::open(registry, global_project).into_pyresult()?;
+    let _lock = registry::lock(&registry_path).into_pyresult()?;
+    let project = registry::read(&options, &registry_path).into_pyresult()?;
+    let mut metadata = Metadata::load(&registry_path).into_pyresult()?;
+    for label in &labels {
+        metadata
+            .check_names(label, &[], |name| project.is_registered(name))
+            .into_pyresult()?;
+    }
+
+    let (specs, rest): (Vec<_>, Vec<_>) = entries
+        .into_iter()
+        .map(|entry| (entry.spec, (entry.supplementary, entry.sha256)))
+        .unzip();
+    let project =
+        async_runner(|| manifest::register_specs(project, specs, &mut metadata)).into_pyresult()?;
+    for (label, (supplementary, sha256)) in labels.iter().zip(rest) {
+        for (kind, source) in supplementary {
+            if metadata.set_file(label, kind, source) {
+                metadata.invalidate_file(label, kind);
+            }
+        }
+        for (kind, digest) in sha256 {
+            metadata.set_sha256(label, kind, digest);
+        }
+    }
+    registry::write_atomic(&registry_path, &project).into_pyresult()?;
+    metadata.save(&registry_path).into_pyresult()?;
+    Ok(labels)
+}
+
+#[allow(clippy::needless_pass_by_value)]
+#[pyfunction]
+#[pyo3(signature = (path, checksums = false, registry = None, global_project = false))]
+fn export_manifest(
+    path: PathBuf,
+    checksums: bool,
+    registry: Option<String>,
+    global_project: bool,
+) -> PyResult<()> {
+    let (options, registry_path) = registry::open(registry, global_project).into_pyresult()?;
+    let project = registry::read(&options, &registry_path).into_pyresult()?;
+    let metadata = Metadata::load(&registry_path).into_pyresult()?;
+    project
+        .export_manifest(&path, &metadata, checksums)
+        .into_pyresult()
+}
+
+#[pyfunction]
+fn resolve_source(source: &str) -> PyResult<HashMap<String, String>> {
+    let resolved = async_runner(|| async {
+        Resolvers::default()
+            .resolve(source)
+            .await
+            .map_err(anyhow::Error::from)
+    })
+    .into_pyresult()?;
+
+    // shaped like a `register_batch` entry so the two can be chained
+    let mut entry: HashMap<_, _> = resolved
+        .files
+        .into_iter()
+        .map(|(kind, url)| (kind.to_string(), url))
+        .collect();
+    entry.insert("label".to_string(), resolved.label);
+    Ok(entry)
+}
+
+#[pyfunction]
+#[pyo3(signature = (label, accession, source = "ncbi", registry = None, global_project = false, validate_urls = true))]
+fn register_accession(
+    label: String,
+    accession: &str,
+    source: &str,
+    registry: Option<String>,
+    global_project: bool,
+    validate_urls: bool,
+) -> PyResult<()> {
+    let source = source
+        .parse::<AccessionSource>()
+        .map_err(PyValueError::new_err)?;
+    let (options, registry_path) = registry::open(registry, global_project).into_pyresult()?;
+    let _lock = registry::lock(&registry_path).into_pyresult()?;
+    let project = registry::read(&options, &registry_path).into_pyresult()?;
+    let mut metadata = Metadata::load(&registry_path).into_pyresult()?;
+    metadata
+        .check_names(&label, &[], |name| project.is_registered(name))
+        .into_pyresult()?;
+
+    let (new_dataset, file_names) = async_runner(|| async {
+        let (dataset, file_names) = Resolvers::default()
+            .dataset_for_accession(label, accession, source)
+            .await?;
+        if validate_urls {
+            let network = Network::new(network::offline_requested(false));
+            downloads::check_sources(&network, &dataset.files()).await?;
+        }
+        Ok((dataset, file_names))
+    })
+    .into_pyresult()?;
+
+    let loaded = metadata.clone();
+    let label = new_dataset.label.clone();
+    if !project.already_holds(&new_dataset) {
+        // digests and mirrors recorded for a dataset previously registered under
+        // the label no longer apply
+        metadata.invalidate(&label);
+        let project = project.register(new_dataset).into_pyresult()?;
+        registry::write_atomic(&registry_path, &project).into_pyresult()?;
+    }
+    metadata.set_file_names(&label, file_names);
+    if metadata != loaded {
+        metadata.save(&registry_path).into_pyresult()?;
+    }
+    Ok(())
+}
+
+#[pyfunction]
+#[pyo3(signature = (label, registry = None, global_project = false, delete_files = false, dest = None, confirm = None))]
+fn remove(
+    label: &str,
+    registry: Option<String>,
+    global_project: bool,
+    delete_files: bool,
+    dest: Option<PathBuf>,
+    confirm: Option<&PyAny>,
 ) -> PyResult<()> {
-    RefmanOptions::new(title, description, requested_path, global_project)?.init_project()
+    if dest.is_some() && !delete_files {
+        return Err(PyValueError::new_err(
+            "`dest` only applies when `delete_files` is set.",
+        ));
+    }
+    if confirm.is_some_and(|callback| !callback.is_callable()) {
+        return Err(PyValueError::new_err("`confirm` must be callable."));
+    }
+    let (options, registry_path) = registry::open(registry, global_project).into_pyresult()?;
+    let _lock = registry::lock(&registry_path).into_pyresult()?;
+    let mut metadata = Metadata::load(&registry_path).into_pyresult()?;
+    let label = metadata.resolve(label).to_string();
+    let project = registry::read(&options, &registry_path).into_pyresult()?;
+    if project.find_dataset(&label).is_none() {
+        return Err(project.not_registered(&label).into());
+    }
+    if let Some(confirm) = confirm {
+        if !confirm.call1((label.as_str(),))?.is_true()? {
+            tracing::info!(%label, "removal declined");
+            return Ok(());
+        }
+    }
+    let project = if delete_files {
+        let dest = match dest {
+            Some(dest) => dest,
+            None => env::current_dir()?,
+        };
+        let supplementary = metadata
+            .get(&label)
+            .map(DatasetMeta::supplementary)
+            .unwrap_or_default();
+        let file_names = metadata.file_names(&label, &HashMap::new());
+        let (project, deleted) = project
+            .remove_with_files(&label, &supplementary, &file_names, &dest)
+            .into_pyresult()?;
+        tracing::info!(%label, deleted = deleted.len(), "deleted downloaded files");
+        project
+    } else {
+        project.remove(&label).into_pyresult()?
+    };
+    registry::write_atomic(&registry_path, &project).into_pyresult()?;
+    if metadata.get(&label).is_some() {
+        metadata.forget(&label);
+        metadata.save(&registry_path).into_pyresult()?;
+    }
+    Ok(())
+}
+
+#[pyfunction]
+#[pyo3(signature = (registry = None, global_project = false))]
+fn clear(registry: Option<String>, global_project: bool) -> PyResult<()> {
+    let (options, registry_path) = registry::open(registry, global_project).into_pyresult()?;
+    let _lock = registry::lock(&registry_path).into_pyresult()?;
+    let project = registry::read(&options, &registry_path)
+        .and_then(ProjectExt::clear)
+        .into_pyresult()?;
+    registry::write_atomic(&registry_path, &project).into_pyresult()?;
+    if Metadata::load(&registry_path).into_pyresult()? != Metadata::default() {
+        Metadata::default().save(&registry_path).into_pyresult()?;
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments, clippy::similar_names)]
+#[pyfunction]
+#[pyo3(signature = (label, fasta=None, genbank=None, gfa=None, gff=None, gtf=None, bed=None, registry=None, global_project=false))]
+fn update(
+    label: &str,
+    fasta: Option<String>,
+    genbank: Option<String>,
+    gfa: Option<String>,
+    gff: Option<String>,
+    gtf: Option<String>,
+    bed: Option<String>,
+    registry: Option<String>,
+    global_project: bool,
+) -> PyResult<()> {
+    let (options, registry_path) = registry::open(registry, global_project).into_pyresult()?;
+    let _lock = registry::lock(&registry_path).into_pyresult()?;
+    let project = registry::read(&options, &registry_path).into_pyresult()?;
+    let mut metadata = Metadata::load(&registry_path).into_pyresult()?;
+    let label = metadata.resolve(label).to_string();
+    let mut spec = project
+        .find_dataset(&label)
+        .map(DatasetSpec::from_dataset)
+        .ok_or_else(|| project.not_registered(&label))
+        .into_pyresult()?;
+    let changed = spec.update([
+        (FileKind::Fasta, fasta),
+        (FileKind::Genbank, genbank),
+        (FileKind::Gfa, gfa),
+        (FileKind::Gff, gff),
+        (FileKind::Gtf, gtf),
+        (FileKind::Bed, bed),
+    ]);
+    if changed.is_empty() {
+        return Ok(());
+    }
+
+    let dataset = async_runner(|| spec.build()).into_pyresult()?;
+    let project = project.replace_dataset(dataset).into_pyresult()?;
+    registry::write_atomic(&registry_path, &project).into_pyresult()?;
+    if metadata.get(&label).is_some() {
+        // digests and mirrors recorded for the replaced sources no longer apply
+        for kind in changed {
+            metadata.invalidate_file(&label, kind);
+        }
+        metadata.save(&registry_path).into_pyresult()?;
+    }
+    Ok(())
+}
+
+#[pyfunction]
+#[pyo3(signature = (old_label, new_label, registry = None, global_project = false))]
+fn rename(
+    old_label: &str,
+    new_label: &str,
+    registry: Option<String>,
+    global_project: bool,
+) -> PyResult<()> {
+    let (options, registry_path) = registry::open(registry, global_project).into_pyresult()?;
+    let _lock = registry::lock(&registry_path).into_pyresult()?;
+    let mut metadata = Metadata::load(&registry_path).into_pyresult()?;
+    let old_label = metadata.resolve(old_label).to_string();
+    metadata
+        .check_names(new_label, &[], |_| false)
+        .into_pyresult()?;
+    let project = registry::read(&options, &registry_path)
+        .into_pyresult()?
+        .rename_dataset(&old_label, new_label)
+        .into_pyresult()?;
+    registry::write_atomic(&registry_path, &project).into_pyresult()?;
+    if metadata.get(&old_label).is_some() {
+        metadata.rename(&old_label, new_label);
+        metadata.save(&registry_path).into_pyresult()?;
+    }
+    Ok(())
+}
+
+/// Convert a Python dictionary keyed by file kind names, e.g. `{"fasta": 1000}`,
+/// into one keyed by `FileKind`.
+fn parse_kind_map<V>(map: Option<HashMap<String, V>>) -> PyResult<HashMap<FileKind, V>> {
+    map.unwrap_or_default()
+        .into_iter()
+        .map(|(kind, value)| {
+            let kind = kind.parse::<FileKind>().map_err(PyValueError::new_err)?;
+            Ok((kind, value))
+        })
+        .collect()
+}
+
+/// A file given to `register` as a single source, or as a list of sources whose
+/// first is registered and whose rest are kept as its mirrors.
+#[derive(FromPyObject)]
+enum Sources {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Sources {
+    /// The source to register and the mirrors to fall back on.
+    fn split(self, kind: FileKind) -> PyResult<(String, Vec<String>)> {
+        match self {
+            Sources::One(source) => Ok((source, Vec::new())),
+            Sources::Many(mut sources) => {
+                if sources.is_empty() {
+                    return Err(PyValueError::new_err(format!(
+                        "An empty list of sources was given for the {kind} file."
+                    )));
+                }
+                let source = sources.remove(0);
+                Ok((source, sources))
+            }
+        }
+    }
+}
+
+/// The supplementary files given to `register`, in the order of
+/// `FileKind::SUPPLEMENTARY`, where an empty source is kept so that it clears
+/// the file.
+fn supplementary_files(sources: [Option<String>; 4]) -> Vec<(FileKind, String)> {
+    FileKind::SUPPLEMENTARY
+        .into_iter()
+        .zip(sources)
+        .filter_map(|(kind, source)| Some((kind, source?)))
+        .collect()
+}
+
+/// Parse the SHA-256 digests given when registering `dataset`, each of which
+/// must belong to one of its files.
+fn parse_checksums(
+    sha256: Option<HashMap<String, String>>,
+    files: &[(FileKind, &str)],
+) -> PyResult<HashMap<FileKind, String>> {
+    let kinds: Vec<_> = files.iter().map(|(kind, _)| *kind).collect();
+    parse_kind_map(sha256)?
+        .into_iter()
+        .map(|(kind, digest)| {
+            if !kinds.contains(&kind) {
+                return Err(PyValueError::new_err(format!(
+                    "A SHA-256 digest was given for {kind}, but no {kind} file is registered."
+                )));
+            }
+            let digest = Algorithm::Sha256.normalize(&digest).ok_or_else(|| {
+                PyValueError::new_err(format!("'{digest}' is not a valid SHA-256 digest."))
+            })?;
+            Ok((kind, digest))
+        })
+        .collect()
+}
+
+#[allow(clippy::too_many_arguments, clippy::fn_params_excessive_bools)]
+#[pyfunction]
+#[pyo3(signature = (label, dest = None, registry = None, global_project = false, sentinel = false, min_sizes = None, max_sizes = None, mode = None, group = None, create_dest = true, provenance = false, auto_verify = false, require_checksum = false, offline = false, resume = true, max_concurrent_downloads = None, progress = None, decompress = false, link_local = false, force = false, max_retries = downloads::DEFAULT_MAX_RETRIES, retry_base_delay_ms = downloads::DEFAULT_RETRY_BASE_DELAY_MS, timeout = None, headers = None, dry_run = false, fetch_indexes = false, build_index = false, skip_space_check = false, verbosity = "normal", return_paths = false, cache_dir = None, rename = None, layout = "flat", on_complete = None))]
+fn download(
+    py: Python<'_>,
+    label: &str,
+    dest: Option<&PyAny>,
+    registry: Option<String>,
+    global_project: bool,
+    sentinel: bool,
+    min_sizes: Option<HashMap<String, u64>>,
+    max_sizes: Option<HashMap<String, u64>>,
+    mode: Option<u32>,
+    group: Option<String>,
+    create_dest: bool,
+    provenance: bool,
+    auto_verify: bool,
+    require_checksum: bool,
+    offline: bool,
+    resume: bool,
+    max_concurrent_downloads: Option<usize>,
+    progress: Option<&PyAny>,
+    decompress: bool,
+    link_local: bool,
+    force: bool,
+    max_retries: u32,
+    retry_base_delay_ms: u64,
+    timeout: Option<u64>,
+    headers: Option<HashMap<String, String>>,
+    dry_run: bool,
+    fetch_indexes: bool,
+    build_index: bool,
+    skip_space_check: bool,
+    verbosity: &str,
+    return_paths: bool,
+    cache_dir: Option<String>,
+    rename: Option<HashMap<String, String>>,
+    layout: &str,
+    on_complete: Option<&PyAny>,
+) -> PyResult<PyObject> {
+    if progress.is_some_and(|callback| !callback.is_callable()) {
+        return Err(PyValueError::new_err("`progress` must be callable."));
+    }
+    if on_complete.is_some_and(|callback| !callback.is_callable()) {
+        return Err(PyValueError::new_err("`on_complete` must be callable."));
+    }
+    let layout = layout.parse::<Layout>().map_err(PyValueError::new_err)?;
+    let mut download_options = download_options(
+        min_sizes,
+        max_sizes,
+        mode,
+        group,
+        create_dest,
+        auto_verify,
+        require_checksum,
+        offline,
+        resume,
+        max_concurrent_downloads,
+        decompress,
+        link_local,
+        force,
+        max_retries,
+        retry_base_delay_ms,
+        timeout,
+        headers,
+        fetch_indexes,
+        skip_space_check,
+        verbosity,
+        cache_dir,
+        rename,
+    )?;
+    let (options, registry_path) = registry::open(registry, global_project).into_pyresult()?;
+    let project = registry::read(&options, &registry_path).into_pyresult()?;
+    let metadata = Metadata::load(&registry_path).into_pyresult()?;
+    let label = metadata.resolve(label).to_string();
+    if let Some(meta) = metadata.get(&label) {
+        download_options.sha256 = meta.sha256();
+        download_options.sizes = meta.sizes();
+        download_options.supplementary = meta.supplementary();
+        download_options.mirrors = meta.mirrors();
+    }
+    let explicit_names = download_options.file_names;
+    download_options.file_names = metadata.file_names(&label, &explicit_names);
+    // the label's directory is resolved here rather than through the options so
+    // that sentinels and provenance records land next to the files
+    if layout == Layout::Flat {
+        downloads::check_flat_target(&project, &metadata, &label, &explicit_names)
+            .into_pyresult()?;
+    }
+    let (destination, writer) = match Destination::from_py(dest)? {
+        Destination::Local(path) => (layout.dest_for(&path, &label), None),
+        Destination::Writer(factory) => {
+            if sentinel
+                || provenance
+                || build_index
+                || mode.is_some()
+                || download_options.group.is_some()
+            {
+                return Err(PyValueError::new_err(
+                    "`sentinel`, `provenance`, `build_index`, `mode`, and `group` require a local `dest`.",
+                ));
+            }
+            if return_paths || on_complete.is_some() {
+                return Err(PyValueError::new_err(
+                    "`return_paths` and `on_complete` require a local `dest`.",
+                ));
+            }
+            (writers::staging_dir(&label), Some(factory))
+        }
+    };
+    if dry_run {
+        // files bound for a writer factory are only known by name up front
+        let planned_dest = writer.map_or(destination, |_| PathBuf::new());
+        return project
+            .plan_download(
+                &label,
+                &download_options.supplementary,
+                &download_options.file_names,
+                &planned_dest,
+            )
+            .into_pyresult()
+            .map(|plan| plan.into_py(py));
+    }
+
+    if sentinel {
+        Sentinel::clear(&destination, &label).into_pyresult()?;
+    }
+
+    let dataset = project
+        .find_dataset(&label)
+        .ok_or_else(|| project.not_registered(&label))
+        .into_pyresult()?;
+    let fetched = fetch_dataset(dataset, &destination, &download_options, progress);
+    if let Some(factory) = writer {
+        let result = fetched.and_then(|fetched| {
+            let written: Vec<_> = fetched.iter().map(|file| file.path.clone()).collect();
+            writers::write_through(py, factory, &written)
+        });
+        writers::clean_up(&destination);
+        return result.map(|()| py.None());
+    }
+    let fetched = fetched?;
+
+    write_records(&label, &destination, &fetched, sentinel, provenance).into_pyresult()?;
+    record_fetches(&registry_path, &label, &fetched, &download_options.sha256).into_pyresult()?;
+
+    finish_download(py, &label, &fetched, build_index, on_complete, return_paths)
+}
+
+/// The last steps of a completed, recorded `download` of `label`: indexing the
+/// files, running the `on_complete` callback, and returning where the files
+/// landed if asked to.
+fn finish_download(
+    py: Python<'_>,
+    label: &str,
+    fetched: &[Fetched],
+    build_index: bool,
+    on_complete: Option<&PyAny>,
+    return_paths: bool,
+) -> PyResult<PyObject> {
+    // indexing comes last so that a failure leaves a complete, recorded download
+    if build_index {
+        index::index_fetched_fasta(fetched).into_pyresult()?;
+    }
+    if let Some(callback) = on_complete {
+        downloads::run_on_complete(label, fetched, |paths| {
+            callback.call1((paths.to_vec(),))?;
+            Ok(())
+        })
+        .into_pyresult()?;
+    }
+
+    if return_paths {
+        let paths: HashMap<String, PathBuf> =
+            downloads::landed_paths(fetched).into_iter().collect();
+        return Ok(paths.into_py(py));
+    }
+    Ok(py.None())
+}
+
+/// Write the sentinel and provenance record `download` was asked for next to
+/// the files of `label` it fetched into `destination`, once every one of them
+/// is confirmed to be in place.
+fn write_records(
+    label: &str,
+    destination: &Path,
+    fetched: &[Fetched],
+    sentinel: bool,
+    provenance: bool,
+) -> anyhow::Result<()> {
+    let written: Vec<_> = fetched.iter().map(|file| file.path.clone()).collect();
+    if sentinel || provenance {
+        downloads::verify_written(&written)?;
+    }
+    if provenance {
+        Provenance::collect(label, fetched)?.write(destination)?;
+    }
+    if sentinel {
+        Sentinel::new(label, written).write(destination)?;
+    }
+    Ok(())
+}
+
+/// Download `dataset` into `destination` for `download`, reporting progress to
+/// the `progress` callback if there is one.
+fn fetch_dataset(
+    dataset: &RefDataset,
+    destination: &Path,
+    options: &DownloadOptions,
+    progress: Option<&PyAny>,
+) -> PyResult<Vec<Fetched>> {
+    async_runner(|| async {
+        match progress {
+            Some(callback) => {
+                downloads::download_dataset_with_progress(
+                    dataset,
+                    destination,
+                    options,
+                    |downloaded, total| {
+                        callback.call1((downloaded, total))?;
+                        Ok(())
+                    },
+                )
+                .await
+            }
+            None => downloads::download_dataset(dataset, destination, options)
+                .await
+                .map_err(anyhow::Error::from),
+        }
+    })
+    .into_pyresult()
+}
+
+/// The options `download` fetches a dataset with, as given to it. What the
+/// metadata sidecar knows about the dataset is filled in once it's read.
+#[allow(clippy::too_many_arguments, clippy::fn_params_excessive_bools)]
+fn download_options(
+    min_sizes: Option<HashMap<String, u64>>,
+    max_sizes: Option<HashMap<String, u64>>,
+    mode: Option<u32>,
+    group: Option<String>,
+    create_dest: bool,
+    auto_verify: bool,
+    require_checksum: bool,
+    offline: bool,
+    resume: bool,
+    max_concurrent_downloads: Option<usize>,
+    decompress: bool,
+    link_local: bool,
+    force: bool,
+    max_retries: u32,
+    retry_base_delay_ms: u64,
+    timeout: Option<u64>,
+    headers: Option<HashMap<String, String>>,
+    fetch_indexes: bool,
+    skip_space_check: bool,
+    verbosity: &str,
+    cache_dir: Option<String>,
+    rename: Option<HashMap<String, String>>,
+) -> PyResult<DownloadOptions> {
+    let verbosity = verbosity
+        .parse::<Verbosity>()
+        .map_err(PyValueError::new_err)?;
+    // linked files share one cache entry, which one project's permissions
+    // mustn't be applied to
+    if cache_dir.is_some() && (mode.is_some() || group.is_some()) {
+        return Err(PyValueError::new_err(
+            "`cache_dir` can't be combined with `mode` or `group`.",
+        ));
+    }
+    let cache = cache_dir
+        .map(|cache_dir| Cache::open(Some(PathBuf::from(cache_dir))))
+        .transpose()
+        .into_pyresult()?;
+    Ok(DownloadOptions {
+        min_sizes: parse_kind_map(min_sizes)?,
+        max_sizes: parse_kind_map(max_sizes)?,
+        mode,
+        group,
+        require_existing_dest: !create_dest,
+        auto_verify,
+        require_checksum,
+        offline: network::offline_requested(offline),
+        sha256: HashMap::new(),
+        sizes: HashMap::new(),
+        mirrors: HashMap::new(),
+        force,
+        resume,
+        max_concurrent_downloads: max_concurrent_downloads
+            .or(Config::load()?.max_concurrent_downloads)
+            .unwrap_or(downloads::DEFAULT_MAX_CONCURRENT_DOWNLOADS),
+        link_local,
+        decompress,
+        progress: None,
+        max_retries,
+        retry_base_delay_ms,
+        timeout_secs: timeout,
+        headers: network::parse_headers(headers.unwrap_or_default()).into_pyresult()?,
+        supplementary: Vec::new(),
+        fetch_indexes,
+        skip_space_check,
+        verbosity,
+        cache,
+        file_names: parse_kind_map(rename)?,
+        layout: Layout::Flat,
+    })
+}
+
+/// Record what `download` fetched for `label` in the metadata sidecar, which
+/// lets the next download skip files that are still in place. Another process
+/// may have changed the metadata in the meantime, so the fetches are recorded
+/// against its latest state.
+fn record_fetches(
+    registry_path: &Path,
+    label: &str,
+    fetched: &[Fetched],
+    sha256: &HashMap<FileKind, String>,
+) -> anyhow::Result<()> {
+    let _lock = registry::lock(registry_path)?;
+    let mut metadata = Metadata::load(registry_path)?;
+    let mut recorded = false;
+    for file in fetched
+        .iter()
+        .filter(|file| !file.reused && !file.companion)
+    {
+        recorded |= metadata.record_fetch(label, file.kind, file.size, file.etag.clone());
+        // knowing the digest lets later downloads link the file from the cache
+        if let Some(key) = &file.cache_key {
+            if sha256.get(&file.kind) != Some(key) {
+                metadata.set_sha256(label, file.kind, key.clone());
+                recorded = true;
+            }
+        }
+    }
+    if recorded {
+        metadata.save(registry_path)?;
+    }
+    Ok(())
+}
+
+/// The options `download_all` fetches every dataset with, which are offline
+/// when `offline` is set or `REFMAN_OFFLINE` asks for it.
+fn download_all_options(
+    verbosity: Verbosity,
+    layout: Layout,
+    offline: bool,
+) -> Result<DownloadOptions, errors::OptionsError> {
+    Ok(DownloadOptions {
+        resume: true,
+        offline: network::offline_requested(offline),
+        max_concurrent_downloads: Config::load()?
+            .max_concurrent_downloads
+            .unwrap_or(downloads::DEFAULT_MAX_CONCURRENT_DOWNLOADS),
+        max_retries: downloads::DEFAULT_MAX_RETRIES,
+        retry_base_delay_ms: downloads::DEFAULT_RETRY_BASE_DELAY_MS,
+        verbosity,
+        layout,
+        ..DownloadOptions::default()
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction]
+#[pyo3(signature = (dest = None, registry = None, global_project = false, verbosity = "normal", layout = "per_label", offline = false, fail_fast = false))]
+fn download_all(
+    py: Python<'_>,
+    dest: Option<PathBuf>,
+    registry: Option<String>,
+    global_project: bool,
+    verbosity: &str,
+    layout: &str,
+    offline: bool,
+    fail_fast: bool,
+) -> PyResult<Vec<String>> {
+    let verbosity = verbosity
+        .parse::<Verbosity>()
+        .map_err(PyValueError::new_err)?;
+    let layout = layout.parse::<Layout>().map_err(PyValueError::new_err)?;
+    let dest = match dest {
+        Some(dest) => dest,
+        None => std::env::current_dir()?,
+    };
+    let download_options = download_all_options(verbosity, layout, offline)?;
+    let (options, registry_path) = registry::open(registry, global_project).into_pyresult()?;
+    let project = registry::read(&options, &registry_path).into_pyresult()?;
+    let metadata = Metadata::load(&registry_path).into_pyresult()?;
+
+    let outcomes = async_runner(|| async {
+        Ok(
+            downloads::download_all(&project, &metadata, &dest, &download_options, fail_fast)
+                .await?,
+        )
+    })
+    .into_pyresult()?;
+
+    let _lock = registry::lock(&registry_path).into_pyresult()?;
+    let mut metadata = Metadata::load(&registry_path).into_pyresult()?;
+    let mut recorded = false;
+    for (label, outcome) in &outcomes {
+        for file in outcome
+            .iter()
+            .flatten()
+            .filter(|file| !file.reused && !file.companion)
+        {
+            recorded |= metadata.record_fetch(label, file.kind, file.size, file.etag.clone());
+        }
+    }
+    if recorded {
+        metadata.save(&registry_path).into_pyresult()?;
+    }
+
+    // what did download is kept, and recorded, even when other datasets failed
+    let mut successes = Vec::new();
+    let mut failures = Vec::new();
+    for (label, outcome) in outcomes {
+        match outcome {
+            Ok(_) => successes.push(label),
+            Err(err) => failures.push((label, err.to_string())),
+        }
+    }
+    if !failures.is_empty() {
+        return Err(errors::batch_error(py, successes, failures));
+    }
+    Ok(successes)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction]
+#[pyo3(signature = (label, from_dir, to_dir, registry = None, global_project = false, rename = None, layout = "flat"))]
+fn relocate<'py>(
+    py: Python<'py>,
+    label: &str,
+    from_dir: &str,
+    to_dir: &str,
+    registry: Option<String>,
+    global_project: bool,
+    rename: Option<HashMap<String, String>>,
+    layout: &str,
+) -> PyResult<&'py PyDict> {
+    let layout = layout.parse::<Layout>().map_err(PyValueError::new_err)?;
+    let renames = parse_kind_map(rename)?;
+    let (options, registry_path) = registry::open(registry, global_project).into_pyresult()?;
+    let metadata = Metadata::load(&registry_path).into_pyresult()?;
+    let label = metadata.resolve(label);
+    let relocation = registry::read(&options, &registry_path)
+        .into_pyresult()?
+        .relocate_files(
+            label,
+            &metadata.supplementary(label),
+            &metadata.file_names(label, &renames),
+            &layout.dest_for(Path::new(from_dir), label),
+            &layout.dest_for(Path::new(to_dir), label),
+        )
+        .into_pyresult()?;
+
+    let dict = PyDict::new(py);
+    dict.set_item("moved", relocation.moved)?;
+    dict.set_item("missing", relocation.missing)?;
+    Ok(dict)
+}
+
+#[pyfunction]
+#[pyo3(signature = (label, dir_a, dir_b, registry = None, global_project = false, rename = None, layout = "flat"))]
+fn diff_downloads(
+    label: &str,
+    dir_a: &str,
+    dir_b: &str,
+    registry: Option<String>,
+    global_project: bool,
+    rename: Option<HashMap<String, String>>,
+    layout: &str,
+) -> PyResult<HashMap<String, &'static str>> {
+    let layout = layout.parse::<Layout>().map_err(PyValueError::new_err)?;
+    let renames = parse_kind_map(rename)?;
+    let (options, registry_path) = registry::open(registry, global_project).into_pyresult()?;
+    let metadata = Metadata::load(&registry_path).into_pyresult()?;
+    let label = metadata.resolve(label);
+    let comparisons = registry::read(&options, &registry_path)
+        .into_pyresult()?
+        .diff_downloads(
+            label,
+            &metadata.supplementary(label),
+            &metadata.file_names(label, &renames),
+            &layout.dest_for(Path::new(dir_a), label),
+            &layout.dest_for(Path::new(dir_b), label),
+        )
+        .into_pyresult()?;
+
+    Ok(comparisons
+        .into_iter()
+        .map(|(file, comparison)| (file, comparison.as_str()))
+        .collect())
+}
+
+#[pyfunction]
+#[pyo3(signature = (dest, registry = None, global_project = false))]
+fn reconcile<'py>(
+    py: Python<'py>,
+    dest: &str,
+    registry: Option<String>,
+    global_project: bool,
+) -> PyResult<&'py PyDict> {
+    let (options, registry_path) = registry::open(registry, global_project).into_pyresult()?;
+    let _lock = registry::lock(&registry_path).into_pyresult()?;
+    let project = registry::read(&options, &registry_path).into_pyresult()?;
+    let mut metadata = Metadata::load(&registry_path).into_pyresult()?;
+    let reconciliation = project
+        .reconcile(&mut metadata, Path::new(dest))
+        .into_pyresult()?;
+    if !reconciliation.updated.is_empty() {
+        metadata.save(&registry_path).into_pyresult()?;
+    }
+
+    let updated: Vec<_> = reconciliation
+        .updated
+        .into_iter()
+        .map(|(label, kind)| (label, kind.as_str()))
+        .collect();
+    let dict = PyDict::new(py);
+    dict.set_item("updated", updated)?;
+    dict.set_item("skipped", reconciliation.skipped)?;
+    Ok(dict)
+}
+
+/// Reject output formats other than plain text and JSON.
+fn check_format(format: &str) -> PyResult<()> {
+    if matches!(format, "text" | "json") {
+        return Ok(());
+    }
+    Err(PyValueError::new_err(format!(
+        "Unsupported format '{format}'. Expected 'text' or 'json'."
+    )))
+}
+
+#[pyfunction]
+#[pyo3(signature = (registry = None, global_project = false, max_concurrent_checks = downloads::DEFAULT_MAX_CONCURRENT_CHECKS, format = "text"))]
+fn verify(
+    registry: Option<String>,
+    global_project: bool,
+    max_concurrent_checks: usize,
+    format: &str,
+) -> PyResult<Vec<(String, String, String)>> {
+    check_format(format)?;
+    let (options, registry_path) = registry::open(registry, global_project).into_pyresult()?;
+    let project = registry::read(&options, &registry_path).into_pyresult()?;
+    let metadata = Metadata::load(&registry_path).into_pyresult()?;
+    let report = async_runner(|| async {
+        let network = Network::new(network::offline_requested(false));
+        Ok(downloads::verify_sources(&network, &project, &metadata, max_concurrent_checks).await)
+    })
+    .into_pyresult()?;
+    let unreachable = report
+        .iter()
+        .flat_map(|(label, statuses)| {
+            statuses.iter().filter_map(move |(kind, reason)| {
+                Some((label.clone(), kind.to_string(), reason.clone()?))
+            })
+        })
+        .collect();
+
+    if format == "json" {
+        let rows: Vec<_> = report
+            .iter()
+            .flat_map(|(label, statuses)| {
+                statuses.iter().map(move |(kind, reason)| {
+                    serde_json::json!({
+                        "label": label,
+                        "field": kind.as_str(),
+                        "status": if reason.is_some() { "unreachable" } else { "ok" },
+                        "reason": reason,
+                    })
+                })
+            })
+            .collect();
+        let json = serde_json::to_string_pretty(&rows)
+            .map_err(|err| PyValueError::new_err(err.to_string()))?;
+        println!("{json}");
+        return Ok(unreachable);
+    }
+    let rows: Vec<(&str, &str, String)> = report
+        .iter()
+        .flat_map(|(label, statuses)| {
+            statuses.iter().map(move |(kind, reason)| {
+                let status = reason.as_ref().map_or_else(
+                    || "ok".to_string(),
+                    |reason| format!("unreachable: {reason}"),
+                );
+                (label.as_str(), kind.as_str(), status)
+            })
+        })
+        .collect();
+    let label_width = rows
+        .iter()
+        .map(|(label, ..)| label.len())
+        .max()
+        .unwrap_or(0)
+        .max("Label".len());
+    let kind_width = rows
+        .iter()
+        .map(|(_, kind, _)| kind.len())
+        .max()
+        .unwrap_or(0)
+        .max("Field".len());
+    println!(
+        "{:<label_width$}  {:<kind_width$}  Status",
+        "Label", "Field"
+    );
+    for (label, kind, status) in rows {
+        println!("{label:<label_width$}  {kind:<kind_width$}  {status}");
+    }
+    Ok(unreachable)
+}
+
+#[pyfunction]
+#[pyo3(signature = (label = None, registry = None, global_project = false))]
+fn download_size<'py>(
+    py: Python<'py>,
+    label: Option<&str>,
+    registry: Option<String>,
+    global_project: bool,
+) -> PyResult<&'py PyDict> {
+    let (options, registry_path) = registry::open(registry, global_project).into_pyresult()?;
+    let project = registry::read(&options, &registry_path).into_pyresult()?;
+    let metadata = Metadata::load(&registry_path).into_pyresult()?;
+    let label = label.map(|label| metadata.resolve(label));
+    let urls = project.download_urls(&metadata, label).into_pyresult()?;
+    let size = async_runner(|| async {
+        let network = Network::new(network::offline_requested(false));
+        Ok(downloads::total_size(&network, urls, downloads::DEFAULT_MAX_CONCURRENT_CHECKS).await)
+    })
+    .into_pyresult()?;
+
+    let dict = PyDict::new(py);
+    dict.set_item("total", size.known)?;
+    dict.set_item("unknown", size.unknown)?;
+    Ok(dict)
+}
+
+#[pyfunction]
+#[pyo3(signature = (source_registry, dest_registry, strategy = "error"))]
+fn merge<'py>(
+    py: Python<'py>,
+    source_registry: String,
+    dest_registry: String,
+    strategy: &str,
+) -> PyResult<&'py PyDict> {
+    let strategy = strategy
+        .parse::<MergeStrategy>()
+        .map_err(PyValueError::new_err)?;
+    let (source_options, source_path) =
+        registry::open(Some(source_registry), false).into_pyresult()?;
+    let source = registry::read(&source_options, &source_path).into_pyresult()?;
+    let source_metadata = Metadata::load(&source_path).into_pyresult()?;
+    let (options, registry_path) = registry::open(Some(dest_registry), false).into_pyresult()?;
+    let _lock = registry::lock(&registry_path).into_pyresult()?;
+    let project = registry::read(&options, &registry_path).into_pyresult()?;
+    let mut metadata = Metadata::load(&registry_path).into_pyresult()?;
+
+    let Merge {
+        project,
+        merged,
+        skipped,
+    } = project.merge(&source, strategy).into_pyresult()?;
+    // the incoming datasets bring their aliases, tags, and the like along with
+    // them, which mustn't clash with what the destination already uses. Those
+    // registered identically on both sides still bring what the destination
+    // lacks, e.g. digests or supplementary files.
+    let loaded = metadata.clone();
+    for label in source.labels() {
+        if skipped.contains(&label) {
+            continue;
+        }
+        let meta = source_metadata.get(&label).cloned().unwrap_or_default();
+        metadata
+            .check_names(&label, &meta.aliases, |name| project.is_registered(name))
+            .into_pyresult()?;
+        if merged.contains(&label) {
+            metadata.replace(&label, meta);
+        } else {
+            metadata.absorb(&label, meta, strategy == MergeStrategy::Overwrite);
+        }
+    }
+    if !merged.is_empty() {
+        registry::write_atomic(&registry_path, &project).into_pyresult()?;
+    }
+    if metadata != loaded {
+        metadata.save(&registry_path).into_pyresult()?;
+    }
+
+    let dict = PyDict::new(py);
+    dict.set_item("merged", merged)?;
+    dict.set_item("skipped", skipped)?;
+    Ok(dict)
+}
+
+#[pyfunction]
+fn diff(registry_a: String, registry_b: String) -> PyResult<()> {
+    let (options_a, path_a) = registry::open(Some(registry_a), false).into_pyresult()?;
+    let project_a = registry::read(&options_a, &path_a).into_pyresult()?;
+    let (options_b, path_b) = registry::open(Some(registry_b), false).into_pyresult()?;
+    let project_b = registry::read(&options_b, &path_b).into_pyresult()?;
+    let metadata_a = Metadata::load(&path_a).into_pyresult()?;
+    let metadata_b = Metadata::load(&path_b).into_pyresult()?;
+
+    print!("{}", project_a.diff(&project_b, &metadata_a, &metadata_b));
+    Ok(())
 }
 
-#[allow(clippy::too_many_arguments, clippy::similar_names)]
 #[pyfunction]
-#[pyo3(signature = (label, fasta=None, genbank=None, gfa=None, gff=None, gtf=None, bed=None, registry=None, global_project=false))]
-fn register(
-    label: String,
-    fasta: Option<String>,
-    genbank: Option<String>,
-    gfa: Option<String>,
-    gff: Option<String>,
-    gtf: Option<String>,
-    bed: Option<String>,
+#[pyo3(signature = (registry = None, global_project = false, format = None))]
+fn stats<'py>(
+    py: Python<'py>,
     registry: Option<String>,
     global_project: bool,
-) -> PyResult<()> {
-    let new_dataset = async_runner(|| async {
-        RefDataset::try_new(label, fasta, genbank, gfa, gff, gtf, bed)
-            .await
-            .map_err(anyhow::Error::from)
-    })
-    .into_pyresult()?;
-    let options = RegistryOptions::try_new(None, None, registry, global_project).into_pyresult()?;
-    let mut project = options
-        .read_registry()
+    format: Option<&str>,
+) -> PyResult<&'py PyDict> {
+    if let Some(format) = format {
+        check_format(format)?;
+    }
+    let (options, registry_path) = registry::open(registry, global_project).into_pyresult()?;
+    let metadata = Metadata::load(&registry_path).into_pyresult()?;
+    let stats = registry::read(&options, &registry_path)
         .into_pyresult()?
-        .register(new_dataset)
-        .into_pyresult()?;
-    options.write_registry(&mut project).into_pyresult()?;
-    Ok(())
+        .stats(&metadata);
+
+    let per_field: HashMap<&str, usize> = stats
+        .per_field
+        .into_iter()
+        .map(|(kind, count)| (kind.as_str(), count))
+        .collect();
+    match format {
+        Some("json") => {
+            let json = serde_json::json!({
+                "datasets": stats.datasets,
+                "per_field": per_field,
+                "total_files": stats.total_files,
+                "empty": stats.empty,
+            });
+            println!("{json:#}");
+        }
+        Some(_) => {
+            println!("Datasets:    {}", stats.datasets);
+            println!("Files:       {}", stats.total_files);
+            println!("Empty:       {}", stats.empty);
+            for kind in FileKind::every() {
+                let count = per_field.get(kind.as_str()).copied().unwrap_or_default();
+                println!("  {:<10} {count}", kind.as_str());
+            }
+        }
+        None => {}
+    }
+    let dict = PyDict::new(py);
+    dict.set_item("datasets", stats.datasets)?;
+    dict.set_item("per_field", per_field)?;
+    dict.set_item("total_files", stats.total_files)?;
+    dict.set_item("empty", stats.empty)?;
+    Ok(dict)
 }
 
 #[pyfunction]
-#[pyo3(signature = (label, registry = None, global_project = false))]
-fn remove(label: &str, registry: Option<String>, global_project: bool) -> PyResult<()> {
-    let options = RegistryOptions::try_new(None, None, registry, global_project).into_pyresult()?;
-    let mut project = options
-        .read_registry()
-        .into_pyresult()?
-        .remove(label)
-        .into_pyresult()?;
-    options.write_registry(&mut project).into_pyresult()?;
-    Ok(())
+#[pyo3(signature = (registry = None, global_project = false))]
+fn validate(registry: Option<String>, global_project: bool) -> PyResult<usize> {
+    let (options, registry_path) = registry::open(registry, global_project).into_pyresult()?;
+    let project = registry::read(&options, &registry_path).into_pyresult()?;
+    let metadata = Metadata::load(&registry_path).into_pyresult()?;
+
+    let issues = project.validate(&metadata);
+    if issues.is_empty() {
+        println!("No problems found.");
+    }
+    for issue in &issues {
+        println!("{issue}");
+    }
+    Ok(issues
+        .iter()
+        .filter(|issue| issue.severity == Severity::Error)
+        .count())
 }
 
 #[pyfunction]
-#[pyo3(signature = (label, dest = None, registry = None, global_project = false))]
-fn download(
+#[pyo3(signature = (label, dest = None, kinds = None, registry = None, global_project = false, csi = false))]
+fn build_indexes(
     label: &str,
     dest: Option<String>,
+    kinds: Option<Vec<String>>,
     registry: Option<String>,
     global_project: bool,
-) -> PyResult<()> {
-    let options = RegistryOptions::try_new(None, None, registry, global_project).into_pyresult()?;
-    let project = options.read_registry().into_pyresult()?;
-    if !project.is_registered(label) {
-        Err(RegistryError::NotRegistered(label.to_string())).into_pyresult()?;
-    }
+    csi: bool,
+) -> PyResult<HashMap<String, &'static str>> {
+    let kinds = kinds
+        .unwrap_or_default()
+        .iter()
+        .map(|kind| kind.parse::<FileKind>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(PyValueError::new_err)?;
+    let (options, registry_path) = registry::open(registry, global_project).into_pyresult()?;
+    let label = Metadata::load(&registry_path)
+        .into_pyresult()?
+        .resolve(label)
+        .to_string();
     let destination = match dest {
         Some(dest) => PathBuf::from(dest),
         None => env::current_dir()?,
     };
+    let outcomes = registry::read(&options, &registry_path)
+        .into_pyresult()?
+        .build_indexes(&label, &destination, &kinds, csi)
+        .into_pyresult()?;
 
-    async_runner(|| project.download_dataset(label, destination)).into_pyresult()?;
+    Ok(outcomes
+        .into_iter()
+        .map(|(file, outcome)| (file.to_string_lossy().to_string(), outcome.as_str()))
+        .collect())
+}
 
-    Ok(())
+#[allow(clippy::too_many_arguments, clippy::fn_params_excessive_bools)]
+#[pyfunction]
+#[pyo3(signature = (dir, recursive = true, registry = None, global_project = false, kind_map = None, strict = false, max_depth = None, include = None, exclude = None, follow_symlinks = false))]
+fn scan_dir<'py>(
+    py: Python<'py>,
+    dir: &str,
+    recursive: bool,
+    registry: Option<String>,
+    global_project: bool,
+    kind_map: Option<HashMap<String, String>>,
+    strict: bool,
+    max_depth: Option<usize>,
+    include: Option<Vec<String>>,
+    exclude: Option<Vec<String>>,
+    follow_symlinks: bool,
+) -> PyResult<&'py PyDict> {
+    let kind_map = kind_map
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(extension, kind)| Ok((extension, kind.parse::<FileKind>()?)))
+        .collect::<Result<_, String>>()
+        .map_err(PyValueError::new_err)?;
+    let scan_options = ScanOptions {
+        recursive,
+        max_depth,
+        include: ScanOptions::patterns(&include.unwrap_or_default()).into_pyresult()?,
+        exclude: ScanOptions::patterns(&exclude.unwrap_or_default()).into_pyresult()?,
+        follow_symlinks,
+        kind_map: KindMap::new(kind_map),
+    };
+    let (_, registry_path) = registry::open(registry, global_project).into_pyresult()?;
+    let _lock = registry::lock(&registry_path).into_pyresult()?;
+    let (project, report) =
+        async_runner(|| scan::from_existing_downloads(Path::new(dir), &scan_options))
+            .into_pyresult()?;
+
+    let mut warnings = Warnings::new(strict);
+    for message in &report.ambiguous {
+        warnings
+            .push("ambiguous-grouping", message)
+            .into_pyresult()?;
+    }
+    for file in &report.unrecognized {
+        warnings
+            .push(
+                "unrecognized-file",
+                format!("'{}' does not have a recognized extension.", file.display()),
+            )
+            .into_pyresult()?;
+    }
+    registry::write_atomic(&registry_path, &project).into_pyresult()?;
+
+    let dict = PyDict::new(py);
+    dict.set_item("scanned", report.scanned)?;
+    dict.set_item("included", report.included)?;
+    dict.set_item("datasets", report.labels)?;
+    dict.set_item("ambiguous", report.ambiguous)?;
+    dict.set_item("unrecognized", report.unrecognized)?;
+    dict.set_item("warnings", warnings.into_vec().into_py(py))?;
+    Ok(dict)
 }
 
 #[pyfunction]
-#[pyo3(signature = (label = None, registry = None, global_project = false))]
+#[pyo3(signature = (label = None, registry = None, global_project = false, format = "text", return_value = false, tag = None, verbosity = "normal"))]
 fn list_datasets(
     label: Option<String>,
     registry: Option<String>,
     global_project: bool,
-) -> PyResult<()> {
-    RegistryOptions::try_new(None, None, registry, global_project)
-        .into_pyresult()?
-        .read_registry()
-        .into_pyresult()?
-        .prettyprint(label);
-    Ok(())
+    format: &str,
+    return_value: bool,
+    tag: Option<&str>,
+    verbosity: &str,
+) -> PyResult<Option<Vec<PyRefDataset>>> {
+    check_format(format)?;
+    let verbosity = verbosity
+        .parse::<Verbosity>()
+        .map_err(PyValueError::new_err)?;
+    let (options, registry_path) = registry::open(registry, global_project).into_pyresult()?;
+    let metadata = Metadata::load(&registry_path).into_pyresult()?;
+    let label = label.map(|label| metadata.resolve(&label).to_string());
+    let mut project = registry::read(&options, &registry_path).into_pyresult()?;
+    if let Some(tag) = tag {
+        project = project.retain_tagged(&metadata, tag).into_pyresult()?;
+    }
+    if return_value {
+        let datasets = project
+            .datasets()
+            .iter()
+            .filter(|dataset| label.as_ref().is_none_or(|label| *label == dataset.label))
+            .map(|dataset| {
+                let meta = metadata.get(&dataset.label).cloned().unwrap_or_default();
+                PyRefDataset(dataset.clone(), meta)
+            })
+            .collect();
+        return Ok(Some(datasets));
+    }
+    if verbosity.is_quiet() {
+        return Ok(None);
+    }
+    if format == "json" {
+        let json = project
+            .to_json(&metadata, label.as_deref())
+            .into_pyresult()?;
+        println!("{json}");
+        return Ok(None);
+    }
+    project.prettyprint(label.clone());
+    metadata.prettyprint_aliases(label.as_deref());
+    metadata.prettyprint_descriptions(label.as_deref());
+    metadata.prettyprint_supplementary(label.as_deref());
+    if verbosity.is_verbose() {
+        metadata.prettyprint_sizes(label.as_deref());
+    }
+    Ok(None)
+}
+
+#[pyfunction]
+#[pyo3(signature = (registry = None, global_project = false))]
+fn labels(registry: Option<String>, global_project: bool) -> PyResult<Vec<String>> {
+    let (options, registry_path) = registry::open(registry, global_project).into_pyresult()?;
+    let project = registry::read(&options, &registry_path).into_pyresult()?;
+    Ok(project.labels())
+}
+
+#[pyfunction]
+#[pyo3(signature = (cache_dir = None))]
+fn cache_info(py: Python<'_>, cache_dir: Option<String>) -> PyResult<&PyDict> {
+    let info = Cache::open(cache_dir.map(PathBuf::from))
+        .and_then(|cache| cache.info())
+        .into_pyresult()?;
+
+    let dict = PyDict::new(py);
+    dict.set_item("cache_dir", info.cache_dir.to_string_lossy())?;
+    dict.set_item("entries", info.entries)?;
+    dict.set_item("total_size", info.total_size)?;
+    Ok(dict)
+}
+
+#[pyfunction]
+#[pyo3(signature = (older_than_days = None, cache_dir = None))]
+fn cache_clear(older_than_days: Option<u64>, cache_dir: Option<String>) -> PyResult<u64> {
+    let older_than =
+        older_than_days.map(|days| Duration::from_secs(days.saturating_mul(24 * 60 * 60)));
+    Cache::open(cache_dir.map(PathBuf::from))
+        .and_then(|cache| cache.clear(older_than))
+        .into_pyresult()
+}
+
+#[pyfunction]
+#[pyo3(signature = (path, algorithm = "sha256"))]
+fn compute_checksum(path: &str, algorithm: &str) -> PyResult<String> {
+    let algorithm = algorithm
+        .parse::<Algorithm>()
+        .map_err(PyValueError::new_err)?;
+    algorithm
+        .hash_file(Path::new(path))
+        .map_err(|err| anyhow::anyhow!("Failed to read '{path}' for hashing: {err}"))
+        .into_pyresult()
+}
+
+#[pyfunction]
+fn validate_label(label: &str) -> PyResult<()> {
+    Ok(dataset::validate_label(label)?)
+}
+
+#[pyfunction]
+fn normalize_label(label: &str) -> String {
+    dataset::normalize_label(label)
+}
+
+#[pyfunction]
+fn set_log_level(level: &str) -> PyResult<()> {
+    logging::set_level(level).map_err(PyValueError::new_err)
 }
 
 /// A Python module implemented in Rust.
 #[pymodule]
 #[pyo3(name = "refman")]
-fn py_refman(_py: Python, pymodule: &PyModule) -> PyResult<()> {
+fn py_refman(py: Python, pymodule: &PyModule) -> PyResult<()> {
     // add wrapped classes
     pymodule.add_class::<RefmanOptions>()?;
     pymodule.add_class::<RefmanProject>()?;
     pymodule.add_class::<PyRefDataset>()?;
+    pymodule.add_class::<warnings::Warning>()?;
+    pymodule.add(
+        "RefmanBatchError",
+        py.get_type::<errors::RefmanBatchError>(),
+    )?;
 
     // add wrapped functions
     pymodule.add_function(wrap_pyfunction!(init, pymodule)?)?;
+    pymodule.add_function(wrap_pyfunction!(registry_path, pymodule)?)?;
     pymodule.add_function(wrap_pyfunction!(register, pymodule)?)?;
+    pymodule.add_function(wrap_pyfunction!(register_batch, pymodule)?)?;
+    pymodule.add_function(wrap_pyfunction!(register_manifest, pymodule)?)?;
+    pymodule.add_function(wrap_pyfunction!(export_manifest, pymodule)?)?;
+    pymodule.add_function(wrap_pyfunction!(resolve_source, pymodule)?)?;
+    pymodule.add_function(wrap_pyfunction!(register_accession, pymodule)?)?;
     pymodule.add_function(wrap_pyfunction!(download, pymodule)?)?;
+    pymodule.add_function(wrap_pyfunction!(download_all, pymodule)?)?;
     pymodule.add_function(wrap_pyfunction!(remove, pymodule)?)?;
+    pymodule.add_function(wrap_pyfunction!(clear, pymodule)?)?;
+    pymodule.add_function(wrap_pyfunction!(rename, pymodule)?)?;
+    pymodule.add_function(wrap_pyfunction!(update, pymodule)?)?;
     pymodule.add_function(wrap_pyfunction!(list_datasets, pymodule)?)?;
+    pymodule.add_function(wrap_pyfunction!(labels, pymodule)?)?;
+    pymodule.add_function(wrap_pyfunction!(relocate, pymodule)?)?;
+    pymodule.add_function(wrap_pyfunction!(diff_downloads, pymodule)?)?;
+    pymodule.add_function(wrap_pyfunction!(build_indexes, pymodule)?)?;
+    pymodule.add_function(wrap_pyfunction!(reconcile, pymodule)?)?;
+    pymodule.add_function(wrap_pyfunction!(merge, pymodule)?)?;
+    pymodule.add_function(wrap_pyfunction!(diff, pymodule)?)?;
+    pymodule.add_function(wrap_pyfunction!(stats, pymodule)?)?;
+    pymodule.add_function(wrap_pyfunction!(validate, pymodule)?)?;
+    pymodule.add_function(wrap_pyfunction!(verify, pymodule)?)?;
+    pymodule.add_function(wrap_pyfunction!(download_size, pymodule)?)?;
+    pymodule.add_function(wrap_pyfunction!(scan_dir, pymodule)?)?;
+    pymodule.add_function(wrap_pyfunction!(cache_info, pymodule)?)?;
+    pymodule.add_function(wrap_pyfunction!(cache_clear, pymodule)?)?;
+    pymodule.add_function(wrap_pyfunction!(compute_checksum, pymodule)?)?;
+    pymodule.add_function(wrap_pyfunction!(validate_label, pymodule)?)?;
+    pymodule.add_function(wrap_pyfunction!(normalize_label, pymodule)?)?;
+    pymodule.add_function(wrap_pyfunction!(set_log_level, pymodule)?)?;
 
     Ok(())
 }
 
+pub(crate) mod cache;
+pub(crate) mod config;
+pub(crate) mod content;
+pub(crate) mod dataset;
+pub(crate) mod downloads;
+pub(crate) mod ftp;
+pub(crate) mod hashing;
+pub(crate) mod index;
+pub(crate) mod logging;
+pub(crate) mod manifest;
+pub(crate) mod metadata;
+pub(crate) mod network;
+pub(crate) mod project;
+pub(crate) mod registry;
+pub(crate) mod resolvers;
+pub(crate) mod s3;
+pub(crate) mod scan;
+pub(crate) mod warnings;
+pub(crate) mod writers;
+
 pub(crate) mod async_handling {
 
     //! The asynchronous handling submodule.
     //!
     //! This module provides utilities for running asynchronous code and handling cancellation signals.
     //! The main functionality is exposed through the `async_runner` function which takes an async
-    //! function and executes it while watching for Ctrl+C cancellation.
+    //! function and executes it while watching for Ctrl+C cancellation. Every call shares one
+    //! multi-threaded runtime, started the first time it's needed, since starting a runtime per
+    //! call dominated the cost of bulk operations like registering from a manifest.
 
-    use std::future::Future;
+    use std::{future::Future, sync::OnceLock};
 
     use anyhow::{bail, Error};
+    use tokio::runtime::Runtime;
+
+    static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+
+    fn runtime() -> &'static Runtime {
+        RUNTIME.get_or_init(|| {
+            Runtime::new()
+                .expect("Failed to launch multi-threaded asynchronous code runner. Aborting.")
+        })
+    }
 
     pub fn async_runner<F, Fut, T>(func: F) -> Fut::Output
     where
         F: FnOnce() -> Fut,
         Fut: Future<Output = Result<T, Error>>,
     {
-        let runtime = tokio::runtime::Runtime::new()
-            .expect("Failed to launch multi-threaded asynchronous code runner. Aborting.");
-
-        runtime.block_on(async {
+        // a cancelled call drops its future, and with it any tasks it spawned
+        // into a `JoinSet`, so nothing it started outlives it on the shared runtime
+        runtime().block_on(async {
             tokio::select! {
                 result = func() => {
                     // If the main future completes first, just return its output.
@@ -437,6 +2383,15 @@ pub(crate) mod errors {
     //! - `PyEntryError`: Wraps `EntryError` for file access/validation related errors
     //! - `PyDownloadError`: Wraps `DownloadError` for network/http related errors
     //! - `PyRegistryError`: Wraps `RegistryError` for registry operations errors
+    //! - `FetchError`: This crate's own errors for downloads it performs itself
+    //! - `OptionsError`: This crate's own errors for contradictory registry options
+    //! - `LockError`: This crate's own errors for registries held by another process
+    //! - `InitError`: This crate's own errors for initializing over an existing registry
+    //! - `DatasetError`: This crate's own errors for datasets with invalid labels or no files
+    //! - `MetadataError`: This crate's own errors for unreadable metadata sidecars
+    //! - `ResolveError`: This crate's own errors for resolving provider identifiers
+    //! - `RefmanBatchError`: A Python exception for bulk operations that partially
+    //!   failed, carrying per-item `successes` and `failures`
     //!
     //! # Error Conversion Flows
     //! This module implements two main error conversion patterns:
@@ -456,12 +2411,14 @@ pub(crate) mod errors {
     //! Each wrapped error provides its own Display implementation and is converted
     //! to a Python `ValueError` with an appropriate error message.
 
-    use std::fmt::Display;
+    use std::{fmt::Display, path::PathBuf};
 
     use anyhow::Error as Report;
     use pyo3::{exceptions::PyValueError, prelude::*};
     use refman::prelude::{DownloadError, EntryError, RegistryError};
 
+    use crate::dataset::FileKind;
+
     #[allow(dead_code)]
     #[derive(Debug)]
     pub struct PyReport(Report);
@@ -549,6 +2506,419 @@ pub(crate) mod errors {
         }
     }
 
+    // the macro expands to a cfg on `addr_of` that older pyo3 doesn't declare
+    #[allow(unexpected_cfgs)]
+    mod batch {
+        use pyo3::exceptions::PyValueError;
+
+        pyo3::create_exception!(
+            refman,
+            RefmanBatchError,
+            PyValueError,
+            "Raised when some items of a bulk operation failed. The `successes` attribute lists the items that completed and `failures` lists `(item, error message)` pairs for those that didn't."
+        );
+    }
+
+    pub use batch::RefmanBatchError;
+
+    /// Build a `RefmanBatchError` describing the per-item outcomes of a bulk
+    /// operation.
+    pub fn batch_error(
+        py: Python<'_>,
+        successes: Vec<String>,
+        failures: Vec<(String, String)>,
+    ) -> PyErr {
+        let failed: Vec<_> = failures.iter().map(|(item, _)| item.as_str()).collect();
+        let err = RefmanBatchError::new_err(format!(
+            "{} of {} items failed: {}.",
+            failures.len(),
+            successes.len() + failures.len(),
+            failed.join(", ")
+        ));
+        let value = err.value(py);
+        if let Err(setattr_err) = value
+            .setattr("successes", successes)
+            .and_then(|()| value.setattr("failures", failures))
+        {
+            return setattr_err;
+        }
+        err
+    }
+
+    /// Errors for combinations of registry options that don't make sense together.
+    #[derive(Debug, thiserror::Error)]
+    pub enum OptionsError {
+        #[error("Conflicting registry options: {0}")]
+        ConflictingOptions(String),
+
+        #[error("The config file '{}' is invalid: {message}", path.display())]
+        InvalidConfig { path: PathBuf, message: String },
+    }
+
+    impl From<OptionsError> for PyErr {
+        fn from(value: OptionsError) -> Self {
+            PyValueError::new_err(value.to_string())
+        }
+    }
+
+    /// Errors raised when a label collides with another dataset's, e.g. when
+    /// relabeling a dataset or merging registries, or names no dataset at all.
+    #[derive(Debug, thiserror::Error)]
+    pub enum LabelError {
+        #[error("A dataset is already registered under the label '{0}'.")]
+        AlreadyExists(String),
+
+        #[error("The label '{0}' holds different files in each registry being merged.")]
+        MergeConflict(String),
+
+        #[error(
+            "No dataset is registered under the label '{label}'.{}",
+            did_you_mean(suggestions)
+        )]
+        NotRegistered {
+            label: String,
+            suggestions: Vec<String>,
+        },
+    }
+
+    fn did_you_mean(suggestions: &[String]) -> String {
+        match suggestions {
+            [] => String::new(),
+            [label] => format!(" Did you mean '{label}'?"),
+            labels => format!(" Did you mean one of: '{}'?", labels.join("', '")),
+        }
+    }
+
+    impl From<LabelError> for PyErr {
+        fn from(value: LabelError) -> Self {
+            PyValueError::new_err(value.to_string())
+        }
+    }
+
+    /// Errors raised when another process holds a registry for longer than
+    /// this one is willing to wait.
+    #[derive(Debug, thiserror::Error)]
+    pub enum LockError {
+        #[error("The registry '{}' is locked by another process; gave up after {seconds} seconds.", path.display())]
+        Locked { path: PathBuf, seconds: u64 },
+    }
+
+    impl From<LockError> for PyErr {
+        fn from(value: LockError) -> Self {
+            PyValueError::new_err(value.to_string())
+        }
+    }
+
+    /// Errors raised when initializing a registry would replace an existing one.
+    #[derive(Debug, thiserror::Error)]
+    pub enum InitError {
+        #[error("A registry already exists at '{}'; pass `force=True` to replace it.", .0.display())]
+        AlreadyInitialized(PathBuf),
+    }
+
+    impl From<InitError> for PyErr {
+        fn from(value: InitError) -> Self {
+            PyValueError::new_err(value.to_string())
+        }
+    }
+
+    /// Errors raised for datasets that can't be registered as given.
+    #[derive(Debug, thiserror::Error)]
+    pub enum DatasetError {
+        #[error("The label '{label}' is invalid: {reason}.")]
+        InvalidLabel { label: String, reason: String },
+
+        #[error("The dataset '{label}' has no files; pass the source of at least one of them.")]
+        NoFilesProvided { label: String },
+    }
+
+    impl From<DatasetError> for PyErr {
+        fn from(value: DatasetError) -> Self {
+            PyValueError::new_err(value.to_string())
+        }
+    }
+
+    /// Errors raised while reading a manifest of datasets to register.
+    #[derive(Debug, thiserror::Error)]
+    pub enum ManifestError {
+        #[error("Failed to read the manifest '{}': {source}", path.display())]
+        Read {
+            path: PathBuf,
+            #[source]
+            source: std::io::Error,
+        },
+
+        #[error("The manifest must start with a header row naming a 'label' column.")]
+        MissingHeader,
+
+        #[error("Row {row} of the manifest has {actual} cells, but the header names {expected} columns.")]
+        RowLength {
+            row: usize,
+            expected: usize,
+            actual: usize,
+        },
+
+        #[error("Row {row} of the manifest is invalid: {message}")]
+        InvalidRow { row: usize, message: String },
+
+        #[error("Row {row} of the manifest repeats the label '{label}' from row {first_row}.")]
+        DuplicateLabel {
+            label: String,
+            row: usize,
+            first_row: usize,
+        },
+
+        #[error("The {column} of dataset '{label}' contains the manifest's delimiter or a line break, so it can't be exported.")]
+        UnrepresentableCell { label: String, column: String },
+    }
+
+    impl From<ManifestError> for PyErr {
+        fn from(value: ManifestError) -> Self {
+            PyValueError::new_err(value.to_string())
+        }
+    }
+
+    /// Errors raised while resolving a provider identifier into file URLs.
+    #[derive(Debug, thiserror::Error)]
+    pub enum ResolveError {
+        #[error("No resolver handles '{id}'. Expected '<scheme>:<id>' with one of these schemes: {known}.")]
+        UnknownScheme { id: String, known: String },
+
+        #[error("'{id}' is not a valid identifier: {reason}.")]
+        InvalidId { id: String, reason: String },
+    }
+
+    impl From<ResolveError> for PyErr {
+        fn from(value: ResolveError) -> Self {
+            PyValueError::new_err(value.to_string())
+        }
+    }
+
+    /// Errors raised when reading the metadata sidecar of a registry.
+    #[derive(Debug, thiserror::Error)]
+    pub enum MetadataError {
+        #[error("The dataset metadata in '{}' has schema version {found}, but this version of refman only supports up to {supported}; please upgrade.", path.display())]
+        UnsupportedSchemaVersion {
+            path: PathBuf,
+            found: u32,
+            supported: u32,
+        },
+    }
+
+    impl From<MetadataError> for PyErr {
+        fn from(value: MetadataError) -> Self {
+            PyValueError::new_err(value.to_string())
+        }
+    }
+
+    /// Errors raised when reading a registry written for a different version
+    /// of its layout.
+    #[derive(Debug, thiserror::Error)]
+    pub enum SchemaError {
+        #[error("The registry has schema version {found}, but this version of refman only supports up to {supported}; please upgrade.")]
+        UnsupportedSchemaVersion { found: u32, supported: u32 },
+    }
+
+    impl From<SchemaError> for PyErr {
+        fn from(value: SchemaError) -> Self {
+            PyValueError::new_err(value.to_string())
+        }
+    }
+
+    /// Errors raised when a local file doesn't look like the format of the
+    /// dataset field it is registered under.
+    #[derive(Debug, thiserror::Error)]
+    pub enum ContentError {
+        #[error("Failed to read '{}' to check its format: {source}", path.display())]
+        Read {
+            path: PathBuf,
+            #[source]
+            source: std::io::Error,
+        },
+
+        #[error("The {kind} file '{}' doesn't look like {kind}: {reason}.", path.display())]
+        InvalidFormat {
+            kind: FileKind,
+            path: PathBuf,
+            reason: String,
+        },
+    }
+
+    impl From<ContentError> for PyErr {
+        fn from(value: ContentError) -> Self {
+            PyValueError::new_err(value.to_string())
+        }
+    }
+
+    /// Errors raised by this crate's own download engine, which complements the
+    /// transfers `refman` handles with schemes and checks it doesn't cover.
+    #[derive(Debug, thiserror::Error)]
+    pub enum FetchError {
+        #[error("Request to '{url}' failed: {source}")]
+        Http {
+            url: String,
+            #[source]
+            source: reqwest::Error,
+        },
+
+        #[error("Request to '{url}' returned HTTP status {status}.")]
+        Status { url: String, status: u16 },
+
+        #[error("Unable to derive a file name from the URL '{url}'.")]
+        UnnamedFile { url: String },
+
+        #[error("I/O error while writing '{}': {source}", path.display())]
+        Io {
+            path: PathBuf,
+            #[source]
+            source: std::io::Error,
+        },
+
+        #[error("The `rsync` executable could not be found on PATH, but is required to download '{url}'.")]
+        RsyncMissing { url: String },
+
+        #[error("rsync failed to download '{url}': {message}")]
+        Rsync { url: String, message: String },
+
+        #[error("Downloaded {kind} file is only {size} bytes, below the expected minimum of {minimum} bytes, and may be truncated.")]
+        SuspiciouslySmall {
+            kind: FileKind,
+            size: u64,
+            minimum: u64,
+        },
+
+        #[error("Downloaded {kind} file is {size} bytes, above the expected maximum of {maximum} bytes.")]
+        SuspiciouslyLarge {
+            kind: FileKind,
+            size: u64,
+            maximum: u64,
+        },
+
+        #[error("Unable to set permissions on '{}': {message}", path.display())]
+        Permissions { path: PathBuf, message: String },
+
+        #[error("Download destination '{}' does not exist.", path.display())]
+        DestMissing { path: PathBuf },
+
+        #[error("'{}' failed {algorithm} verification: expected {expected}, got {actual}.", path.display())]
+        ChecksumMismatch {
+            path: PathBuf,
+            algorithm: &'static str,
+            expected: String,
+            actual: String,
+        },
+
+        #[error("No published checksum was found for '{url}'.")]
+        ChecksumMissing { url: String },
+
+        #[error("Download of '{}' is incomplete: expected {expected} bytes, got {actual}.", path.display())]
+        Incomplete {
+            path: PathBuf,
+            expected: u64,
+            actual: u64,
+        },
+
+        #[error("Failed to decompress '{}': {message}", path.display())]
+        DecompressionFailed { path: PathBuf, message: String },
+
+        #[error("The {kind} source '{url}' is unreachable: {reason}.")]
+        UnreachableUrl {
+            kind: FileKind,
+            url: String,
+            reason: String,
+        },
+
+        #[error("The {kind} file '{}' does not exist.", path.display())]
+        LocalFileMissing { kind: FileKind, path: PathBuf },
+
+        #[error("FTP download of '{url}' failed: {message}")]
+        Ftp { url: String, message: String },
+
+        #[error(
+            "The `aws` executable could not be found on PATH, but is required to download '{url}'."
+        )]
+        AwsCliMissing { url: String },
+
+        #[error("S3 transfer of 's3://{bucket}/{key}' failed: {message}")]
+        S3 {
+            bucket: String,
+            key: String,
+            message: String,
+        },
+
+        #[error("Download of '{url}' timed out after {seconds} seconds.")]
+        Timeout { url: String, seconds: u64 },
+
+        #[error("Giving up on '{url}' after {attempts} attempts: {source}")]
+        RetriesExhausted {
+            url: String,
+            attempts: u32,
+            #[source]
+            source: Box<FetchError>,
+        },
+
+        #[error("Request to '{url}' was refused with HTTP status {status}; check the credentials or headers sent with it.")]
+        Unauthorized { url: String, status: u16 },
+
+        #[error("The header '{name}' is not a valid HTTP header.")]
+        InvalidHeader { name: String },
+
+        #[error("Downloading needs {needed} bytes, but only {available} bytes are free at '{}'.", path.display())]
+        InsufficientSpace {
+            path: PathBuf,
+            needed: u64,
+            available: u64,
+        },
+
+        #[error("Failed to index '{}', which was downloaded and left in place: {message}", path.display())]
+        IndexBuildFailed { path: PathBuf, message: String },
+
+        #[error("Refusing to contact '{url}' in offline mode.")]
+        OfflineMode { url: String },
+
+        #[error("Every source of the {kind} file failed: {}", list_failures(failures))]
+        MirrorsExhausted {
+            kind: FileKind,
+            failures: Vec<(String, String)>,
+        },
+
+        #[error(
+            "The source '{url}' refers to the environment variable '{var}', which is not set."
+        )]
+        UnresolvedVariable { var: String, url: String },
+
+        #[error("'{name}' is not a valid file name for the {kind} file.")]
+        InvalidFileName { kind: FileKind, name: String },
+
+        #[error("The {first} and {second} files would both be written as '{name}'.")]
+        FileNameCollision {
+            name: String,
+            first: FileKind,
+            second: FileKind,
+        },
+
+        #[error("'{first}' and '{second}' both have a file that would be written as '{name}'.")]
+        LabelFileNameCollision {
+            name: String,
+            first: String,
+            second: String,
+        },
+    }
+
+    fn list_failures(failures: &[(String, String)]) -> String {
+        failures
+            .iter()
+            .map(|(url, reason)| format!("'{url}' ({reason})"))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+
+    impl From<FetchError> for PyErr {
+        fn from(value: FetchError) -> Self {
+            PyValueError::new_err(value.to_string())
+        }
+    }
+
     pub trait IntoPyResult<T> {
         fn into_pyresult(self) -> PyResult<T>;
     }
@@ -576,12 +2946,59 @@ pub(crate) mod errors {
             self.map_err(|e| PyRegistryError::from(e).into())
         }
     }
+
+    impl<T> IntoPyResult<T> for Result<T, FetchError> {
+        fn into_pyresult(self) -> PyResult<T> {
+            self.map_err(PyErr::from)
+        }
+    }
+
+    impl<T> IntoPyResult<T> for Result<T, OptionsError> {
+        fn into_pyresult(self) -> PyResult<T> {
+            self.map_err(PyErr::from)
+        }
+    }
+
+    impl<T> IntoPyResult<T> for Result<T, LabelError> {
+        fn into_pyresult(self) -> PyResult<T> {
+            self.map_err(PyErr::from)
+        }
+    }
+
+    impl<T> IntoPyResult<T> for Result<T, ManifestError> {
+        fn into_pyresult(self) -> PyResult<T> {
+            self.map_err(PyErr::from)
+        }
+    }
+
+    impl<T> IntoPyResult<T> for Result<T, ResolveError> {
+        fn into_pyresult(self) -> PyResult<T> {
+            self.map_err(PyErr::from)
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// A fresh directory under the system's temporary directory to hold a test's
+    /// registry, so that tests never write one into the working directory.
+    fn scratch_dir(name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("py-refman-{name}"));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.display().to_string()
+    }
+
+    /// A `file://` URL to a FASTA file written under the system's temporary
+    /// directory, which passes `refman`'s check that sources exist.
+    fn local_fasta(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("py-refman-{name}.fasta"));
+        std::fs::write(&path, ">seq\nACGT\n").unwrap();
+        format!("file://{}", path.display())
+    }
+
     #[test]
     fn test_refman_options_new() {
         let options = RefmanOptions::new(
@@ -593,11 +3010,24 @@ mod tests {
         assert!(options.is_ok());
     }
 
+    #[test]
+    fn test_refman_options_conflicting_location() {
+        let options = RefmanOptions::new(None, None, Some("refman.toml".to_string()), true);
+        assert!(options.is_err());
+    }
+
+    #[test]
+    fn test_read_registry_rejects_title() {
+        let project =
+            RefmanProject::read_registry(false, Some("Test Title".to_string()), None, None);
+        assert!(project.is_err());
+    }
+
     #[test]
     fn test_py_refdataset_new() {
         let dataset = PyRefDataset::try_new(
             "test_label".to_string(),
-            Some("test.fasta".to_string()),
+            Some(local_fasta("refdataset-new")),
             None,
             None,
             None,
@@ -607,15 +3037,151 @@ mod tests {
         assert!(dataset.is_ok());
     }
 
+    #[test]
+    fn test_py_refdataset_requires_files() {
+        let dataset =
+            PyRefDataset::try_new("test_label".to_string(), None, None, None, None, None, None);
+        assert!(dataset.is_err());
+
+        let spec = DatasetSpec {
+            label: "test_label".to_string(),
+            ..DatasetSpec::default()
+        };
+        assert!(async_runner(|| spec.build()).is_err());
+    }
+
+    #[test]
+    fn test_download_all_offline() {
+        let options = download_all_options(Verbosity::default(), Layout::Flat, true);
+        assert!(options.unwrap().offline);
+    }
+
+    #[test]
+    fn test_label_validation() {
+        assert!(dataset::validate_label("GRCh38.p14").is_ok());
+        assert!(dataset::validate_label("e coli").is_err());
+        assert!(dataset::validate_label("refs/e_coli").is_err());
+        assert!(dataset::validate_label("..").is_err());
+        assert!(dataset::validate_label("").is_err());
+        assert_eq!(dataset::normalize_label("E. coli K-12"), "E._coli_K-12");
+        assert_eq!(dataset::normalize_label(".."), "__");
+
+        let dataset = PyRefDataset::try_new(
+            "e coli".to_string(),
+            Some(local_fasta("label-validation")),
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+        assert!(dataset.is_err());
+    }
+
+    #[test]
+    fn test_repr() {
+        let dataset = PyRefDataset(
+            RefDataset {
+                label: "e_coli".to_string(),
+                fasta: Some("test.fasta".to_string()),
+                ..RefDataset::default()
+            },
+            DatasetMeta::default(),
+        );
+        assert_eq!(
+            dataset.__repr__(),
+            "RefDataset(label='e_coli', fasta='test.fasta')"
+        );
+
+        let project = RefmanProject::new(Some("Bob's References".to_string()), None, false);
+        assert_eq!(
+            project.__repr__().unwrap(),
+            "RefmanProject(title='Bob\\'s References', datasets=0)"
+        );
+    }
+
+    #[test]
+    fn test_available_formats() {
+        let dataset = PyRefDataset(
+            RefDataset {
+                label: "e_coli".to_string(),
+                fasta: Some("test.fasta".to_string()),
+                gff: Some("test.gff".to_string()),
+                ..RefDataset::default()
+            },
+            DatasetMeta::default(),
+        );
+        assert_eq!(dataset.available_formats(), ["fasta", "gff"]);
+        assert!(dataset.has_format("GFF").unwrap());
+        assert!(!dataset.has_format("gtf").unwrap());
+        assert!(dataset.has_format("star").is_err());
+    }
+
+    #[test]
+    fn test_dataset_identity() {
+        let dataset = |label: &str| {
+            PyRefDataset(
+                RefDataset {
+                    label: label.to_string(),
+                    fasta: Some("test.fasta".to_string()),
+                    ..RefDataset::default()
+                },
+                DatasetMeta::default(),
+            )
+        };
+        let (a, b) = (dataset("e_coli"), dataset("e_coli"));
+        assert_eq!(a.identity(), b.identity());
+        assert_eq!(a.__hash__(), b.__hash__());
+        assert_ne!(a.identity(), dataset("s_aureus").identity());
+    }
+
+    #[test]
+    fn test_pickle_state_round_trip() {
+        let mut dataset = PyRefDataset(
+            RefDataset {
+                label: "e_coli".to_string(),
+                fasta: Some("test.fasta".to_string()),
+                gtf: Some("test.gtf".to_string()),
+                ..RefDataset::default()
+            },
+            DatasetMeta::default(),
+        );
+        dataset.1.tags.push("bacteria".to_string());
+        let (registry, metadata) = dataset.state().unwrap();
+        let restored = PyRefDataset::from_state((&registry, &metadata)).unwrap();
+        assert!(restored.0.same_content(&dataset.0));
+        assert_eq!(restored.1, dataset.1);
+
+        let project = RefmanProject::new(Some("References".to_string()), None, false);
+        let (registry, metadata) = project.state().unwrap();
+        let restored = RefmanProject::from_state((&registry, &metadata)).unwrap();
+        assert_eq!(restored.__repr__().unwrap(), project.__repr__().unwrap());
+    }
+
+    #[test]
+    fn test_registry_path_is_absolute() {
+        let path = registry_path(Some("refs/refman.yaml".to_string()), false).unwrap();
+        let path = Path::new(&path);
+        assert!(path.is_absolute());
+        assert!(path.ends_with("refs/refman.yaml"));
+        assert!(registry_path(Some("refs/refman.yaml".to_string()), true).is_err());
+    }
+
     #[test]
     fn test_init_with_empty_options() {
-        let result = init(None, None, None, false);
+        let result = init(None, None, Some(scratch_dir("init-empty")), false, true);
         assert!(result.is_ok());
     }
 
     #[test]
     fn test_init_with_title() {
-        let result = init(Some("Test Registry".to_string()), None, None, false);
+        let result = init(
+            Some("Test Registry".to_string()),
+            None,
+            Some(scratch_dir("init-title")),
+            false,
+            true,
+        );
         assert!(result.is_ok());
     }
 
@@ -623,15 +3189,58 @@ mod tests {
     fn test_register_invalid_path() {
         let result = register(
             "test".to_string(),
-            Some("nonexistent.fasta".to_string()),
+            Some(Sources::One("nonexistent.fasta".to_string())),
+            None,
             None,
             None,
             None,
             None,
+            Some(scratch_dir("register-invalid")),
+            false,
+            false,
+            None,
+            None,
+            None,
+            true,
             None,
             None,
             false,
+            None,
+            None,
+            None,
+            None,
         );
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_project_register_mirrors() {
+        let (primary, mirror) = (local_fasta("primary"), local_fasta("mirror"));
+        let project = RefmanProject::new(None, None, false)
+            .register(
+                "e_coli".to_string(),
+                Some(Sources::Many(vec![primary.clone(), mirror.clone()])),
+                None,
+                None,
+                None,
+                None,
+                None,
+                false,
+                None,
+                None,
+                None,
+                false,
+                None,
+                None,
+                false,
+                None,
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+        assert_eq!(project.0.datasets()[0].fasta.as_deref(), Some(&*primary));
+        let mirrors = project.1.get("e_coli").unwrap().mirrors();
+        assert_eq!(mirrors[&FileKind::Fasta], vec![mirror]);
+    }
 }
@@ -0,0 +1,78 @@
+//! The logging submodule.
+//!
+//! Downloads, registry reads and writes, and registrations emit `tracing`
+//! events, e.g. each URL fetched, the bytes written, and any retries. Nothing
+//! is printed unless a level is set with `set_level`, which installs a
+//! subscriber writing to stderr. URLs are passed through `network::redact`
+//! before they're logged, and request headers never are.
+//!
+//! Separately from logging, listings and downloads print a little for people at
+//! a terminal, e.g. warnings about skipped checks. How much is a `Verbosity`
+//! chosen per call.
+
+use std::{str::FromStr, sync::OnceLock};
+
+use tracing_subscriber::{
+    filter::LevelFilter, fmt, layer::SubscriberExt, reload, util::SubscriberInitExt, Registry,
+};
+
+/// How much a listing or download prints besides its errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Verbosity {
+    /// Print nothing but errors.
+    Quiet,
+    /// Print listings and warnings.
+    #[default]
+    Normal,
+    /// Also print the source, destination, and size of every file.
+    Verbose,
+}
+
+impl Verbosity {
+    pub fn is_quiet(self) -> bool {
+        self == Verbosity::Quiet
+    }
+
+    pub fn is_verbose(self) -> bool {
+        self == Verbosity::Verbose
+    }
+}
+
+impl FromStr for Verbosity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "quiet" => Ok(Verbosity::Quiet),
+            "normal" => Ok(Verbosity::Normal),
+            "verbose" => Ok(Verbosity::Verbose),
+            _ => Err(format!(
+                "Unsupported verbosity '{s}'. Expected one of: quiet, normal, verbose."
+            )),
+        }
+    }
+}
+
+/// The handle to change the level of the subscriber once it's installed.
+static LEVEL: OnceLock<reload::Handle<LevelFilter, Registry>> = OnceLock::new();
+
+/// Show log events at `level` and above, e.g. "info" or "debug", or none with
+/// "off". The first call installs the subscriber; later calls change its level.
+pub fn set_level(level: &str) -> Result<(), String> {
+    let filter: LevelFilter = level.parse().map_err(|_| {
+        format!(
+            "Unknown log level '{level}'. Expected one of: off, error, warn, info, debug, trace."
+        )
+    })?;
+    if let Some(handle) = LEVEL.get() {
+        return handle.reload(filter).map_err(|err| err.to_string());
+    }
+    let (layer, handle) = reload::Layer::new(filter);
+    tracing_subscriber::registry()
+        .with(layer)
+        .with(fmt::layer().with_writer(std::io::stderr))
+        .try_init()
+        .map_err(|err| format!("Unable to install the log subscriber: {err}"))?;
+    let _ = LEVEL.set(handle);
+    Ok(())
+}
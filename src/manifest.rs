@@ -0,0 +1,395 @@
+//! The manifest submodule.
+//!
+//! A manifest is a spreadsheet-friendly listing of datasets, one per row, with
+//! a header naming the `label` column and any of the file kind columns, i.e.
+//! `label`, `fasta`, `genbank`, `gfa`, `gff`, `gtf`, `bed`, `twobit`, `nib`,
+//! `vcf`, and `vcf_index`. Files ending in
+//! `.csv` are comma-separated and anything else is tab-separated. Cells are
+//! taken verbatim, without CSV quoting, and blank cells mean the dataset has
+//! no file of that kind. Exported manifests always carry every column, so that
+//! they can be edited in a spreadsheet and imported again. The supplementary
+//! columns, `twobit` through `vcf_index`, are kept in the metadata sidecar and
+//! only touched where present, so that older manifests don't drop them.
+//!
+//! A manifest may also carry a `<kind>_sha256` column per file kind, e.g.
+//! `fasta_sha256`, holding the SHA-256 digest expected of that file. These are
+//! written on request from the digests stored in the registry's metadata, left
+//! blank where none is known, and imported back into the metadata.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::Path,
+};
+
+use anyhow::Result;
+use refman::prelude::{Project, RefDataset};
+
+use crate::{
+    dataset::{DatasetSpec, FileKind, RefDatasetExt},
+    errors::ManifestError,
+    hashing::Algorithm,
+    metadata::{DatasetMeta, Metadata},
+    project::ProjectExt,
+    registry,
+};
+
+/// The suffix of the column holding the SHA-256 digest of each file kind.
+const SHA256_SUFFIX: &str = "_sha256";
+
+/// One row of a manifest: the dataset to register, its supplementary files,
+/// and the digests expected of its files.
+#[derive(Debug, Clone)]
+pub struct ManifestEntry {
+    pub spec: DatasetSpec,
+    /// The cells of the supplementary columns present, where a blank one means
+    /// the dataset has no file of that kind.
+    pub supplementary: BTreeMap<FileKind, String>,
+    pub sha256: BTreeMap<FileKind, String>,
+}
+
+/// Pull the supplementary file cells out of a row, blank or not, as
+/// `DatasetSpec` has no place for them.
+fn take_supplementary(map: &mut HashMap<String, String>) -> BTreeMap<FileKind, String> {
+    FileKind::SUPPLEMENTARY
+        .into_iter()
+        .filter_map(|kind| Some((kind, map.remove(kind.as_str())?)))
+        .collect()
+}
+
+/// Pull the non-blank `<kind>_sha256` cells out of a row, validating each one.
+fn take_checksums(
+    map: &mut HashMap<String, String>,
+    row: usize,
+) -> Result<BTreeMap<FileKind, String>, ManifestError> {
+    let columns: Vec<String> = map
+        .keys()
+        .filter(|column| column.ends_with(SHA256_SUFFIX))
+        .cloned()
+        .collect();
+    let mut sha256 = BTreeMap::new();
+    for column in columns {
+        let cell = map.remove(&column).unwrap_or_default();
+        let kind = column
+            .trim_end_matches(SHA256_SUFFIX)
+            .parse::<FileKind>()
+            .map_err(|message| ManifestError::InvalidRow { row, message })?;
+        if cell.is_empty() {
+            continue;
+        }
+        let Some(digest) = Algorithm::Sha256.normalize(&cell) else {
+            return Err(ManifestError::InvalidRow {
+                row,
+                message: format!("the {column} '{cell}' is not a SHA-256 digest"),
+            });
+        };
+        sha256.insert(kind, digest);
+    }
+    Ok(sha256)
+}
+
+/// The delimiter of the manifest at `path`, going by its extension.
+fn delimiter(path: &Path) -> char {
+    match path.extension() {
+        Some(extension) if extension.eq_ignore_ascii_case("csv") => ',',
+        _ => '\t',
+    }
+}
+
+/// Parse the contents of a manifest into one entry per row. Rows are numbered
+/// by their line in the file, counting the header as line 1, and blank lines
+/// or lines starting with `#` are skipped.
+pub fn parse(contents: &str, delimiter: char) -> Result<Vec<ManifestEntry>, ManifestError> {
+    let mut lines = contents
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim_end_matches('\r')))
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'));
+    let Some((_, header)) = lines.next() else {
+        return Err(ManifestError::MissingHeader);
+    };
+    let columns: Vec<String> = header
+        .split(delimiter)
+        .map(|column| column.trim().to_ascii_lowercase())
+        .collect();
+    if !columns.iter().any(|column| column == "label") {
+        return Err(ManifestError::MissingHeader);
+    }
+
+    let mut entries = Vec::new();
+    let mut first_rows: HashMap<String, usize> = HashMap::new();
+    for (row, line) in lines {
+        let cells: Vec<&str> = line.split(delimiter).map(str::trim).collect();
+        if cells.len() != columns.len() {
+            return Err(ManifestError::RowLength {
+                row,
+                expected: columns.len(),
+                actual: cells.len(),
+            });
+        }
+        let mut map = columns
+            .iter()
+            .cloned()
+            .zip(cells.into_iter().map(str::to_string))
+            .collect();
+        let sha256 = take_checksums(&mut map, row)?;
+        let supplementary = take_supplementary(&mut map);
+        let spec = DatasetSpec::from_map(map)
+            .map_err(|message| ManifestError::InvalidRow { row, message })?;
+        if spec.label.is_empty() {
+            return Err(ManifestError::InvalidRow {
+                row,
+                message: "the label is blank".to_string(),
+            });
+        }
+        if let Some(&first_row) = first_rows.get(&spec.label) {
+            return Err(ManifestError::DuplicateLabel {
+                label: spec.label,
+                row,
+                first_row,
+            });
+        }
+        first_rows.insert(spec.label.clone(), row);
+        entries.push(ManifestEntry {
+            spec,
+            supplementary,
+            sha256,
+        });
+    }
+    Ok(entries)
+}
+
+/// Read the manifest at `path` into one entry per row.
+pub fn read(path: &Path) -> Result<Vec<ManifestEntry>, ManifestError> {
+    let contents = fs::read_to_string(path).map_err(|source| ManifestError::Read {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    parse(&contents, delimiter(path))
+}
+
+/// One dataset's label, files of every kind in `FileKind::every` order, and the
+/// digests known for its files, as exported to a manifest row.
+type Row<'a> = (
+    &'a str,
+    Vec<(FileKind, Option<&'a str>)>,
+    HashMap<FileKind, String>,
+);
+
+/// Render datasets as a manifest with a header row and every column, where
+/// missing files are left blank. With `checksums`, a `<kind>_sha256` column
+/// follows the file columns, blank where no digest is known. A cell that would
+/// contain the delimiter or a line break couldn't be read back, and so is
+/// rejected.
+pub fn render<'a>(
+    datasets: impl IntoIterator<Item = Row<'a>>,
+    delimiter: char,
+    checksums: bool,
+) -> Result<String, ManifestError> {
+    let separator = delimiter.to_string();
+    let checksum_columns: Vec<String> = FileKind::every()
+        .filter(|_| checksums)
+        .map(|kind| format!("{kind}{SHA256_SUFFIX}"))
+        .collect();
+    let mut header: Vec<&str> = std::iter::once("label")
+        .chain(FileKind::every().map(FileKind::as_str))
+        .collect();
+    header.extend(checksum_columns.iter().map(String::as_str));
+    let mut contents = header.join(&separator);
+    contents.push('\n');
+    for (label, fields, sha256) in datasets {
+        let cells: Vec<(&str, &str)> = std::iter::once(("label", label))
+            .chain(
+                fields
+                    .into_iter()
+                    .map(|(kind, value)| (kind.as_str(), value.unwrap_or_default())),
+            )
+            .collect();
+        if let Some((column, _)) = cells
+            .iter()
+            .find(|(_, cell)| cell.contains(delimiter) || cell.contains(['\n', '\r']))
+        {
+            return Err(ManifestError::UnrepresentableCell {
+                label: label.to_string(),
+                column: (*column).to_string(),
+            });
+        }
+        let digests = FileKind::every()
+            .filter(|_| checksums)
+            .map(|kind| sha256.get(&kind).map_or("", String::as_str));
+        let row: Vec<&str> = cells
+            .into_iter()
+            .map(|(_, cell)| cell)
+            .chain(digests)
+            .collect();
+        contents.push_str(&row.join(&separator));
+        contents.push('\n');
+    }
+    Ok(contents)
+}
+
+/// Atomically write `datasets` to a manifest at `path`, comma-separated if it
+/// ends in `.csv` and tab-separated otherwise, with the supplementary files
+/// `metadata` holds for each. With `checksums`, the manifest also carries the
+/// digests it holds for each file.
+pub fn write(
+    path: &Path,
+    datasets: &[RefDataset],
+    metadata: &Metadata,
+    checksums: bool,
+) -> Result<()> {
+    let rows = datasets.iter().map(|dataset| {
+        let meta = metadata.get(&dataset.label);
+        let supplementary =
+            FileKind::SUPPLEMENTARY.map(|kind| (kind, meta.and_then(|meta| meta.file(kind))));
+        let sha256 = meta
+            .filter(|_| checksums)
+            .map(DatasetMeta::sha256)
+            .unwrap_or_default();
+        let files = dataset.fields().into_iter().chain(supplementary).collect();
+        (dataset.label.as_str(), files, sha256)
+    });
+    let contents = render(rows, delimiter(path), checksums)?;
+    registry::write_file_atomic(path, &contents)
+}
+
+/// Validate and register each of `specs` with `project` in turn, dropping the
+/// digests and mirrors `metadata` holds for any dataset whose files change.
+pub async fn register_specs(
+    mut project: Project,
+    specs: Vec<DatasetSpec>,
+    metadata: &mut Metadata,
+) -> Result<Project> {
+    for spec in specs {
+        let dataset = spec.build().await?;
+        if !project.already_holds(&dataset) {
+            metadata.invalidate(&dataset.label);
+            project = project.register(dataset)?;
+        }
+    }
+    Ok(project)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_manifest() {
+        let contents = "label\tfasta\tgff\n\
+                        GRCh38\thttps://example.org/hg38.fa.gz\t\n\
+                        \n\
+                        mm10\thttps://example.org/mm10.fa.gz\thttps://example.org/mm10.gff3\n";
+        let entries = parse(contents, '\t').unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].spec.label, "GRCh38");
+        assert!(!entries[0].spec.files.contains_key(&FileKind::Gff));
+        assert!(entries[0].sha256.is_empty());
+        let gff = entries[1].spec.files.get(&FileKind::Gff);
+        assert_eq!(
+            gff.map(String::as_str),
+            Some("https://example.org/mm10.gff3")
+        );
+    }
+
+    #[test]
+    fn test_render_round_trip() {
+        let entries = parse("label\tfasta\tbed\nmm10\tmm10.fa\t\n", '\t').unwrap();
+        let fields = FileKind::every()
+            .map(|kind| (kind, (kind == FileKind::Fasta).then_some("mm10.fa")))
+            .collect();
+        let rendered = render([("mm10", fields, HashMap::new())], '\t', false).unwrap();
+        assert_eq!(
+            rendered,
+            "label\tfasta\tgenbank\tgfa\tgff\tgtf\tbed\ttwobit\tnib\tvcf\tvcf_index\n\
+             mm10\tmm10.fa\t\t\t\t\t\t\t\t\t\n"
+        );
+        let reparsed = parse(&rendered, '\t').unwrap();
+        assert_eq!(reparsed[0].spec.files, entries[0].spec.files);
+        assert_eq!(reparsed[0].supplementary.len(), 4);
+        assert!(reparsed[0].supplementary.values().all(String::is_empty));
+
+        assert_eq!(
+            render([], ',', false).unwrap(),
+            "label,fasta,genbank,gfa,gff,gtf,bed,twobit,nib,vcf,vcf_index\n"
+        );
+    }
+
+    #[test]
+    fn test_checksum_columns_round_trip() {
+        let digest = "ab".repeat(32);
+        let fields = FileKind::every()
+            .map(|kind| (kind, FileKind::ALL[..5].contains(&kind).then_some("x")))
+            .collect();
+        let sha256 = HashMap::from([(FileKind::Fasta, digest.to_uppercase())]);
+        let rendered = render([("mm10", fields, sha256)], ',', true).unwrap();
+        let header = rendered.lines().next().unwrap();
+        assert!(
+            header.ends_with(",bed_sha256,twobit_sha256,nib_sha256,vcf_sha256,vcf_index_sha256")
+        );
+
+        let entries = parse(&rendered, ',').unwrap();
+        assert_eq!(entries[0].spec.files.len(), 5);
+        assert_eq!(
+            entries[0].sha256,
+            BTreeMap::from([(FileKind::Fasta, digest)])
+        );
+
+        let invalid = "label,fasta,fasta_sha256\nmm10,mm10.fa,abc\n";
+        assert!(matches!(
+            parse(invalid, ','),
+            Err(ManifestError::InvalidRow { row: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn test_supplementary_columns_round_trip() {
+        let digest = "cd".repeat(32);
+        let fields = FileKind::every()
+            .map(|kind| match kind {
+                FileKind::Fasta => (kind, Some("hg38.fa")),
+                FileKind::TwoBit => (kind, Some("hg38.2bit")),
+                _ => (kind, None),
+            })
+            .collect();
+        let sha256 = HashMap::from([(FileKind::TwoBit, digest.clone())]);
+        let rendered = render([("GRCh38", fields, sha256)], '\t', true).unwrap();
+
+        let entries = parse(&rendered, '\t').unwrap();
+        assert_eq!(entries[0].spec.files.len(), 1);
+        assert_eq!(
+            entries[0]
+                .supplementary
+                .get(&FileKind::TwoBit)
+                .map(String::as_str),
+            Some("hg38.2bit")
+        );
+        assert_eq!(
+            entries[0]
+                .supplementary
+                .get(&FileKind::Vcf)
+                .map(String::as_str),
+            Some("")
+        );
+        assert_eq!(
+            entries[0].sha256,
+            BTreeMap::from([(FileKind::TwoBit, digest)])
+        );
+
+        let older = parse("label,fasta\nGRCh38,hg38.fa\n", ',').unwrap();
+        assert!(older[0].supplementary.is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_label_names_rows() {
+        let contents = "label,fasta\nGRCh38,a.fa\nmm10,b.fa\nGRCh38,c.fa\n";
+        match parse(contents, ',') {
+            Err(ManifestError::DuplicateLabel {
+                label,
+                row,
+                first_row,
+            }) => assert_eq!((label.as_str(), row, first_row), ("GRCh38", 4, 2)),
+            other => panic!("expected a duplicate label error, got {other:?}"),
+        }
+    }
+}
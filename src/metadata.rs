@@ -0,0 +1,760 @@
+//! The metadata submodule.
+//!
+//! `refman`'s registry only knows about a dataset's label and file URLs, and it
+//! rewrites `refman.toml` from scratch whenever it saves a project, dropping
+//! anything it doesn't recognize. Metadata the bindings attach to datasets, like
+//! aliases, therefore lives in a sidecar file next to the registry, e.g.
+//! `refman.meta.toml`, keyed by each dataset's canonical label.
+//!
+//! The sidecar records the version of its layout as `schema_version`. Sidecars
+//! from older versions are read with any fields added since filled in with
+//! their defaults and are written back at the current version, while sidecars
+//! from newer versions are rejected rather than silently losing what this
+//! version doesn't understand.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{dataset::FileKind, errors::MetadataError, registry};
+
+/// The version of the sidecar layout this build reads and writes. Sidecars
+/// written before the version was recorded count as version 0.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// The version a sidecar was written with, read before the rest of it.
+#[derive(Deserialize)]
+struct SchemaVersion {
+    #[serde(default)]
+    schema_version: u32,
+}
+
+/// A sidecar as written to disk, stamped with the current version.
+#[derive(Serialize)]
+struct Versioned<'a> {
+    schema_version: u32,
+    #[serde(flatten)]
+    metadata: &'a Metadata,
+}
+
+/// What is known about the content of one of a dataset's files, e.g. as
+/// observed during an earlier download.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileIntegrity {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+}
+
+/// The bindings-specific metadata attached to a single dataset.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DatasetMeta {
+    /// Secondary labels the dataset can also be looked up by.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+
+    /// Free-form key-value metadata, e.g. assembly level or a contact, that
+    /// refman carries along but never interprets.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub extra: BTreeMap<String, String>,
+
+    /// Integrity information for the dataset's files, keyed by file kind.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub integrity: BTreeMap<String, FileIntegrity>,
+
+    /// Categories the dataset belongs to, e.g. "bacteria" or "host", for
+    /// filtering listings.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+
+    /// Free text describing the dataset, e.g. `GRCh38 primary assembly, Ensembl
+    /// release 110`. Purely informational.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// Sources of files `refman` has no dataset field for, like `.2bit`
+    /// sequence or a VCF and its index, keyed by file kind.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub files: BTreeMap<String, String>,
+
+    /// Fallback sources for the dataset's files, keyed by file kind, tried in
+    /// order when the registered source fails to download.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub mirrors: BTreeMap<String, Vec<String>>,
+
+    /// Names to write the dataset's files under when downloaded, keyed by file
+    /// kind, for sources whose URLs don't end in a usable one, like NCBI's
+    /// E-utilities.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub names: BTreeMap<String, String>,
+}
+
+impl DatasetMeta {
+    fn is_empty(&self) -> bool {
+        self.aliases.is_empty()
+            && self.extra.is_empty()
+            && self.integrity.is_empty()
+            && self.tags.is_empty()
+            && self.description.is_none()
+            && self.files.is_empty()
+            && self.mirrors.is_empty()
+            && self.names.is_empty()
+    }
+
+    /// The supplementary files of the dataset, in the order of
+    /// `FileKind::SUPPLEMENTARY`.
+    pub fn supplementary(&self) -> Vec<(FileKind, String)> {
+        FileKind::SUPPLEMENTARY
+            .into_iter()
+            .filter_map(|kind| Some((kind, self.files.get(kind.as_str())?.clone())))
+            .collect()
+    }
+
+    pub fn file(&self, kind: FileKind) -> Option<&str> {
+        self.files.get(kind.as_str()).map(String::as_str)
+    }
+
+    /// Whether the dataset carries `tag`, ignoring case.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags
+            .iter()
+            .any(|candidate| candidate.eq_ignore_ascii_case(tag))
+    }
+
+    pub fn integrity(&self, kind: FileKind) -> Option<&FileIntegrity> {
+        self.integrity.get(kind.as_str())
+    }
+
+    /// One field of the integrity information for each of the dataset's files
+    /// where it is known, by file kind.
+    fn known<T>(&self, field: impl Fn(&FileIntegrity) -> Option<T>) -> HashMap<FileKind, T> {
+        FileKind::every()
+            .filter_map(|kind| Some((kind, field(self.integrity(kind)?)?)))
+            .collect()
+    }
+
+    /// The SHA-256 digests known for the dataset's files, by file kind.
+    pub fn sha256(&self) -> HashMap<FileKind, String> {
+        self.known(|integrity| integrity.sha256.clone())
+    }
+
+    /// The sizes known for the dataset's files, by file kind.
+    pub fn sizes(&self) -> HashMap<FileKind, u64> {
+        self.known(|integrity| integrity.size)
+    }
+
+    /// The mirrors of the dataset's files, by file kind.
+    pub fn mirrors(&self) -> HashMap<FileKind, Vec<String>> {
+        FileKind::every()
+            .filter_map(|kind| Some((kind, self.mirrors.get(kind.as_str())?.clone())))
+            .collect()
+    }
+
+    /// The names to write the dataset's files under, by file kind.
+    pub fn file_names(&self) -> HashMap<FileKind, String> {
+        FileKind::every()
+            .filter_map(|kind| Some((kind, self.names.get(kind.as_str())?.clone())))
+            .collect()
+    }
+}
+
+/// Metadata for every dataset in a registry, keyed by canonical label.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Metadata {
+    #[serde(default)]
+    datasets: BTreeMap<String, DatasetMeta>,
+}
+
+impl Metadata {
+    /// The sidecar file holding metadata for the registry at `registry_path`.
+    pub fn path_for(registry_path: &Path) -> PathBuf {
+        registry_path.with_extension("meta.toml")
+    }
+
+    /// Read the metadata sidecar for a registry, which is empty when the
+    /// sidecar doesn't exist yet.
+    pub fn load(registry_path: &Path) -> Result<Self> {
+        let path = Self::path_for(registry_path);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = fs::read_to_string(&path).with_context(|| {
+            format!("Failed to read dataset metadata from '{}'.", path.display())
+        })?;
+        Self::parse(&contents, &path)
+    }
+
+    fn parse(contents: &str, path: &Path) -> Result<Self> {
+        let invalid = || format!("Failed to parse dataset metadata in '{}'.", path.display());
+        let SchemaVersion { schema_version } = toml::from_str(contents).with_context(invalid)?;
+        if schema_version > SCHEMA_VERSION {
+            return Err(MetadataError::UnsupportedSchemaVersion {
+                path: path.to_path_buf(),
+                found: schema_version,
+                supported: SCHEMA_VERSION,
+            }
+            .into());
+        }
+        toml::from_str(contents).with_context(invalid)
+    }
+
+    /// Atomically write the metadata sidecar for a registry.
+    pub fn save(&self, registry_path: &Path) -> Result<()> {
+        let versioned = Versioned {
+            schema_version: SCHEMA_VERSION,
+            metadata: self,
+        };
+        let contents = toml::to_string_pretty(&versioned)
+            .context("Failed to serialize dataset metadata to TOML.")?;
+        registry::write_file_atomic(&Self::path_for(registry_path), &contents)
+    }
+
+    pub fn get(&self, label: &str) -> Option<&DatasetMeta> {
+        self.datasets.get(label)
+    }
+
+    /// The supplementary files of `label`, as for `DatasetMeta::supplementary`.
+    pub fn supplementary(&self, label: &str) -> Vec<(FileKind, String)> {
+        self.get(label)
+            .map(DatasetMeta::supplementary)
+            .unwrap_or_default()
+    }
+
+    /// The names to write the files of `label` under: those recorded for it,
+    /// overridden by `renames`.
+    pub fn file_names(
+        &self,
+        label: &str,
+        renames: &HashMap<FileKind, String>,
+    ) -> HashMap<FileKind, String> {
+        let mut names = self
+            .get(label)
+            .map(DatasetMeta::file_names)
+            .unwrap_or_default();
+        names.extend(renames.iter().map(|(&kind, name)| (kind, name.clone())));
+        names
+    }
+
+    /// The canonical label `name` refers to, which is `name` itself unless it is
+    /// an alias of some dataset.
+    pub fn resolve<'a>(&'a self, name: &'a str) -> &'a str {
+        self.datasets
+            .iter()
+            .find(|(_, meta)| meta.aliases.iter().any(|alias| alias == name))
+            .map_or(name, |(label, _)| label.as_str())
+    }
+
+    /// Check that `label` and `aliases` don't collide with any other dataset's
+    /// label or aliases, where `is_label` tells whether a name is already used
+    /// as a dataset label in the registry.
+    pub fn check_names(
+        &self,
+        label: &str,
+        aliases: &[String],
+        is_label: impl Fn(&str) -> bool,
+    ) -> Result<()> {
+        let owner = self.resolve(label);
+        if owner != label {
+            bail!("The label '{label}' is already an alias of dataset '{owner}'.");
+        }
+        for alias in aliases {
+            if alias == label || is_label(alias) {
+                bail!("The alias '{alias}' is already used as a dataset label.");
+            }
+            let owner = self.resolve(alias);
+            if owner != alias && owner != label {
+                bail!("The alias '{alias}' is already an alias of dataset '{owner}'.");
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply `change` to the metadata of `label`, dropping the entry entirely
+    /// if that leaves it empty.
+    fn update(&mut self, label: &str, change: impl FnOnce(&mut DatasetMeta)) {
+        let meta = self.datasets.entry(label.to_string()).or_default();
+        change(meta);
+        if meta.is_empty() {
+            self.datasets.remove(label);
+        }
+    }
+
+    /// Replace the aliases of `label`.
+    pub fn set_aliases(&mut self, label: &str, mut aliases: Vec<String>) {
+        aliases.sort();
+        aliases.dedup();
+        self.update(label, |meta| meta.aliases = aliases);
+    }
+
+    /// Replace the tags of `label`.
+    pub fn set_tags(&mut self, label: &str, mut tags: Vec<String>) {
+        tags.sort();
+        tags.dedup_by(|a, b| a.eq_ignore_ascii_case(b));
+        self.update(label, |meta| meta.tags = tags);
+    }
+
+    /// Replace the description of `label`, where an empty one removes it.
+    pub fn set_description(&mut self, label: &str, description: String) {
+        self.update(label, |meta| {
+            meta.description = Some(description).filter(|text| !text.trim().is_empty());
+        });
+    }
+
+    /// Set the source of one of the supplementary files of `label`, where an
+    /// empty source removes it. Returns whether the source changed.
+    pub fn set_file(&mut self, label: &str, kind: FileKind, source: String) -> bool {
+        let mut changed = false;
+        self.update(label, |meta| {
+            changed = meta.files.get(kind.as_str()).map_or("", String::as_str) != source;
+            if source.is_empty() {
+                meta.files.remove(kind.as_str());
+            } else {
+                meta.files.insert(kind.to_string(), source);
+            }
+        });
+        changed
+    }
+
+    /// Replace the mirrors of the `kind` file of `label`, dropping them when
+    /// `mirrors` is empty.
+    pub fn set_mirrors(&mut self, label: &str, kind: FileKind, mirrors: Vec<String>) {
+        self.update(label, |meta| {
+            if mirrors.is_empty() {
+                meta.mirrors.remove(kind.as_str());
+            } else {
+                meta.mirrors.insert(kind.to_string(), mirrors);
+            }
+        });
+    }
+
+    /// Replace the names the files of `label` are written under.
+    pub fn set_file_names(&mut self, label: &str, names: BTreeMap<FileKind, String>) {
+        self.update(label, |meta| {
+            meta.names = names
+                .into_iter()
+                .map(|(kind, name)| (kind.to_string(), name))
+                .collect();
+        });
+    }
+
+    /// Replace the free-form metadata of `label`.
+    pub fn set_extra(&mut self, label: &str, extra: BTreeMap<String, String>) {
+        self.update(label, |meta| meta.extra = extra);
+    }
+
+    /// Record integrity information for one of the files of `label`, returning
+    /// whether that changed what was stored.
+    pub fn set_integrity(&mut self, label: &str, kind: FileKind, integrity: FileIntegrity) -> bool {
+        let mut changed = false;
+        self.update(label, |meta| {
+            changed = meta.integrity.get(kind.as_str()) != Some(&integrity);
+            meta.integrity.insert(kind.to_string(), integrity);
+        });
+        changed
+    }
+
+    /// Record the expected SHA-256 digest of one of the files of `label`,
+    /// keeping anything else known about that file.
+    pub fn set_sha256(&mut self, label: &str, kind: FileKind, sha256: String) {
+        self.update(label, |meta| {
+            meta.integrity.entry(kind.to_string()).or_default().sha256 = Some(sha256);
+        });
+    }
+
+    /// Record the size and `ETag` one of the files of `label` was just fetched
+    /// with, keeping any known digest, and return whether anything changed.
+    pub fn record_fetch(
+        &mut self,
+        label: &str,
+        kind: FileKind,
+        size: u64,
+        etag: Option<String>,
+    ) -> bool {
+        let mut changed = false;
+        self.update(label, |meta| {
+            let integrity = meta.integrity.entry(kind.to_string()).or_default();
+            changed = integrity.size != Some(size) || integrity.etag != etag;
+            integrity.size = Some(size);
+            integrity.etag = etag;
+        });
+        changed
+    }
+
+    /// Drop the integrity information, mirrors, and file names of `label`, e.g.
+    /// once its files are replaced by different sources, which none applies to.
+    pub fn invalidate(&mut self, label: &str) {
+        self.update(label, |meta| {
+            meta.integrity.clear();
+            meta.mirrors.clear();
+            meta.names.clear();
+        });
+    }
+
+    /// Drop the integrity information, mirrors, and file name of one of the
+    /// files of `label`, e.g. once its source is replaced.
+    pub fn invalidate_file(&mut self, label: &str, kind: FileKind) {
+        self.update(label, |meta| {
+            meta.integrity.remove(kind.as_str());
+            meta.mirrors.remove(kind.as_str());
+            meta.names.remove(kind.as_str());
+        });
+    }
+
+    /// Replace all metadata of `label` with `meta`, e.g. as carried over from
+    /// another registry.
+    pub fn replace(&mut self, label: &str, meta: DatasetMeta) {
+        self.update(label, |existing| *existing = meta);
+    }
+
+    /// Fold `incoming` metadata for `label`, e.g. from another registry holding
+    /// the same dataset, into what is already recorded for it. Aliases and tags
+    /// are combined, and anything recorded on only one side is kept. Where both
+    /// sides record something different, `incoming` wins only with `overwrite`,
+    /// and a supplementary file it replaces brings its own digests and mirrors
+    /// rather than keeping those of the old source.
+    pub fn absorb(&mut self, label: &str, incoming: DatasetMeta, overwrite: bool) {
+        self.update(label, |meta| {
+            for alias in incoming.aliases {
+                if !meta.aliases.contains(&alias) {
+                    meta.aliases.push(alias);
+                }
+            }
+            meta.aliases.sort();
+            for tag in incoming.tags {
+                if !meta.has_tag(&tag) {
+                    meta.tags.push(tag);
+                }
+            }
+            if meta.description.is_none() || overwrite {
+                meta.description = incoming.description.or(meta.description.take());
+            }
+            for (key, value) in incoming.extra {
+                if overwrite || !meta.extra.contains_key(&key) {
+                    meta.extra.insert(key, value);
+                }
+            }
+            // digests and mirrors only carry over along with the file they were
+            // recorded for
+            let mut kept = Vec::new();
+            for (kind, source) in incoming.files {
+                match meta.files.get(&kind) {
+                    Some(existing) if *existing == source => {}
+                    Some(_) if !overwrite => kept.push(kind),
+                    _ => {
+                        meta.integrity.remove(&kind);
+                        meta.mirrors.remove(&kind);
+                        meta.files.insert(kind, source);
+                    }
+                }
+            }
+            for (kind, integrity) in incoming.integrity {
+                if !kept.contains(&kind) && (overwrite || !meta.integrity.contains_key(&kind)) {
+                    meta.integrity.insert(kind, integrity);
+                }
+            }
+            for (kind, mirrors) in incoming.mirrors {
+                if !kept.contains(&kind) && (overwrite || !meta.mirrors.contains_key(&kind)) {
+                    meta.mirrors.insert(kind, mirrors);
+                }
+            }
+        });
+    }
+
+    /// Move the metadata of `old_label` over to `new_label`, e.g. once the
+    /// dataset is relabeled.
+    pub fn rename(&mut self, old_label: &str, new_label: &str) {
+        if let Some(meta) = self.datasets.remove(old_label) {
+            self.datasets.insert(new_label.to_string(), meta);
+        }
+    }
+
+    /// Drop all metadata for `label`, e.g. once the dataset is removed.
+    pub fn forget(&mut self, label: &str) {
+        self.datasets.remove(label);
+    }
+
+    /// Print the aliases of `label`, or of every dataset when `label` is `None`,
+    /// to accompany `refman`'s own listing.
+    pub fn prettyprint_aliases(&self, label: Option<&str>) {
+        let with_aliases: Vec<_> = self
+            .datasets
+            .iter()
+            .filter(|(name, meta)| {
+                !meta.aliases.is_empty() && label.is_none_or(|label| label == name.as_str())
+            })
+            .collect();
+        if with_aliases.is_empty() {
+            return;
+        }
+        println!("Aliases:");
+        for (name, meta) in with_aliases {
+            println!("  {name}: {}", meta.aliases.join(", "));
+        }
+    }
+
+    /// Print the description of `label`, or of every dataset when `label` is
+    /// `None`, to accompany `refman`'s own listing.
+    pub fn prettyprint_descriptions(&self, label: Option<&str>) {
+        let described: Vec<_> = self
+            .datasets
+            .iter()
+            .filter(|(name, _)| label.is_none_or(|label| label == name.as_str()))
+            .filter_map(|(name, meta)| Some((name, meta.description.as_deref()?)))
+            .collect();
+        if described.is_empty() {
+            return;
+        }
+        println!("Descriptions:");
+        for (name, description) in described {
+            println!("  {name}: {description}");
+        }
+    }
+
+    /// Print the sizes recorded for the files of `label`, or of every dataset
+    /// when `label` is `None`, for verbose listings.
+    pub fn prettyprint_sizes(&self, label: Option<&str>) {
+        let sizes: Vec<_> = self
+            .datasets
+            .iter()
+            .filter(|(name, _)| label.is_none_or(|label| label == name.as_str()))
+            .flat_map(|(name, meta)| {
+                let sizes = meta.sizes();
+                FileKind::every()
+                    .filter_map(move |kind| Some((name, kind, *sizes.get(&kind)?)))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        if sizes.is_empty() {
+            return;
+        }
+        println!("Recorded sizes:");
+        for (name, kind, size) in sizes {
+            println!("  {name} ({kind}): {size} bytes");
+        }
+    }
+
+    /// Print the supplementary files of `label`, or of every dataset when
+    /// `label` is `None`, to accompany `refman`'s own listing.
+    pub fn prettyprint_supplementary(&self, label: Option<&str>) {
+        let with_files: Vec<_> = self
+            .datasets
+            .iter()
+            .filter(|(name, meta)| {
+                !meta.files.is_empty() && label.is_none_or(|label| label == name.as_str())
+            })
+            .collect();
+        if with_files.is_empty() {
+            return;
+        }
+        println!("Supplementary files:");
+        for (name, meta) in with_files {
+            for (kind, source) in meta.supplementary() {
+                println!("  {name} ({kind}): {source}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schema_version() {
+        let path = Path::new("refman.meta.toml");
+        let mut metadata = Metadata::default();
+        metadata.set_aliases("GRCh38", vec!["hg38".to_string()]);
+        let versioned = Versioned {
+            schema_version: SCHEMA_VERSION,
+            metadata: &metadata,
+        };
+        let contents = toml::to_string_pretty(&versioned).unwrap();
+        assert!(contents.starts_with(&format!("schema_version = {SCHEMA_VERSION}")));
+        assert_eq!(Metadata::parse(&contents, path).unwrap(), metadata);
+
+        // sidecars from before versioning still load
+        let unversioned = "[datasets.GRCh38]\naliases = [\"hg38\"]\n";
+        assert_eq!(Metadata::parse(unversioned, path).unwrap(), metadata);
+
+        let newer = format!("schema_version = {}\n", SCHEMA_VERSION + 1);
+        let err = Metadata::parse(&newer, path).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(MetadataError::UnsupportedSchemaVersion { .. })
+        ));
+    }
+
+    #[test]
+    fn test_resolve_aliases() {
+        let mut metadata = Metadata::default();
+        metadata.set_aliases("GRCh38", vec!["hg38".to_string(), "human".to_string()]);
+        assert_eq!(metadata.resolve("hg38"), "GRCh38");
+        assert_eq!(metadata.resolve("human"), "GRCh38");
+        assert_eq!(metadata.resolve("GRCh38"), "GRCh38");
+        assert_eq!(metadata.resolve("mm10"), "mm10");
+
+        metadata.set_aliases("GRCh38", Vec::new());
+        assert_eq!(metadata.resolve("hg38"), "hg38");
+        assert!(metadata.get("GRCh38").is_none());
+    }
+
+    #[test]
+    fn test_extra_round_trip() {
+        let mut metadata = Metadata::default();
+        metadata.set_aliases("GRCh38", vec!["hg38".to_string()]);
+        metadata.set_extra(
+            "GRCh38",
+            BTreeMap::from([
+                ("assembly_level".to_string(), "chromosome".to_string()),
+                (
+                    "contact".to_string(),
+                    "Refs Team <refs@example.org>".to_string(),
+                ),
+            ]),
+        );
+
+        let serialized = toml::to_string_pretty(&metadata).unwrap();
+        let parsed: Metadata = toml::from_str(&serialized).unwrap();
+        assert_eq!(parsed, metadata);
+
+        metadata.set_aliases("GRCh38", Vec::new());
+        assert!(metadata.get("GRCh38").is_some());
+    }
+
+    #[test]
+    fn test_tags_ignore_case() {
+        let mut metadata = Metadata::default();
+        metadata.set_tags(
+            "GRCh38",
+            vec!["Host".to_string(), "human".to_string(), "host".to_string()],
+        );
+        let meta = metadata.get("GRCh38").unwrap();
+        assert_eq!(meta.tags.len(), 2);
+        assert!(meta.has_tag("HOST"));
+        assert!(!meta.has_tag("viral"));
+
+        // sidecars written before tags existed still parse
+        let parsed: Metadata = toml::from_str("[datasets.mm10]\naliases = [\"mouse\"]\n").unwrap();
+        assert!(parsed.get("mm10").unwrap().tags.is_empty());
+    }
+
+    #[test]
+    fn test_absorb() {
+        let twobit = |name: &str| format!("https://example.org/{name}.2bit");
+        let mut metadata = Metadata::default();
+        metadata.set_tags("hg38", vec!["host".to_string()]);
+        metadata.set_file("hg38", FileKind::TwoBit, twobit("hg38"));
+        metadata.set_sha256("hg38", FileKind::TwoBit, "a".repeat(64));
+
+        let mut incoming = Metadata::default();
+        incoming.set_tags("hg38", vec!["Host".to_string(), "human".to_string()]);
+        incoming.set_description("hg38", "GRCh38".to_string());
+        incoming.set_file("hg38", FileKind::TwoBit, twobit("GRCh38"));
+        incoming.set_sha256("hg38", FileKind::TwoBit, "b".repeat(64));
+        incoming.set_sha256("hg38", FileKind::Fasta, "c".repeat(64));
+        let incoming = incoming.get("hg38").unwrap().clone();
+
+        let mut kept = metadata.clone();
+        kept.absorb("hg38", incoming.clone(), false);
+        let meta = kept.get("hg38").unwrap();
+        assert_eq!(meta.tags, ["host", "human"]);
+        assert_eq!(meta.description.as_deref(), Some("GRCh38"));
+        assert_eq!(meta.file(FileKind::TwoBit), Some(twobit("hg38").as_str()));
+        assert_eq!(meta.sha256()[&FileKind::TwoBit], "a".repeat(64));
+        assert_eq!(meta.sha256()[&FileKind::Fasta], "c".repeat(64));
+
+        metadata.absorb("hg38", incoming, true);
+        let meta = metadata.get("hg38").unwrap();
+        assert_eq!(meta.file(FileKind::TwoBit), Some(twobit("GRCh38").as_str()));
+        assert_eq!(meta.sha256()[&FileKind::TwoBit], "b".repeat(64));
+    }
+
+    #[test]
+    fn test_supplementary_files() {
+        let mut metadata = Metadata::default();
+        let source = "https://example.org/hg38.2bit";
+        assert!(metadata.set_file("hg38", FileKind::TwoBit, source.to_string()));
+        assert!(!metadata.set_file("hg38", FileKind::TwoBit, source.to_string()));
+        let meta = metadata.get("hg38").unwrap();
+        assert_eq!(meta.file(FileKind::TwoBit), Some(source));
+        assert_eq!(meta.file(FileKind::Nib), None);
+
+        let parsed: Metadata = toml::from_str(&toml::to_string_pretty(&metadata).unwrap()).unwrap();
+        assert_eq!(parsed, metadata);
+
+        assert!(metadata.set_file("hg38", FileKind::TwoBit, String::new()));
+        assert!(metadata.get("hg38").is_none());
+    }
+
+    #[test]
+    fn test_mirrors() {
+        let mut metadata = Metadata::default();
+        let mirror = "https://mirror.example.org/hg38.fa.gz".to_string();
+        metadata.set_mirrors("hg38", FileKind::Fasta, vec![mirror.clone()]);
+        let meta = metadata.get("hg38").unwrap();
+        assert_eq!(
+            meta.mirrors(),
+            HashMap::from([(FileKind::Fasta, vec![mirror])])
+        );
+
+        metadata.set_mirrors("hg38", FileKind::Fasta, Vec::new());
+        assert!(metadata.get("hg38").is_none());
+    }
+
+    #[test]
+    fn test_sha256_keeps_observed_integrity() {
+        let mut metadata = Metadata::default();
+        let observed = FileIntegrity {
+            sha256: None,
+            size: Some(42),
+            etag: Some("\"abc\"".to_string()),
+        };
+        metadata.set_integrity("GRCh38", FileKind::Fasta, observed);
+        metadata.set_sha256("GRCh38", FileKind::Fasta, "00".repeat(32));
+
+        let meta = metadata.get("GRCh38").unwrap();
+        assert_eq!(
+            meta.sha256(),
+            HashMap::from([(FileKind::Fasta, "00".repeat(32))])
+        );
+        assert_eq!(meta.integrity(FileKind::Fasta).unwrap().size, Some(42));
+
+        metadata.set_mirrors(
+            "GRCh38",
+            FileKind::Fasta,
+            vec!["https://example.org/a.fa".into()],
+        );
+        metadata.invalidate_file("GRCh38", FileKind::Fasta);
+        assert!(metadata.get("GRCh38").is_none());
+    }
+
+    #[test]
+    fn test_alias_conflicts() {
+        let mut metadata = Metadata::default();
+        metadata.set_aliases("GRCh38", vec!["hg38".to_string()]);
+        let is_label = |name: &str| ["GRCh38", "mm10"].contains(&name);
+
+        assert!(metadata
+            .check_names(
+                "GRCh38",
+                &["hg38".to_string(), "human".to_string()],
+                is_label
+            )
+            .is_ok());
+        assert!(metadata
+            .check_names("mm10", &["hg38".to_string()], is_label)
+            .is_err());
+        assert!(metadata
+            .check_names("mm10", &["GRCh38".to_string()], is_label)
+            .is_err());
+        assert!(metadata.check_names("hg38", &[], is_label).is_err());
+    }
+}
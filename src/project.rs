@@ -0,0 +1,994 @@
+//! The project submodule.
+//!
+//! Extension methods for `refman`'s `Project` that the Python bindings need but
+//! that aren't part of the upstream API.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Display,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{Context, Result};
+use refman::prelude::{Project, RefDataset};
+use serde::Serialize;
+
+use crate::{
+    dataset::{self, FileKind, RefDatasetExt},
+    downloads::{self, FileComparison, Provenance, Relocation},
+    errors::LabelError,
+    index::{self, IndexOutcome},
+    manifest,
+    metadata::{DatasetMeta, FileIntegrity, Metadata},
+};
+
+/// The outcome of folding observed download results back into the registry.
+#[derive(Debug, Default)]
+pub struct Reconciliation {
+    /// The `(label, kind)` pairs whose stored integrity information changed.
+    pub updated: Vec<(String, FileKind)>,
+
+    /// Provenance entries that were ignored, with the reason why.
+    pub skipped: Vec<(PathBuf, String)>,
+}
+
+/// What to do when a dataset being merged in is registered under the same label
+/// as a different dataset in the registry it is merged into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// Keep the dataset already in the destination.
+    Skip,
+    /// Replace the dataset in the destination with the incoming one.
+    Overwrite,
+    /// Fail the merge.
+    Error,
+}
+
+impl FromStr for MergeStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "skip" => Ok(MergeStrategy::Skip),
+            "overwrite" => Ok(MergeStrategy::Overwrite),
+            "error" => Ok(MergeStrategy::Error),
+            _ => Err(format!(
+                "Unsupported merge strategy '{s}'. Expected one of: skip, overwrite, error."
+            )),
+        }
+    }
+}
+
+/// The outcome of merging one project into another.
+#[derive(Debug)]
+pub struct Merge {
+    /// The destination project with the incoming datasets folded in.
+    pub project: Project,
+
+    /// The labels of the incoming datasets that were added or replaced.
+    pub merged: Vec<String>,
+
+    /// The labels of the incoming datasets that conflicted and were left out.
+    pub skipped: Vec<String>,
+}
+
+/// How many single-character edits apart a registered label may be from a
+/// missing one and still be suggested in its place.
+pub const SUGGESTION_DISTANCE: usize = 2;
+
+/// A file field that differs between two registrations of the same dataset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldChange {
+    pub kind: FileKind,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+/// How one registry differs from another, as found by `ProjectExt::diff`.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct RegistryDiff {
+    /// The labels only registered in the other registry.
+    pub added: Vec<String>,
+
+    /// The labels only registered in this registry.
+    pub removed: Vec<String>,
+
+    /// The labels registered in both but holding different files, with the
+    /// fields that changed.
+    pub modified: Vec<(String, Vec<FieldChange>)>,
+}
+
+impl RegistryDiff {
+    /// Whether the two registries hold exactly the same datasets.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+impl Display for RegistryDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No differences.");
+        }
+        for label in &self.added {
+            writeln!(f, "+ {label}")?;
+        }
+        for label in &self.removed {
+            writeln!(f, "- {label}")?;
+        }
+        for (label, changes) in &self.modified {
+            writeln!(f, "~ {label}")?;
+            for change in changes {
+                let before = change.before.as_deref().unwrap_or("(none)");
+                let after = change.after.as_deref().unwrap_or("(none)");
+                writeln!(f, "    {}: {before} -> {after}", change.kind)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Aggregate counts over a project's datasets, as tallied by
+/// `ProjectExt::stats`.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct RegistryStats {
+    /// The number of registered datasets.
+    pub datasets: usize,
+
+    /// How many datasets have each kind of file, in field order followed by
+    /// the supplementary kinds.
+    pub per_field: Vec<(FileKind, usize)>,
+
+    /// The number of files registered across every dataset.
+    pub total_files: usize,
+
+    /// How many datasets have no files at all, which is usually a mistake.
+    pub empty: usize,
+}
+
+/// How serious a problem found by `ProjectExt::validate` is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// Something that will probably bite later, e.g. a source that depends on
+    /// an environment variable that isn't set in this shell.
+    Warning,
+    /// Something that is wrong with the registry as it stands.
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A problem with one registered dataset, as found by `ProjectExt::validate`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationIssue {
+    pub severity: Severity,
+    pub label: String,
+    pub message: String,
+}
+
+impl Display for ValidationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}: {}", self.severity, self.label, self.message)
+    }
+}
+
+/// Why `source` can never be fetched as written, if it can't. Only the form of
+/// the source is checked, never whether anything is actually there.
+fn malformed_source(source: &str) -> Option<String> {
+    if downloads::Scheme::of(source) != downloads::Scheme::Local {
+        match reqwest::Url::parse(source) {
+            Err(err) => return Some(format!("is not a valid URL: {err}")),
+            Ok(url) if !url.has_host() => return Some("has no host".to_string()),
+            Ok(_) => {}
+        }
+    }
+    downloads::target_name(source)
+        .err()
+        .map(|_| "does not end in a file name".to_string())
+}
+
+/// The version of the JSON listing schema, bumped only for changes that could
+/// break existing parsers, i.e. renaming or removing fields.
+pub const JSON_SCHEMA_VERSION: u32 = 1;
+
+/// The JSON listing of a registry. Every file field is always present, holding
+/// `null` when the dataset has no file of that kind.
+#[derive(Debug, Serialize)]
+pub struct ProjectListing<'a> {
+    pub schema_version: u32,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub datasets: Vec<DatasetListing<'a>>,
+}
+
+/// One dataset in the JSON listing of a registry.
+#[derive(Debug, Serialize)]
+pub struct DatasetListing<'a> {
+    pub label: &'a str,
+    pub description: Option<&'a str>,
+    pub aliases: &'a [String],
+    pub tags: &'a [String],
+    pub fasta: Option<&'a str>,
+    pub genbank: Option<&'a str>,
+    pub gfa: Option<&'a str>,
+    pub gff: Option<&'a str>,
+    pub gtf: Option<&'a str>,
+    pub bed: Option<&'a str>,
+    pub twobit: Option<&'a str>,
+    pub nib: Option<&'a str>,
+    pub vcf: Option<&'a str>,
+    pub vcf_index: Option<&'a str>,
+    pub extra: BTreeMap<&'a str, &'a str>,
+}
+
+pub trait ProjectExt {
+    /// Look up a registered dataset by label without going through the async
+    /// runtime.
+    fn find_dataset(&self, label: &str) -> Option<&RefDataset>;
+
+    /// The label of every registered dataset, without cloning the datasets.
+    fn labels(&self) -> Vec<String>;
+
+    /// A text field of the project's header, i.e. its `title` or `description`.
+    fn header_text(&self, key: &str) -> Result<Option<String>>;
+
+    /// The registered labels within `max_distance` edits of `query`, ignoring
+    /// case, closest first.
+    fn find_labels(&self, query: &str, max_distance: usize) -> Vec<String>;
+
+    /// The error for a `label` that isn't registered, suggesting the registered
+    /// labels close enough to it that it may be a typo of one.
+    fn not_registered(&self, label: &str) -> LabelError {
+        LabelError::NotRegistered {
+            label: label.to_string(),
+            suggestions: self.find_labels(label, SUGGESTION_DISTANCE),
+        }
+    }
+
+    /// Whether registering `dataset` would leave the project unchanged, i.e. a
+    /// dataset with identical content is already registered under its label.
+    fn already_holds(&self, dataset: &RefDataset) -> bool {
+        self.find_dataset(&dataset.label)
+            .is_some_and(|existing| existing.same_content(dataset))
+    }
+
+    /// Register the dataset under `old_label` under `new_label` instead, keeping
+    /// its files as they are.
+    fn rename_dataset(self, old_label: &str, new_label: &str) -> Result<Project>
+    where
+        Self: Sized;
+
+    /// Serialize the project, or just the dataset under `label`, to JSON in the
+    /// `ProjectListing` schema, along with the metadata attached to each dataset.
+    fn to_json(&self, metadata: &Metadata, label: Option<&str>) -> Result<String>;
+
+    /// Swap the dataset registered under `dataset`'s label for `dataset`.
+    fn replace_dataset(self, dataset: RefDataset) -> Result<Project>
+    where
+        Self: Sized;
+
+    /// Write every registered dataset to a manifest at `path` that
+    /// `manifest::read` can import again, with the supplementary files
+    /// `metadata` holds for them and, with `checksums`, their files' digests.
+    fn export_manifest(&self, path: &Path, metadata: &Metadata, checksums: bool) -> Result<()>;
+
+    /// The project with every dataset removed but its title and description
+    /// kept.
+    fn clear(self) -> Result<Project>
+    where
+        Self: Sized;
+
+    /// Fold the datasets of `other` into this project, keeping this project's
+    /// title and description. Datasets registered identically in both are left
+    /// as they are, and `strategy` decides what happens when the same label holds
+    /// different files in each.
+    fn merge(self, other: &Project, strategy: MergeStrategy) -> Result<Merge>
+    where
+        Self: Sized;
+
+    /// What it would take to turn this project's datasets into `other`'s: the
+    /// labels added and removed, and the files changed under the labels both
+    /// share, including the supplementary files each registry's metadata holds.
+    fn diff(&self, other: &Project, metadata: &Metadata, other_metadata: &Metadata)
+        -> RegistryDiff;
+
+    /// How many datasets are registered, how many have each kind of file,
+    /// counting the supplementary files `metadata` holds, and how many have
+    /// none.
+    fn stats(&self, metadata: &Metadata) -> RegistryStats;
+
+    /// Every consistency problem with the registry and its `metadata` sidecar:
+    /// duplicate or invalid labels, datasets without files, malformed sources,
+    /// sources naming unset environment variables, files without the files they
+    /// depend on, and aliases that shadow labels. Nothing is fetched.
+    fn validate(&self, metadata: &Metadata) -> Vec<ValidationIssue>;
+
+    /// The project with only the datasets tagged with `tag` in `metadata` left in
+    /// it, ignoring case.
+    fn retain_tagged(self, metadata: &Metadata, tag: &str) -> Result<Project>
+    where
+        Self: Sized;
+
+    /// Move the already-downloaded files for `label` and its `supplementary`
+    /// files, named as `file_names` say, from one directory to another, keeping
+    /// any sentinel in step with the new locations.
+    fn relocate_files(
+        &self,
+        label: &str,
+        supplementary: &[(FileKind, String)],
+        file_names: &HashMap<FileKind, String>,
+        from_dir: &Path,
+        to_dir: &Path,
+    ) -> Result<Relocation> {
+        let dataset = self
+            .find_dataset(label)
+            .ok_or_else(|| self.not_registered(label))?;
+        downloads::relocate_files(dataset, supplementary, file_names, from_dir, to_dir)
+    }
+
+    /// Remove the dataset under `label` along with the files a download of it
+    /// and its `supplementary` files into `dest` left there, named as
+    /// `file_names` say, returning the project without it and the paths that
+    /// were deleted.
+    fn remove_with_files(
+        self,
+        label: &str,
+        supplementary: &[(FileKind, String)],
+        file_names: &HashMap<FileKind, String>,
+        dest: &Path,
+    ) -> Result<(Project, Vec<PathBuf>)>
+    where
+        Self: Sized;
+
+    /// The sources a download of `label`, or of every dataset when `label` is
+    /// `None`, would fetch, including the supplementary files `metadata` holds.
+    fn download_urls(&self, metadata: &Metadata, label: Option<&str>) -> Result<Vec<String>>;
+
+    /// The `(url, target path)` pairs a download of `label` and its
+    /// `supplementary` files into `dest`, named as `file_names` say, would
+    /// fetch, without fetching or writing anything.
+    fn plan_download(
+        &self,
+        label: &str,
+        supplementary: &[(FileKind, String)],
+        file_names: &HashMap<FileKind, String>,
+        dest: &Path,
+    ) -> Result<Vec<(String, PathBuf)>> {
+        let dataset = self
+            .find_dataset(label)
+            .ok_or_else(|| self.not_registered(label))?;
+        Ok(downloads::plan_download(
+            dataset,
+            supplementary,
+            file_names,
+            dest,
+        )?)
+    }
+
+    /// Compare the downloaded files for `label` and its `supplementary` files,
+    /// named as `file_names` say, across two directories.
+    fn diff_downloads(
+        &self,
+        label: &str,
+        supplementary: &[(FileKind, String)],
+        file_names: &HashMap<FileKind, String>,
+        dir_a: &Path,
+        dir_b: &Path,
+    ) -> Result<Vec<(String, FileComparison)>> {
+        let dataset = self
+            .find_dataset(label)
+            .ok_or_else(|| self.not_registered(label))?;
+        downloads::diff_downloads(dataset, supplementary, file_names, dir_a, dir_b)
+    }
+
+    /// Fold the checksums, sizes, and `ETag`s recorded in the provenance files
+    /// under `dest` into `metadata` for the datasets they belong to. Only files
+    /// whose source still matches the registered one are taken into account, and
+    /// datasets are never added or removed.
+    fn reconcile(&self, metadata: &mut Metadata, dest: &Path) -> Result<Reconciliation> {
+        let mut reconciliation = Reconciliation::default();
+        for (path, record) in Provenance::find_under(dest)? {
+            let Some(dataset) = self.find_dataset(&record.label) else {
+                reconciliation.skipped.push((
+                    path,
+                    format!("'{}' is not a registered dataset", record.label),
+                ));
+                continue;
+            };
+            // companion indexes have no registered source to reconcile against
+            for file in record.files.into_iter().filter(|file| !file.companion) {
+                let Ok(kind) = file.kind.parse::<FileKind>() else {
+                    reconciliation
+                        .skipped
+                        .push((path.clone(), format!("unknown file kind '{}'", file.kind)));
+                    continue;
+                };
+                let registered = dataset
+                    .files()
+                    .into_iter()
+                    .find_map(|(registered_kind, url)| (registered_kind == kind).then_some(url));
+                if registered != Some(file.source_url.as_str()) {
+                    reconciliation.skipped.push((
+                        path.clone(),
+                        format!(
+                            "the {kind} file of '{}' was downloaded from a different source than is registered",
+                            record.label
+                        ),
+                    ));
+                    continue;
+                }
+                let integrity = FileIntegrity {
+                    sha256: Some(file.sha256),
+                    size: Some(file.size),
+                    etag: file.etag,
+                };
+                if metadata.set_integrity(&record.label, kind, integrity) {
+                    reconciliation.updated.push((record.label.clone(), kind));
+                }
+            }
+        }
+        Ok(reconciliation)
+    }
+
+    /// Build any missing or stale indexes for the files of `label` already
+    /// downloaded to `dir`, without downloading anything.
+    fn build_indexes(
+        &self,
+        label: &str,
+        dir: &Path,
+        kinds: &[FileKind],
+        csi: bool,
+    ) -> Result<Vec<(PathBuf, IndexOutcome)>> {
+        let dataset = self
+            .find_dataset(label)
+            .ok_or_else(|| self.not_registered(label))?;
+        index::build_indexes(dataset, dir, kinds, csi)
+    }
+}
+
+impl ProjectExt for Project {
+    fn find_dataset(&self, label: &str) -> Option<&RefDataset> {
+        self.datasets()
+            .iter()
+            .find(|dataset| dataset.label == label)
+    }
+
+    fn labels(&self) -> Vec<String> {
+        self.datasets()
+            .iter()
+            .map(|dataset| dataset.label.clone())
+            .collect()
+    }
+
+    fn find_labels(&self, query: &str, max_distance: usize) -> Vec<String> {
+        let query = query.to_lowercase();
+        let mut matches: Vec<(usize, &str)> = self
+            .datasets()
+            .iter()
+            .map(|dataset| {
+                let distance = levenshtein(&query, &dataset.label.to_lowercase());
+                (distance, dataset.label.as_str())
+            })
+            .filter(|(distance, _)| *distance <= max_distance)
+            .collect();
+        matches.sort_unstable();
+        matches
+            .into_iter()
+            .map(|(_, label)| label.to_string())
+            .collect()
+    }
+
+    fn download_urls(&self, metadata: &Metadata, label: Option<&str>) -> Result<Vec<String>> {
+        let datasets: Vec<&RefDataset> = match label {
+            Some(label) => vec![self
+                .find_dataset(label)
+                .ok_or_else(|| self.not_registered(label))?],
+            None => self.datasets().iter().collect(),
+        };
+        Ok(datasets
+            .into_iter()
+            .flat_map(|dataset| {
+                let supplementary = metadata
+                    .get(&dataset.label)
+                    .map(DatasetMeta::supplementary)
+                    .unwrap_or_default();
+                dataset
+                    .files_with(&supplementary)
+                    .into_iter()
+                    .map(|(_, url)| url.to_string())
+                    .collect::<Vec<_>>()
+            })
+            .collect())
+    }
+
+    fn header_text(&self, key: &str) -> Result<Option<String>> {
+        // `refman` keeps the title and description to itself, so they are read
+        // back out of the project's serialized form, as found in `refman.toml`
+        let serialized = serde_json::to_value(self).context("Failed to serialize the registry.")?;
+        let header = serialized.get("project").unwrap_or(&serialized);
+        Ok(header
+            .get(key)
+            .and_then(|value| value.as_str())
+            .map(str::to_string))
+    }
+
+    fn to_json(&self, metadata: &Metadata, label: Option<&str>) -> Result<String> {
+        let datasets = self
+            .datasets()
+            .iter()
+            .filter(|dataset| label.is_none_or(|label| label == dataset.label))
+            .map(|dataset| {
+                let meta = metadata.get(&dataset.label);
+                DatasetListing {
+                    label: &dataset.label,
+                    description: meta.and_then(|meta| meta.description.as_deref()),
+                    aliases: meta.map_or(&[], |meta| meta.aliases.as_slice()),
+                    tags: meta.map_or(&[], |meta| meta.tags.as_slice()),
+                    fasta: dataset.fasta.as_deref(),
+                    genbank: dataset.genbank.as_deref(),
+                    gfa: dataset.gfa.as_deref(),
+                    gff: dataset.gff.as_deref(),
+                    gtf: dataset.gtf.as_deref(),
+                    bed: dataset.bed.as_deref(),
+                    twobit: meta.and_then(|meta| meta.file(FileKind::TwoBit)),
+                    nib: meta.and_then(|meta| meta.file(FileKind::Nib)),
+                    vcf: meta.and_then(|meta| meta.file(FileKind::Vcf)),
+                    vcf_index: meta.and_then(|meta| meta.file(FileKind::VcfIndex)),
+                    extra: meta
+                        .into_iter()
+                        .flat_map(|meta| &meta.extra)
+                        .map(|(key, value)| (key.as_str(), value.as_str()))
+                        .collect(),
+                }
+            })
+            .collect();
+        let listing = ProjectListing {
+            schema_version: JSON_SCHEMA_VERSION,
+            title: self.header_text("title")?,
+            description: self.header_text("description")?,
+            datasets,
+        };
+        serde_json::to_string_pretty(&listing).context("Failed to serialize the registry to JSON.")
+    }
+
+    fn rename_dataset(self, old_label: &str, new_label: &str) -> Result<Project> {
+        let mut dataset = self
+            .find_dataset(old_label)
+            .ok_or_else(|| self.not_registered(old_label))?
+            .clone();
+        dataset::validate_label(new_label)?;
+        if self.find_dataset(new_label).is_some() {
+            return Err(LabelError::AlreadyExists(new_label.to_string()).into());
+        }
+        dataset.label = new_label.to_string();
+        Ok(retain_datasets(&self, |held| held.label != old_label)?.register(dataset)?)
+    }
+
+    fn replace_dataset(self, dataset: RefDataset) -> Result<Project> {
+        if self.find_dataset(&dataset.label).is_none() {
+            return Err(self.not_registered(&dataset.label).into());
+        }
+        Ok(retain_datasets(&self, |held| held.label != dataset.label)?.register(dataset)?)
+    }
+
+    fn remove_with_files(
+        self,
+        label: &str,
+        supplementary: &[(FileKind, String)],
+        file_names: &HashMap<FileKind, String>,
+        dest: &Path,
+    ) -> Result<(Project, Vec<PathBuf>)> {
+        let dataset = self
+            .find_dataset(label)
+            .ok_or_else(|| self.not_registered(label))?;
+        let deleted = downloads::delete_files(dataset, supplementary, file_names, dest)?;
+        Ok((self.remove(label)?, deleted))
+    }
+
+    fn export_manifest(&self, path: &Path, metadata: &Metadata, checksums: bool) -> Result<()> {
+        manifest::write(path, self.datasets(), metadata, checksums)
+    }
+
+    fn clear(self) -> Result<Project> {
+        retain_datasets(&self, |_| false)
+    }
+
+    fn merge(self, other: &Project, strategy: MergeStrategy) -> Result<Merge> {
+        let mut project = self;
+        let mut merged = Vec::new();
+        let mut skipped = Vec::new();
+        for dataset in other.datasets() {
+            match project.find_dataset(&dataset.label) {
+                Some(existing) if existing.same_content(dataset) => continue,
+                Some(_) => match strategy {
+                    MergeStrategy::Skip => {
+                        skipped.push(dataset.label.clone());
+                        continue;
+                    }
+                    MergeStrategy::Overwrite => {
+                        project = project.replace_dataset(dataset.clone())?;
+                    }
+                    MergeStrategy::Error => {
+                        return Err(LabelError::MergeConflict(dataset.label.clone()).into());
+                    }
+                },
+                None => project = project.register(dataset.clone())?,
+            }
+            merged.push(dataset.label.clone());
+        }
+        Ok(Merge {
+            project,
+            merged,
+            skipped,
+        })
+    }
+
+    fn diff(
+        &self,
+        other: &Project,
+        metadata: &Metadata,
+        other_metadata: &Metadata,
+    ) -> RegistryDiff {
+        let mut diff = RegistryDiff::default();
+        for dataset in self.datasets() {
+            let Some(incoming) = other.find_dataset(&dataset.label) else {
+                diff.removed.push(dataset.label.clone());
+                continue;
+            };
+            let supplementary = metadata.supplementary(&dataset.label);
+            let other_supplementary = other_metadata.supplementary(&dataset.label);
+            let before_files = dataset.files_with(&supplementary);
+            let after_files = incoming.files_with(&other_supplementary);
+            let source = |files: &[(FileKind, &str)], kind| {
+                files
+                    .iter()
+                    .find(|(file_kind, _)| *file_kind == kind)
+                    .map(|(_, source)| (*source).to_string())
+            };
+            let changes: Vec<FieldChange> = FileKind::every()
+                .map(|kind| FieldChange {
+                    kind,
+                    before: source(&before_files, kind),
+                    after: source(&after_files, kind),
+                })
+                .filter(|change| change.before != change.after)
+                .collect();
+            if !changes.is_empty() {
+                diff.modified.push((dataset.label.clone(), changes));
+            }
+        }
+        diff.added = other
+            .datasets()
+            .iter()
+            .filter(|dataset| self.find_dataset(&dataset.label).is_none())
+            .map(|dataset| dataset.label.clone())
+            .collect();
+        diff
+    }
+
+    fn stats(&self, metadata: &Metadata) -> RegistryStats {
+        let mut stats = RegistryStats {
+            datasets: self.datasets().len(),
+            per_field: FileKind::every().map(|kind| (kind, 0)).collect(),
+            ..RegistryStats::default()
+        };
+        for dataset in self.datasets() {
+            let supplementary = metadata.supplementary(&dataset.label);
+            let files = dataset.files_with(&supplementary);
+            stats.total_files += files.len();
+            if files.is_empty() {
+                stats.empty += 1;
+            }
+            for (kind, count) in &mut stats.per_field {
+                if files.iter().any(|&(file_kind, _)| file_kind == *kind) {
+                    *count += 1;
+                }
+            }
+        }
+        stats
+    }
+
+    fn validate(&self, metadata: &Metadata) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+        let mut seen = HashSet::new();
+        for dataset in self.datasets() {
+            let label = dataset.label.as_str();
+            let mut report = |severity, message: String| {
+                issues.push(ValidationIssue {
+                    severity,
+                    label: label.to_string(),
+                    message,
+                });
+            };
+            if !seen.insert(label) {
+                report(Severity::Error, "is registered more than once".to_string());
+            }
+            if let Err(err) = dataset::validate_label(label) {
+                report(Severity::Error, err.to_string());
+            }
+
+            let meta = metadata.get(label);
+            let supplementary = meta.map(DatasetMeta::supplementary).unwrap_or_default();
+            let files = dataset.files_with(&supplementary);
+            if files.is_empty() {
+                report(Severity::Error, "has no files".to_string());
+            }
+            for &(kind, source) in &files {
+                match downloads::expand_vars(source) {
+                    Err(err) => report(Severity::Warning, err.to_string()),
+                    Ok(expanded) => {
+                        if let Some(problem) = malformed_source(&expanded) {
+                            let message = format!("{kind} source '{source}' {problem}");
+                            report(Severity::Error, message);
+                        }
+                    }
+                }
+            }
+
+            let has = |wanted: &[FileKind]| files.iter().any(|(kind, _)| wanted.contains(kind));
+            if has(&[FileKind::VcfIndex]) && !has(&[FileKind::Vcf]) {
+                report(Severity::Error, "has a VCF index but no VCF".to_string());
+            }
+            let sequences = [
+                FileKind::Fasta,
+                FileKind::Genbank,
+                FileKind::TwoBit,
+                FileKind::Nib,
+            ];
+            if has(&[FileKind::Gff, FileKind::Gtf, FileKind::Bed]) && !has(&sequences) {
+                report(
+                    Severity::Warning,
+                    "has annotations but no sequence to annotate".to_string(),
+                );
+            }
+
+            for alias in meta.map(|meta| meta.aliases.as_slice()).unwrap_or_default() {
+                if self.find_dataset(alias).is_some() {
+                    report(
+                        Severity::Error,
+                        format!("has the alias '{alias}', which is also a registered label"),
+                    );
+                }
+            }
+        }
+        issues
+    }
+
+    fn retain_tagged(self, metadata: &Metadata, tag: &str) -> Result<Project> {
+        retain_datasets(&self, |dataset| {
+            metadata
+                .get(&dataset.label)
+                .is_some_and(|meta| meta.has_tag(tag))
+        })
+    }
+}
+
+/// `project` with only the datasets `keep` accepts left in it. `refman` refuses
+/// to remove a registry's last dataset, so rather than removing the rest one by
+/// one, the datasets are replaced in the project's serialized form, which keeps
+/// its title and description as they were.
+fn retain_datasets(project: &Project, keep: impl Fn(&RefDataset) -> bool) -> Result<Project> {
+    let kept: Vec<&RefDataset> = project
+        .datasets()
+        .iter()
+        .filter(|dataset| keep(dataset))
+        .collect();
+    let mut serialized =
+        serde_json::to_value(project).context("Failed to serialize the registry.")?;
+    serialized["project"]["datasets"] =
+        serde_json::to_value(kept).context("Failed to serialize the registry.")?;
+    serde_json::from_value(serialized).context("Failed to rebuild the registry.")
+}
+
+/// The number of single-character insertions, deletions, and substitutions it
+/// takes to turn `a` into `b`.
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if a_char == *b_char {
+                diagonal
+            } else {
+                1 + diagonal.min(above).min(row[j])
+            };
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dataset(label: &str, fasta: &str, gff: Option<&str>) -> RefDataset {
+        RefDataset {
+            label: label.to_string(),
+            fasta: Some(fasta.to_string()),
+            gff: gff.map(str::to_string),
+            ..RefDataset::default()
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        let before = Project::new(None, None, false)
+            .register(dataset("e_coli", "e_coli.fasta", None))
+            .unwrap()
+            .register(dataset("yeast", "yeast.fasta", None))
+            .unwrap();
+        let after = Project::new(None, None, false)
+            .register(dataset("e_coli", "e_coli_v2.fasta", Some("e_coli.gff")))
+            .unwrap()
+            .register(dataset("human", "human.fasta", None))
+            .unwrap();
+
+        let none = Metadata::default();
+        let diff = before.diff(&after, &none, &none);
+        assert_eq!(diff.added, vec!["human".to_string()]);
+        assert_eq!(diff.removed, vec!["yeast".to_string()]);
+        assert_eq!(
+            diff.modified,
+            vec![(
+                "e_coli".to_string(),
+                vec![
+                    FieldChange {
+                        kind: FileKind::Fasta,
+                        before: Some("e_coli.fasta".to_string()),
+                        after: Some("e_coli_v2.fasta".to_string()),
+                    },
+                    FieldChange {
+                        kind: FileKind::Gff,
+                        before: None,
+                        after: Some("e_coli.gff".to_string()),
+                    },
+                ],
+            )]
+        );
+        assert!(after.diff(&after, &none, &none).is_empty());
+
+        let mut with_twobit = Metadata::default();
+        with_twobit.set_file("e_coli", FileKind::TwoBit, "e_coli.2bit".to_string());
+        let diff = after.diff(&after, &none, &with_twobit);
+        assert_eq!(diff.modified[0].1[0].kind, FileKind::TwoBit);
+    }
+
+    #[test]
+    fn test_clear() {
+        let project = Project::new(
+            Some("References".to_string()),
+            Some("Bacterial genomes".to_string()),
+            false,
+        )
+        .register(dataset("e_coli", "e_coli.fasta", None))
+        .unwrap()
+        .register(dataset("yeast", "yeast.fasta", None))
+        .unwrap();
+
+        let cleared = project.clear().unwrap();
+        assert!(cleared.labels().is_empty());
+        assert_eq!(
+            cleared.header_text("title").unwrap().as_deref(),
+            Some("References")
+        );
+        assert_eq!(
+            cleared.header_text("description").unwrap().as_deref(),
+            Some("Bacterial genomes")
+        );
+    }
+
+    #[test]
+    fn test_rename_dataset() {
+        let project = Project::new(None, None, false)
+            .register(dataset("e_coli", "e_coli.fasta", None))
+            .unwrap()
+            .rename_dataset("e_coli", "escherichia_coli")
+            .unwrap();
+        assert_eq!(project.labels(), vec!["escherichia_coli".to_string()]);
+        assert!(project.clone().rename_dataset("e_coli", "k12").is_err());
+    }
+
+    #[test]
+    fn test_replace_dataset() {
+        let project = Project::new(None, None, false)
+            .register(dataset("e_coli", "e_coli.fasta", None))
+            .unwrap()
+            .replace_dataset(dataset("e_coli", "e_coli_v2.fasta", None))
+            .unwrap();
+        assert_eq!(
+            project.datasets()[0].fasta.as_deref(),
+            Some("e_coli_v2.fasta")
+        );
+        assert!(project
+            .replace_dataset(dataset("yeast", "yeast.fasta", None))
+            .is_err());
+    }
+
+    #[test]
+    fn test_retain_tagged() {
+        let project = Project::new(None, None, false)
+            .register(dataset("e_coli", "e_coli.fasta", None))
+            .unwrap()
+            .register(dataset("yeast", "yeast.fasta", None))
+            .unwrap();
+        let mut metadata = Metadata::default();
+        metadata.set_tags("yeast", vec!["Fungi".to_string()]);
+
+        let fungi = project.clone().retain_tagged(&metadata, "fungi").unwrap();
+        assert_eq!(fungi.labels(), vec!["yeast".to_string()]);
+        let viral = project.retain_tagged(&metadata, "viral").unwrap();
+        assert!(viral.labels().is_empty());
+    }
+
+    #[test]
+    fn test_stats() {
+        let project = Project::new(None, None, false)
+            .register(dataset("e_coli", "e_coli.fasta", Some("e_coli.gff")))
+            .unwrap()
+            .register(dataset("yeast", "yeast.fasta", None))
+            .unwrap();
+
+        let mut metadata = Metadata::default();
+        metadata.set_file("yeast", FileKind::TwoBit, "yeast.2bit".to_string());
+        let stats = project.stats(&metadata);
+        assert_eq!(stats.datasets, 2);
+        assert_eq!(stats.total_files, 4);
+        assert!(stats.per_field.contains(&(FileKind::TwoBit, 1)));
+        assert_eq!(stats.empty, 0);
+        assert!(stats.per_field.contains(&(FileKind::Fasta, 2)));
+        assert!(stats.per_field.contains(&(FileKind::Gff, 1)));
+        assert!(stats.per_field.contains(&(FileKind::Bed, 0)));
+    }
+
+    #[test]
+    fn test_validate() {
+        let project = Project::new(None, None, false)
+            .register(dataset("hg38", "hg38.fa", Some("https://a b/a.gff")))
+            .unwrap()
+            .register(dataset("mirror", "${PY_REFMAN_UNSET}/hg38.fa", None))
+            .unwrap();
+
+        let issues = project.validate(&Metadata::default());
+        assert_eq!(issues.len(), 2, "{issues:?}");
+        assert_eq!(issues[0].severity, Severity::Error);
+        assert_eq!(issues[0].label, "hg38");
+        assert_eq!(issues[1].severity, Severity::Warning);
+        assert_eq!(issues[1].label, "mirror");
+    }
+
+    #[test]
+    fn test_find_labels() {
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("", "abc"), 3);
+
+        let project = Project::new(None, None, false)
+            .register(dataset("e_coli", "e_coli.fasta", None))
+            .unwrap()
+            .register(dataset("ecoli_k12", "ecoli_k12.fasta", None))
+            .unwrap()
+            .register(dataset("yeast", "yeast.fasta", None))
+            .unwrap();
+        assert_eq!(project.find_labels("EColi", 2), vec!["e_coli".to_string()]);
+        assert_eq!(
+            project.find_labels("ecoli_k1", 4),
+            vec!["ecoli_k12".to_string(), "e_coli".to_string()]
+        );
+        assert!(project.find_labels("human", 2).is_empty());
+        assert!(project
+            .not_registered("e_colli")
+            .to_string()
+            .ends_with("Did you mean 'e_coli'?"));
+    }
+}
@@ -0,0 +1,486 @@
+//! The registry submodule.
+//!
+//! `refman` reads and writes the `refman.toml` registry through
+//! `RegistryOptions`, but doesn't expose where that file lives. This module
+//! resolves the registry location the same way `refman` does so that the
+//! bindings can persist a project themselves with stronger guarantees than a
+//! plain overwrite: every write goes to a temporary file that then replaces
+//! the registry, so that a crash or Ctrl+C mid-write never leaves a truncated
+//! registry behind, and changes are made under an advisory lock, so that
+//! processes sharing a registry take turns rather than losing each other's
+//! updates.
+//!
+//! Registries are TOML by default, but a registry path ending in `.json`,
+//! `.yaml`, or `.yml` is read and written in that format instead. `refman` only
+//! speaks TOML, so those are (de)serialized here.
+//!
+//! Registries written here record the version of their layout as a top-level
+//! `schema_version`, which `refman` itself ignores. Registries from older
+//! versions, including those written by `refman` without a version, are read
+//! with any fields added since filled in with their defaults and are written
+//! back at the current version, while registries from newer versions are
+//! rejected rather than silently losing what this version doesn't understand.
+
+use std::{
+    env,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use fs2::FileExt;
+use refman::prelude::{Project, RegistryOptions};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::Config,
+    errors::{InitError, LockError, OptionsError, SchemaError},
+    metadata::Metadata,
+};
+
+pub const REGISTRY_FILE_NAME: &str = "refman.toml";
+
+/// The version of the registry layout this build reads and writes. Registries
+/// written before the version was recorded count as version 0.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// The version a registry was written with, read before the rest of it.
+#[derive(Deserialize)]
+struct SchemaVersion {
+    #[serde(default)]
+    schema_version: u32,
+}
+
+/// A registry as written to disk, stamped with the current version.
+#[derive(Serialize)]
+struct Versioned<'a> {
+    schema_version: u32,
+    #[serde(flatten)]
+    project: &'a Project,
+}
+
+/// How long to wait for another process to release a registry unless the
+/// config says otherwise.
+pub const DEFAULT_LOCK_TIMEOUT_SECS: u64 = 60;
+
+/// How often a held lock is checked again while waiting for it.
+const LOCK_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Reject a registry location that is specified twice. An explicit path and
+/// the global flag each say where the registry lives, so rather than silently
+/// letting one win, passing both is an error.
+pub fn check_location(requested_path: Option<&str>, global: bool) -> Result<(), OptionsError> {
+    match requested_path {
+        Some(requested) if global => Err(OptionsError::ConflictingOptions(format!(
+            "a registry path ('{requested}') and the global registry were both requested."
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Reject a title or description when reading a registry. Those only take
+/// effect when a registry is initialized, so accepting them on a read would
+/// suggest they had changed something when they hadn't.
+pub fn check_read_only(title: Option<&str>, description: Option<&str>) -> Result<(), OptionsError> {
+    if title.is_some() || description.is_some() {
+        return Err(OptionsError::ConflictingOptions(
+            "a title or description can only be set when initializing a registry, not when reading one."
+                .to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Resolve the path of the registry file: an explicitly requested path, the
+/// global registry under `$REFMAN_HOME` (or `~/.refman`), or otherwise
+/// `refman.toml` in the current working directory.
+pub fn resolve_path(requested_path: Option<&str>, global: bool) -> Result<PathBuf> {
+    check_location(requested_path, global)?;
+    if let Some(requested) = requested_path {
+        let requested = PathBuf::from(requested);
+        return Ok(if requested.is_dir() {
+            requested.join(REGISTRY_FILE_NAME)
+        } else {
+            requested
+        });
+    }
+
+    let dir = if global {
+        match env::var_os("REFMAN_HOME") {
+            Some(home) => PathBuf::from(home),
+            None => env::var_os("HOME")
+                .map(|home| PathBuf::from(home).join(".refman"))
+                .context("Unable to locate a home directory for the global registry.")?,
+        }
+    } else {
+        env::current_dir()?
+    };
+
+    Ok(dir.join(REGISTRY_FILE_NAME))
+}
+
+/// Fill in a registry location that wasn't specified from the user's config,
+/// so that an explicit path or global flag always wins over the config, which
+/// in turn wins over `refman.toml` in the working directory.
+pub fn locate(requested_path: Option<String>, global: bool) -> Result<(Option<String>, bool)> {
+    if requested_path.is_some() || global {
+        return Ok((requested_path, global));
+    }
+    let config = Config::load()?;
+    Ok((config.registry, config.global_project))
+}
+
+/// The serialization format of a registry file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RegistryFormat {
+    #[default]
+    Toml,
+    Json,
+    Yaml,
+}
+
+impl RegistryFormat {
+    /// The format of the registry at `path`, going by its extension, which is
+    /// TOML unless the extension says otherwise.
+    pub fn of(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("json") => RegistryFormat::Json,
+            Some("yaml" | "yml") => RegistryFormat::Yaml,
+            _ => RegistryFormat::Toml,
+        }
+    }
+
+    pub fn serialize(self, project: &Project) -> Result<String> {
+        let versioned = Versioned {
+            schema_version: SCHEMA_VERSION,
+            project,
+        };
+        Ok(match self {
+            RegistryFormat::Toml => toml::to_string_pretty(&versioned)
+                .context("Failed to serialize the registry to TOML.")?,
+            RegistryFormat::Json => serde_json::to_string_pretty(&versioned)
+                .context("Failed to serialize the registry to JSON.")?,
+            RegistryFormat::Yaml => serde_yaml::to_string(&versioned)
+                .context("Failed to serialize the registry to YAML.")?,
+        })
+    }
+
+    /// Parse a registry, refusing one written by a newer version of the
+    /// bindings. The fields added since an older version have defaults, so
+    /// migrating one is a matter of parsing it as the current version.
+    pub fn deserialize(self, contents: &str) -> Result<Project> {
+        let SchemaVersion { schema_version } = self.parse(contents)?;
+        if schema_version > SCHEMA_VERSION {
+            return Err(SchemaError::UnsupportedSchemaVersion {
+                found: schema_version,
+                supported: SCHEMA_VERSION,
+            }
+            .into());
+        }
+        self.parse(contents)
+    }
+
+    fn parse<T: serde::de::DeserializeOwned>(self, contents: &str) -> Result<T> {
+        Ok(match self {
+            RegistryFormat::Toml => {
+                toml::from_str(contents).context("Failed to parse the registry as TOML.")?
+            }
+            RegistryFormat::Json => {
+                serde_json::from_str(contents).context("Failed to parse the registry as JSON.")?
+            }
+            RegistryFormat::Yaml => {
+                serde_yaml::from_str(contents).context("Failed to parse the registry as YAML.")?
+            }
+        })
+    }
+}
+
+impl FromStr for RegistryFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "toml" => Ok(RegistryFormat::Toml),
+            "json" => Ok(RegistryFormat::Json),
+            "yaml" | "yml" => Ok(RegistryFormat::Yaml),
+            _ => Err(format!(
+                "Unsupported registry format '{s}'. Expected one of: toml, json, yaml."
+            )),
+        }
+    }
+}
+
+/// Create the registry at `path`, refusing to replace an existing one unless
+/// `force` is set, in which case its metadata sidecar goes along with it.
+/// `refman` always initializes an unversioned TOML registry, so it is rewritten
+/// right after at the current version and in the format the path names.
+pub fn init(options: &RegistryOptions, path: &Path, force: bool) -> Result<()> {
+    let _lock = lock(path)?;
+    if path.exists() {
+        if !force {
+            return Err(InitError::AlreadyInitialized(path.to_path_buf()).into());
+        }
+        let sidecar = Metadata::path_for(path);
+        if sidecar.exists() {
+            fs::remove_file(&sidecar)
+                .with_context(|| format!("Failed to remove '{}'.", sidecar.display()))?;
+        }
+    }
+    options.init()?;
+    let project = RegistryFormat::Toml.deserialize(&fs::read_to_string(path)?)?;
+    write_atomic(path, &project)
+}
+
+/// Read the registry at `path`, in whichever format its extension names,
+/// migrating it from an older version of the layout if need be.
+pub fn read(options: &RegistryOptions, path: &Path) -> Result<Project> {
+    let format = RegistryFormat::of(path);
+    // `refman` reads a missing or empty TOML registry as an empty project.
+    if format == RegistryFormat::Toml && !fs::metadata(path).is_ok_and(|meta| meta.len() > 0) {
+        return Ok(options.read_registry()?);
+    }
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read the registry '{}'.", path.display()))?;
+    format.deserialize(&contents)
+}
+
+/// The options for operating on an existing registry alongside the path of its
+/// file.
+pub fn open(requested_path: Option<String>, global: bool) -> Result<(RegistryOptions, PathBuf)> {
+    let (requested_path, global) = locate(requested_path, global)?;
+    let path = resolve_path(requested_path.as_deref(), global)?;
+    tracing::debug!(path = %path.display(), "opening registry");
+    let options = RegistryOptions::try_new(None, None, requested_path, global)?;
+    Ok((options, path))
+}
+
+/// Write `project` to `path`, in whichever format its extension names, such
+/// that the registry is never observed in a partially written state.
+pub fn write_atomic(path: &Path, project: &Project) -> Result<()> {
+    let contents = RegistryFormat::of(path).serialize(project)?;
+    write_file_atomic(path, &contents)
+}
+
+/// Write `contents` to `path` via a temporary file in the same directory, which
+/// is synced and then renamed over the target.
+pub fn write_file_atomic(path: &Path, contents: &str) -> Result<()> {
+    let file_name = path.file_name().map_or_else(
+        || REGISTRY_FILE_NAME.to_string(),
+        |name| name.to_string_lossy().to_string(),
+    );
+    let temp_path = path.with_file_name(format!(".{file_name}.{}.tmp", std::process::id()));
+
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create directory '{}'.", dir.display()))?;
+    }
+
+    let write_temp = || -> Result<()> {
+        let mut temp_file = File::create(&temp_path)?;
+        temp_file.write_all(contents.as_bytes())?;
+        temp_file.sync_all()?;
+        replace(&temp_path, path)?;
+        Ok(())
+    };
+
+    let result = write_temp();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    } else {
+        tracing::debug!(path = %path.display(), bytes = contents.len(), "wrote file atomically");
+    }
+    result.with_context(|| format!("Failed to write '{}'.", path.display()))
+}
+
+/// An advisory lock on a registry and its metadata sidecar, released when
+/// dropped. The lock is taken on a separate `.<registry>.lock` file beside the
+/// registry, since the registry itself is replaced rather than rewritten in
+/// place.
+#[derive(Debug)]
+pub struct RegistryLock {
+    _file: File,
+}
+
+/// Hold the registry at `path` for a read-modify-write, waiting for as long as
+/// the config allows if another process holds it.
+pub fn lock(path: &Path) -> Result<RegistryLock> {
+    let seconds = Config::load()?
+        .lock_timeout_secs
+        .unwrap_or(DEFAULT_LOCK_TIMEOUT_SECS);
+    lock_within(path, Duration::from_secs(seconds))
+}
+
+fn lock_within(path: &Path, timeout: Duration) -> Result<RegistryLock> {
+    let file_name = path.file_name().map_or_else(
+        || REGISTRY_FILE_NAME.to_string(),
+        |name| name.to_string_lossy().to_string(),
+    );
+    let lock_path = path.with_file_name(format!(".{file_name}.lock"));
+    if let Some(dir) = lock_path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create directory '{}'.", dir.display()))?;
+    }
+    let file = File::create(&lock_path)
+        .with_context(|| format!("Failed to open lock file '{}'.", lock_path.display()))?;
+
+    let started = Instant::now();
+    loop {
+        match file.try_lock_exclusive() {
+            Ok(()) => return Ok(RegistryLock { _file: file }),
+            Err(err) if err.kind() == fs2::lock_contended_error().kind() => {}
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("Failed to lock '{}'.", lock_path.display()))
+            }
+        }
+        if started.elapsed() >= timeout {
+            return Err(LockError::Locked {
+                path: path.to_path_buf(),
+                seconds: timeout.as_secs(),
+            }
+            .into());
+        }
+        tracing::debug!(path = %path.display(), "waiting for another process to release the registry");
+        thread::sleep(LOCK_POLL_INTERVAL);
+    }
+}
+
+/// How many times replacing a file is attempted on Windows.
+#[cfg(windows)]
+const REPLACE_ATTEMPTS: u32 = 5;
+
+/// Move `from` over `to`. `fs::rename` replaces an existing target on every
+/// platform, but on Windows it fails while another process, e.g. a virus
+/// scanner or an editor, briefly holds the target open, so it is retried there.
+fn replace(from: &Path, to: &Path) -> io::Result<()> {
+    #[cfg(windows)]
+    for attempt in 1..REPLACE_ATTEMPTS {
+        match fs::rename(from, to) {
+            Err(err) if err.kind() == io::ErrorKind::PermissionDenied => {
+                thread::sleep(Duration::from_millis(50 * u64::from(attempt)));
+            }
+            result => return result,
+        }
+    }
+    fs::rename(from, to)
+}
+
+#[cfg(test)]
+mod tests {
+    use refman::prelude::RefDataset;
+
+    use super::*;
+
+    #[test]
+    fn test_path_and_global_conflict() {
+        assert!(check_location(Some("refs/refman.toml"), true).is_err());
+        assert!(resolve_path(Some("refs/refman.toml"), true).is_err());
+        assert!(check_location(Some("refs/refman.toml"), false).is_ok());
+        assert!(check_location(None, true).is_ok());
+    }
+
+    #[test]
+    fn test_title_on_read_conflicts() {
+        assert!(check_read_only(Some("My References"), None).is_err());
+        assert!(check_read_only(None, Some("Genome references")).is_err());
+        assert!(check_read_only(None, None).is_ok());
+    }
+
+    #[test]
+    fn test_write_file_atomic_replaces() {
+        let dir = env::temp_dir().join("py-refman-atomic-write");
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("nested").join(REGISTRY_FILE_NAME);
+
+        write_file_atomic(&path, "title = \"first\"\n").unwrap();
+        write_file_atomic(&path, "title = \"second\"\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "title = \"second\"\n");
+        assert_eq!(fs::read_dir(path.parent().unwrap()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_registry_formats_round_trip() {
+        let format_of = |path: &str| RegistryFormat::of(Path::new(path));
+        assert_eq!(format_of("refs/refman.toml"), RegistryFormat::Toml);
+        assert_eq!(format_of("refs/refman.YML"), RegistryFormat::Yaml);
+        assert_eq!(format_of("refs/refman.json"), RegistryFormat::Json);
+        assert_eq!(format_of("refs"), RegistryFormat::Toml);
+        assert_eq!(RegistryFormat::from_str("YML"), Ok(RegistryFormat::Yaml));
+        assert!(RegistryFormat::from_str("xml").is_err());
+
+        let dataset = RefDataset {
+            label: "e_coli".to_string(),
+            fasta: Some("test.fasta".to_string()),
+            gff: Some("test.gff".to_string()),
+            ..RefDataset::default()
+        };
+        let project = Project::new(Some("References".to_string()), None, false)
+            .register(dataset)
+            .unwrap();
+        let toml = RegistryFormat::Toml.serialize(&project).unwrap();
+        for format in [RegistryFormat::Json, RegistryFormat::Yaml] {
+            let restored = format
+                .deserialize(&format.serialize(&project).unwrap())
+                .unwrap();
+            assert_eq!(RegistryFormat::Toml.serialize(&restored).unwrap(), toml);
+        }
+    }
+
+    #[test]
+    fn test_schema_version() {
+        let project = Project::new(Some("References".to_string()), None, false);
+        let contents = RegistryFormat::Toml.serialize(&project).unwrap();
+        assert!(contents.starts_with(&format!("schema_version = {SCHEMA_VERSION}")));
+
+        let unversioned = toml::to_string_pretty(&project).unwrap();
+        assert!(RegistryFormat::Toml.deserialize(&unversioned).is_ok());
+
+        let newer = contents.replace(
+            &format!("schema_version = {SCHEMA_VERSION}"),
+            &format!("schema_version = {}", SCHEMA_VERSION + 1),
+        );
+        let err = RegistryFormat::Toml.deserialize(&newer).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(SchemaError::UnsupportedSchemaVersion { found, .. }) if *found == SCHEMA_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn test_init_refuses_existing() {
+        let dir = env::temp_dir().join("py-refman-init-existing");
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join(REGISTRY_FILE_NAME);
+        write_file_atomic(&path, "title = \"existing\"\n").unwrap();
+
+        let options =
+            RegistryOptions::try_new(None, None, Some(path.to_string_lossy().to_string()), false)
+                .unwrap();
+        let err = init(&options, &path, false).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(InitError::AlreadyInitialized(_))
+        ));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "title = \"existing\"\n");
+    }
+
+    #[test]
+    fn test_lock_times_out() {
+        let dir = env::temp_dir().join("py-refman-lock");
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join(REGISTRY_FILE_NAME);
+
+        let held = lock_within(&path, Duration::ZERO).unwrap();
+        let err = lock_within(&path, Duration::from_millis(200)).unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(LockError::Locked { .. })));
+        drop(held);
+        assert!(lock_within(&path, Duration::ZERO).is_ok());
+    }
+}
@@ -0,0 +1,419 @@
+//! The resolvers submodule.
+//!
+//! Turns a provider-specific identifier, like `ncbi:NC_045512.2`,
+//! `ensembl:Homo_sapiens.GRCh38.112`, or `ucsc:hg38`, into the concrete file
+//! URLs a dataset should be registered with. Each provider implements `SourceResolver`, and `Resolvers` picks the
+//! one responsible for an identifier by the scheme before its first colon, so
+//! new providers can be added without touching the registration code and the
+//! selection logic can be tested offline.
+
+use std::{collections::BTreeMap, str::FromStr};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use refman::prelude::RefDataset;
+
+use crate::{
+    dataset::{DatasetSpec, FileKind},
+    errors::ResolveError,
+};
+
+/// The files a source identifier resolved to, along with a suggested label.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResolvedFiles {
+    pub label: String,
+    pub files: BTreeMap<FileKind, String>,
+
+    /// Names to download files under whose URLs don't end in a usable one, by
+    /// file kind.
+    pub names: BTreeMap<FileKind, String>,
+}
+
+#[async_trait]
+pub trait SourceResolver: Send + Sync {
+    /// The scheme identifiers for this provider are prefixed with, e.g. `ncbi`.
+    fn scheme(&self) -> &'static str;
+
+    /// Resolve the part of an identifier after the scheme into file URLs.
+    async fn resolve(&self, id: &str) -> Result<ResolvedFiles, ResolveError>;
+}
+
+/// Check that an identifier only holds the characters accessions and assembly
+/// names are made of, so it can be spliced into a URL as-is.
+fn check_id(id: &str) -> Result<(), ResolveError> {
+    let valid = !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'));
+    if valid {
+        Ok(())
+    } else {
+        Err(ResolveError::InvalidId {
+            id: id.to_string(),
+            reason: "expected only letters, digits, '_', '.', or '-'".to_string(),
+        })
+    }
+}
+
+/// The FTP directory of an NCBI assembly, given its accession followed by its
+/// assembly name, e.g. `GCF_000005845.2_ASM584v2`, which is what NCBI names
+/// the directory and its files after. The name can't be looked up without a
+/// request, so a bare accession is rejected.
+fn assembly_dir(id: &str) -> Result<String, ResolveError> {
+    let invalid = |reason: &str| ResolveError::InvalidId {
+        id: id.to_string(),
+        reason: reason.to_string(),
+    };
+    let (accession, _) = id
+        .split_once('.')
+        .and_then(|(accession, rest)| Some((accession, rest.split_once('_')?.1)))
+        .filter(|(_, name)| !name.is_empty())
+        .ok_or_else(|| invalid("expected the assembly name after the accession"))?;
+    let digits = &accession[4..];
+    if digits.len() != 9 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(invalid("expected nine digits after 'GCF_' or 'GCA_'"));
+    }
+    Ok(format!(
+        "https://ftp.ncbi.nlm.nih.gov/genomes/all/{}/{}/{}/{}/{id}",
+        &accession[..3],
+        &digits[..3],
+        &digits[3..6],
+        &digits[6..]
+    ))
+}
+
+/// Resolves NCBI nucleotide accessions, e.g. `MN908947.3`, to FASTA and
+/// `GenBank` records from E-utilities, and NCBI assemblies, e.g.
+/// `GCF_000005845.2_ASM584v2`, to the genome FASTA and annotations in their
+/// FTP directory.
+pub struct NcbiResolver;
+
+#[async_trait]
+impl SourceResolver for NcbiResolver {
+    fn scheme(&self) -> &'static str {
+        "ncbi"
+    }
+
+    async fn resolve(&self, id: &str) -> Result<ResolvedFiles, ResolveError> {
+        check_id(id)?;
+        if id.starts_with("GCF_") || id.starts_with("GCA_") {
+            let dir = assembly_dir(id)?;
+            return Ok(ResolvedFiles {
+                label: id.to_string(),
+                files: BTreeMap::from([
+                    (FileKind::Fasta, format!("{dir}/{id}_genomic.fna.gz")),
+                    (FileKind::Gff, format!("{dir}/{id}_genomic.gff.gz")),
+                    (FileKind::Gtf, format!("{dir}/{id}_genomic.gtf.gz")),
+                ]),
+                names: BTreeMap::new(),
+            });
+        }
+        let efetch = |rettype: &str| {
+            format!(
+                "https://eutils.ncbi.nlm.nih.gov/entrez/eutils/efetch.fcgi?db=nuccore&id={id}&rettype={rettype}&retmode=text"
+            )
+        };
+        Ok(ResolvedFiles {
+            label: id.to_string(),
+            files: BTreeMap::from([
+                (FileKind::Fasta, efetch("fasta")),
+                (FileKind::Genbank, efetch("gbwithparts")),
+            ]),
+            // every E-utilities URL ends in `efetch.fcgi`
+            names: BTreeMap::from([
+                (FileKind::Fasta, format!("{id}.fasta")),
+                (FileKind::Genbank, format!("{id}.gbk")),
+            ]),
+        })
+    }
+}
+
+/// Resolves UCSC assembly names, e.g. `hg38`, to the genome FASTA and `RefSeq`
+/// gene annotations hosted on the UCSC download server.
+pub struct UcscResolver;
+
+#[async_trait]
+impl SourceResolver for UcscResolver {
+    fn scheme(&self) -> &'static str {
+        "ucsc"
+    }
+
+    async fn resolve(&self, id: &str) -> Result<ResolvedFiles, ResolveError> {
+        check_id(id)?;
+        let base = format!("https://hgdownload.soe.ucsc.edu/goldenPath/{id}/bigZips");
+        Ok(ResolvedFiles {
+            label: id.to_string(),
+            files: BTreeMap::from([
+                (FileKind::Fasta, format!("{base}/{id}.fa.gz")),
+                (
+                    FileKind::Gtf,
+                    format!("{base}/genes/{id}.ncbiRefSeq.gtf.gz"),
+                ),
+            ]),
+            names: BTreeMap::new(),
+        })
+    }
+}
+
+/// Split an Ensembl genome name into its species, assembly, and release, e.g.
+/// `Homo_sapiens`, `GRCh38`, and `112`. Assembly names may themselves hold dots.
+/// The name is expected to have passed `check_id` already.
+fn ensembl_parts(id: &str) -> Option<(&str, &str, &str)> {
+    let (species, rest) = id.split_once('.')?;
+    let (assembly, release) = rest.rsplit_once('.')?;
+    let valid = species.contains('_')
+        && !species.contains('-')
+        && !assembly.is_empty()
+        && !release.is_empty()
+        && release.chars().all(|c| c.is_ascii_digit());
+    valid.then_some((species, assembly, release))
+}
+
+/// Resolves Ensembl genomes, named the way Ensembl names their files, i.e. the
+/// species, the assembly, and the release, e.g. `Homo_sapiens.GRCh38.112`, to
+/// the toplevel genome FASTA and the GFF3 and GTF annotations of that release.
+pub struct EnsemblResolver;
+
+#[async_trait]
+impl SourceResolver for EnsemblResolver {
+    fn scheme(&self) -> &'static str {
+        "ensembl"
+    }
+
+    async fn resolve(&self, id: &str) -> Result<ResolvedFiles, ResolveError> {
+        check_id(id)?;
+        let Some((species, assembly, release)) = ensembl_parts(id) else {
+            return Err(ResolveError::InvalidId {
+                id: id.to_string(),
+                reason: "expected '<Species>.<assembly>.<release>'".to_string(),
+            });
+        };
+        let dir = species.to_ascii_lowercase();
+        let base = format!("https://ftp.ensembl.org/pub/release-{release}");
+        Ok(ResolvedFiles {
+            label: id.to_string(),
+            files: BTreeMap::from([
+                (
+                    FileKind::Fasta,
+                    format!("{base}/fasta/{dir}/dna/{species}.{assembly}.dna.toplevel.fa.gz"),
+                ),
+                (FileKind::Gff, format!("{base}/gff3/{dir}/{id}.gff3.gz")),
+                (FileKind::Gtf, format!("{base}/gtf/{dir}/{id}.gtf.gz")),
+            ]),
+            names: BTreeMap::new(),
+        })
+    }
+}
+
+/// The providers an accession can be registered from with
+/// `Resolvers::dataset_for_accession`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessionSource {
+    Ncbi,
+    Ensembl,
+}
+
+impl AccessionSource {
+    /// The scheme of the resolver for this provider.
+    pub fn scheme(self) -> &'static str {
+        match self {
+            AccessionSource::Ncbi => "ncbi",
+            AccessionSource::Ensembl => "ensembl",
+        }
+    }
+}
+
+impl FromStr for AccessionSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "ncbi" => Ok(AccessionSource::Ncbi),
+            "ensembl" => Ok(AccessionSource::Ensembl),
+            _ => Err(format!(
+                "Unsupported accession source '{s}'. Expected one of: ncbi, ensembl."
+            )),
+        }
+    }
+}
+
+/// The set of resolvers identifiers are dispatched to.
+pub struct Resolvers {
+    resolvers: Vec<Box<dyn SourceResolver>>,
+}
+
+impl Default for Resolvers {
+    fn default() -> Self {
+        let mut resolvers = Self::empty();
+        resolvers.add(Box::new(NcbiResolver));
+        resolvers.add(Box::new(EnsemblResolver));
+        resolvers.add(Box::new(UcscResolver));
+        resolvers
+    }
+}
+
+impl Resolvers {
+    pub fn empty() -> Self {
+        Self {
+            resolvers: Vec::new(),
+        }
+    }
+
+    /// Add a resolver, taking precedence over any earlier one for its scheme.
+    pub fn add(&mut self, resolver: Box<dyn SourceResolver>) {
+        self.resolvers.insert(0, resolver);
+    }
+
+    /// Resolve an identifier of the form `<scheme>:<id>` with the resolver
+    /// registered for its scheme.
+    pub async fn resolve(&self, source: &str) -> Result<ResolvedFiles, ResolveError> {
+        let unknown = || {
+            let mut schemes: Vec<_> = self.resolvers.iter().map(|r| r.scheme()).collect();
+            schemes.sort_unstable();
+            schemes.dedup();
+            ResolveError::UnknownScheme {
+                id: source.to_string(),
+                known: schemes.join(", "),
+            }
+        };
+        let (scheme, id) = source.split_once(':').ok_or_else(unknown)?;
+        let resolver = self
+            .resolvers
+            .iter()
+            .find(|resolver| resolver.scheme().eq_ignore_ascii_case(scheme))
+            .ok_or_else(unknown)?;
+        resolver.resolve(id).await
+    }
+
+    /// A dataset labelled `label` with the files `source` publishes for
+    /// `accession`, along with the names its files are to be downloaded under.
+    /// The URLs follow from the accession alone, so an accession that isn't in
+    /// a form `source` uses is rejected without any request.
+    pub async fn dataset_for_accession(
+        &self,
+        label: String,
+        accession: &str,
+        source: AccessionSource,
+    ) -> Result<(RefDataset, BTreeMap<FileKind, String>)> {
+        let resolved = self
+            .resolve(&format!("{}:{accession}", source.scheme()))
+            .await?;
+        let dataset = DatasetSpec {
+            label,
+            files: resolved.files,
+        }
+        .build()
+        .await?;
+        Ok((dataset, resolved.names))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::*;
+    use crate::downloads;
+
+    struct MockResolver;
+
+    #[async_trait]
+    impl SourceResolver for MockResolver {
+        fn scheme(&self) -> &'static str {
+            "mock"
+        }
+
+        async fn resolve(&self, id: &str) -> Result<ResolvedFiles, ResolveError> {
+            Ok(ResolvedFiles {
+                label: format!("mock_{id}"),
+                files: BTreeMap::from([(FileKind::Fasta, format!("file:///refs/{id}.fa"))]),
+                names: BTreeMap::new(),
+            })
+        }
+    }
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn test_dispatch_by_scheme() {
+        let mut resolvers = Resolvers::default();
+        resolvers.add(Box::new(MockResolver));
+
+        let resolved = block_on(resolvers.resolve("mock:ecoli")).unwrap();
+        assert_eq!(resolved.label, "mock_ecoli");
+        assert_eq!(resolved.files[&FileKind::Fasta], "file:///refs/ecoli.fa");
+
+        let resolved = block_on(resolvers.resolve("UCSC:hg38")).unwrap();
+        assert!(resolved.files[&FileKind::Fasta].ends_with("/hg38/bigZips/hg38.fa.gz"));
+
+        assert!(matches!(
+            block_on(resolvers.resolve("gencode:human")),
+            Err(ResolveError::UnknownScheme { .. })
+        ));
+        assert!(matches!(
+            block_on(resolvers.resolve("NC_045512.2")),
+            Err(ResolveError::UnknownScheme { .. })
+        ));
+        assert!(matches!(
+            block_on(resolvers.resolve("ncbi:NC_045512.2&retmode=xml")),
+            Err(ResolveError::InvalidId { .. })
+        ));
+    }
+
+    #[test]
+    fn test_accessions() {
+        let resolvers = Resolvers::default();
+        let resolved = block_on(resolvers.resolve("ncbi:GCF_000005845.2_ASM584v2")).unwrap();
+        assert_eq!(
+            resolved.files[&FileKind::Gff],
+            "https://ftp.ncbi.nlm.nih.gov/genomes/all/GCF/000/005/845/GCF_000005845.2_ASM584v2/GCF_000005845.2_ASM584v2_genomic.gff.gz"
+        );
+        let resolved = block_on(resolvers.resolve("ensembl:Homo_sapiens.GRCh38.112")).unwrap();
+        assert_eq!(
+            resolved.files[&FileKind::Gtf],
+            "https://ftp.ensembl.org/pub/release-112/gtf/homo_sapiens/Homo_sapiens.GRCh38.112.gtf.gz"
+        );
+
+        for source in ["ncbi:GCF_000005845.2", "ensembl:GRCh38"] {
+            assert!(matches!(
+                block_on(resolvers.resolve(source)),
+                Err(ResolveError::InvalidId { .. })
+            ));
+        }
+        let dataset = block_on(resolvers.dataset_for_accession(
+            "e_coli".to_string(),
+            "GCF_000005845.2",
+            "NCBI".parse().unwrap(),
+        ));
+        assert!(matches!(
+            dataset.unwrap_err().downcast_ref(),
+            Some(ResolveError::InvalidId { .. })
+        ));
+    }
+
+    #[test]
+    fn test_nucleotide_file_names() {
+        let resolved = block_on(Resolvers::default().resolve("ncbi:MN908947.3")).unwrap();
+        let dataset = RefDataset {
+            label: resolved.label,
+            fasta: resolved.files.get(&FileKind::Fasta).cloned(),
+            genbank: resolved.files.get(&FileKind::Genbank).cloned(),
+            ..RefDataset::default()
+        };
+        let names = resolved.names.into_iter().collect();
+        let plan = downloads::plan_download(&dataset, &[], &names, Path::new("refs")).unwrap();
+        let targets: Vec<_> = plan.into_iter().map(|(_, target)| target).collect();
+        assert_eq!(
+            targets,
+            vec![
+                PathBuf::from("refs/MN908947.3.fasta"),
+                PathBuf::from("refs/MN908947.3.gbk"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,116 @@
+//! The S3 submodule.
+//!
+//! Transfers for `s3://bucket/key` sources, which are handed to the AWS CLI so
+//! that whatever credentials are configured in the environment, e.g. environment
+//! variables, a profile, or an instance role, are picked up the same way any
+//! other AWS tool would pick them up. The `aws` executable must be on PATH.
+
+use std::{path::Path, process::Stdio};
+
+use tokio::process::Command;
+
+use crate::errors::FetchError;
+
+/// Split an `s3://bucket/key` URL into its bucket and key.
+pub fn split(url: &str) -> Option<(&str, &str)> {
+    let (bucket, key) = url.strip_prefix("s3://")?.split_once('/')?;
+    (!bucket.is_empty() && !key.is_empty()).then_some((bucket, key))
+}
+
+fn invalid(url: &str) -> FetchError {
+    let bucket = url.trim_start_matches("s3://").trim_end_matches('/');
+    FetchError::S3 {
+        bucket: bucket.to_string(),
+        key: String::new(),
+        message: "the URL doesn't name an object, expected 's3://bucket/key'".to_string(),
+    }
+}
+
+/// Run the AWS CLI with `args`, returning what it printed.
+async fn run_aws(url: &str, bucket: &str, key: &str, args: &[&str]) -> Result<Vec<u8>, FetchError> {
+    let s3_error = |message: String| FetchError::S3 {
+        bucket: bucket.to_string(),
+        key: key.to_string(),
+        message,
+    };
+    let output = Command::new("aws")
+        .args(args)
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(|source| {
+            if source.kind() == std::io::ErrorKind::NotFound {
+                FetchError::AwsCliMissing {
+                    url: url.to_string(),
+                }
+            } else {
+                s3_error(source.to_string())
+            }
+        })?;
+    if !output.status.success() {
+        return Err(s3_error(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(output.stdout)
+}
+
+/// Confirm the object at `url` exists and return its size.
+pub async fn head(url: &str) -> Result<u64, FetchError> {
+    let (bucket, key) = split(url).ok_or_else(|| invalid(url))?;
+    let stdout = run_aws(
+        url,
+        bucket,
+        key,
+        &[
+            "s3api",
+            "head-object",
+            "--bucket",
+            bucket,
+            "--key",
+            key,
+            "--output",
+            "json",
+        ],
+    )
+    .await?;
+    serde_json::from_slice::<serde_json::Value>(&stdout)
+        .ok()
+        .and_then(|head| head.get("ContentLength")?.as_u64())
+        .ok_or_else(|| FetchError::S3 {
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+            message: "the object's size could not be read from `aws s3api head-object`".to_string(),
+        })
+}
+
+/// Copy the object at `url` to `target`.
+pub async fn fetch(url: &str, target: &Path) -> Result<(), FetchError> {
+    let (bucket, key) = split(url).ok_or_else(|| invalid(url))?;
+    let target = target.to_string_lossy();
+    run_aws(
+        url,
+        bucket,
+        key,
+        &["s3", "cp", "--only-show-errors", url, &target],
+    )
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split() {
+        assert_eq!(
+            split("s3://refs-bucket/genomes/GRCh38.fa.gz"),
+            Some(("refs-bucket", "genomes/GRCh38.fa.gz"))
+        );
+        assert_eq!(split("s3://refs-bucket/"), None);
+        assert_eq!(split("s3://refs-bucket"), None);
+        assert_eq!(split("https://refs-bucket/x.fa"), None);
+    }
+}
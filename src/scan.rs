@@ -0,0 +1,312 @@
+//! The scan submodule.
+//!
+//! Reconstructs a registry from reference files that are already on disk, for
+//! when the files survived but the `refman.toml` describing them didn't. Files
+//! are assigned a kind by their extension and grouped into datasets either by
+//! the subdirectory they live in or, for files directly inside the scanned
+//! directory, by their file stem.
+
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use glob::Pattern;
+use refman::prelude::Project;
+
+use crate::dataset::{self, DatasetSpec, FileKind};
+
+/// Extensions recognized for each kind of file, checked after any `.gz` suffix
+/// has been removed.
+const EXTENSIONS: [(&str, FileKind); 12] = [
+    ("fasta", FileKind::Fasta),
+    ("fa", FileKind::Fasta),
+    ("fna", FileKind::Fasta),
+    ("gbk", FileKind::Genbank),
+    ("gb", FileKind::Genbank),
+    ("genbank", FileKind::Genbank),
+    ("gfa", FileKind::Gfa),
+    ("gff", FileKind::Gff),
+    ("gff3", FileKind::Gff),
+    ("gtf", FileKind::Gtf),
+    ("bed", FileKind::Bed),
+    ("bedgraph", FileKind::Bed),
+];
+
+/// What a directory scan found besides the datasets it could reconstruct.
+#[derive(Debug, Default)]
+pub struct ScanReport {
+    pub scanned: usize,
+    pub included: usize,
+    pub labels: Vec<String>,
+    pub ambiguous: Vec<String>,
+    pub unrecognized: Vec<PathBuf>,
+}
+
+/// The mapping from file extensions to kinds used to classify scanned files.
+/// Lab-specific overrides take precedence over the built-in extensions, so that,
+/// for example, `.gff` files can be treated as GTF.
+#[derive(Debug, Clone, Default)]
+pub struct KindMap {
+    overrides: HashMap<String, FileKind>,
+}
+
+impl KindMap {
+    pub fn new(overrides: HashMap<String, FileKind>) -> Self {
+        let overrides = overrides
+            .into_iter()
+            .map(|(extension, kind)| {
+                let extension = extension.trim_start_matches('.').to_ascii_lowercase();
+                (extension, kind)
+            })
+            .collect();
+        Self { overrides }
+    }
+
+    fn kind_of(&self, extension: &str) -> Option<FileKind> {
+        let extension = extension.to_ascii_lowercase();
+        self.overrides.get(&extension).copied().or_else(|| {
+            EXTENSIONS
+                .iter()
+                .find(|(candidate, _)| *candidate == extension)
+                .map(|(_, kind)| *kind)
+        })
+    }
+
+    /// Split a file name into its stem and kind, e.g. `ecoli.fa.gz` into `ecoli`
+    /// and `FileKind::Fasta`.
+    fn classify<'a>(&self, file_name: &'a str) -> Option<(&'a str, FileKind)> {
+        let name = file_name.strip_suffix(".gz").unwrap_or(file_name);
+        let (stem, extension) = name.rsplit_once('.')?;
+        self.kind_of(extension).map(|kind| (stem, kind))
+    }
+}
+
+/// Group the recognized files in `files` into datasets keyed by label, where
+/// `label_for` decides which dataset a file belongs to. A group holding two
+/// files of the same kind is ambiguous and is reported instead of guessed at.
+fn group(
+    files: &[PathBuf],
+    kind_map: &KindMap,
+    label_for: impl Fn(&str) -> String,
+    report: &mut ScanReport,
+) -> Vec<DatasetSpec> {
+    let mut groups: BTreeMap<String, DatasetSpec> = BTreeMap::new();
+    let mut conflicted = Vec::new();
+    for file in files {
+        let name = file
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let Some((stem, kind)) = kind_map.classify(&name) else {
+            report.unrecognized.push(file.clone());
+            continue;
+        };
+        let label = label_for(stem);
+        let spec = groups.entry(label.clone()).or_insert_with(|| DatasetSpec {
+            label: label.clone(),
+            ..DatasetSpec::default()
+        });
+        if let Some(existing) = spec.files.insert(kind, file.to_string_lossy().to_string()) {
+            report.ambiguous.push(format!(
+                "Dataset '{label}' has more than one {kind} file: '{existing}' and '{}'.",
+                file.display()
+            ));
+            conflicted.push(label);
+        }
+    }
+    groups
+        .into_values()
+        .filter(|spec| !conflicted.contains(&spec.label))
+        .collect()
+}
+
+/// Settings bounding and filtering a directory scan.
+#[derive(Debug, Clone, Default)]
+pub struct ScanOptions {
+    pub recursive: bool,
+
+    /// How many levels of subdirectories to descend into, where `Some(0)` only
+    /// scans the top-level directory. Unbounded when `None`.
+    pub max_depth: Option<usize>,
+
+    /// When non-empty, only files whose name or relative path matches one of
+    /// these patterns are considered.
+    pub include: Vec<Pattern>,
+
+    /// Files whose name or relative path matches one of these patterns are
+    /// ignored, e.g. `*.tmp` or `logs/*`.
+    pub exclude: Vec<Pattern>,
+
+    /// Whether to descend into symlinked directories. Off by default to avoid
+    /// cycles.
+    pub follow_symlinks: bool,
+
+    pub kind_map: KindMap,
+}
+
+impl ScanOptions {
+    /// Compile include and exclude glob patterns.
+    pub fn patterns(globs: &[String]) -> Result<Vec<Pattern>> {
+        globs
+            .iter()
+            .map(|glob| {
+                Pattern::new(glob).with_context(|| format!("Invalid glob pattern '{glob}'."))
+            })
+            .collect()
+    }
+
+    fn admits(&self, file: &Path, root: &Path) -> bool {
+        let relative = file.strip_prefix(root).unwrap_or(file);
+        let name = file.file_name().map_or(relative, Path::new);
+        let matches =
+            |pattern: &Pattern| pattern.matches_path(name) || pattern.matches_path(relative);
+        (self.include.is_empty() || self.include.iter().any(matches))
+            && !self.exclude.iter().any(matches)
+    }
+}
+
+/// Collect the files directly inside `dir` and its subdirectories.
+fn list_dir(dir: &Path, follow_symlinks: bool) -> Result<(Vec<PathBuf>, Vec<PathBuf>)> {
+    let mut files = Vec::new();
+    let mut subdirs = Vec::new();
+    let listing = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read directory '{}'.", dir.display()))?;
+    for entry in listing {
+        let entry = entry?;
+        let path = entry.path();
+        if path.is_dir() {
+            if follow_symlinks || !entry.file_type()?.is_symlink() {
+                subdirs.push(path);
+            }
+        } else {
+            files.push(path);
+        }
+    }
+    files.sort();
+    subdirs.sort();
+    Ok((files, subdirs))
+}
+
+/// Work out the datasets the reference files under `dir` make up. Files directly
+/// in `dir` are grouped by stem; when scanning recursively, the files in each
+/// subdirectory form one dataset labeled by the subdirectory's relative path.
+pub fn scan(dir: &Path, options: &ScanOptions) -> Result<(Vec<DatasetSpec>, ScanReport)> {
+    let dir = dir
+        .canonicalize()
+        .with_context(|| format!("Unable to resolve directory '{}'.", dir.display()))?;
+    let mut report = ScanReport::default();
+    let mut specs = Vec::new();
+    let mut pending = VecDeque::from([(dir.clone(), 0)]);
+
+    while let Some((current, depth)) = pending.pop_front() {
+        let (files, subdirs) = list_dir(&current, options.follow_symlinks)?;
+        let descend = options.recursive && options.max_depth.is_none_or(|max| depth < max);
+        if descend {
+            pending.extend(subdirs.into_iter().map(|subdir| (subdir, depth + 1)));
+        }
+
+        report.scanned += files.len();
+        let files: Vec<_> = files
+            .into_iter()
+            .filter(|file| options.admits(file, &dir))
+            .collect();
+        report.included += files.len();
+
+        if current == dir {
+            specs.extend(group(
+                &files,
+                &options.kind_map,
+                dataset::normalize_label,
+                &mut report,
+            ));
+        } else {
+            let label = current
+                .strip_prefix(&dir)
+                .unwrap_or(&current)
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("_");
+            let label = dataset::normalize_label(&label);
+            specs.extend(group(
+                &files,
+                &options.kind_map,
+                |_| label.clone(),
+                &mut report,
+            ));
+        }
+    }
+
+    report.labels = specs.iter().map(|spec| spec.label.clone()).collect();
+    Ok((specs, report))
+}
+
+/// Reconstruct a `Project` from the reference files already downloaded under
+/// `dir`, along with a report of what couldn't be placed in a dataset.
+pub async fn from_existing_downloads(
+    dir: &Path,
+    options: &ScanOptions,
+) -> Result<(Project, ScanReport)> {
+    let (specs, report) = scan(dir, options)?;
+    let mut project = Project::new(None, None, false);
+    for spec in specs {
+        project = project.register(spec.build().await?)?;
+    }
+    Ok((project, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let kind_map = KindMap::default();
+        assert_eq!(
+            kind_map.classify("ecoli.fa.gz"),
+            Some(("ecoli", FileKind::Fasta))
+        );
+        assert_eq!(
+            kind_map.classify("MN908947.3.gbk"),
+            Some(("MN908947.3", FileKind::Genbank))
+        );
+        assert_eq!(
+            kind_map.classify("annotations.GFF3"),
+            Some(("annotations", FileKind::Gff))
+        );
+        assert_eq!(kind_map.classify("notes.txt"), None);
+        assert_eq!(kind_map.classify("README"), None);
+    }
+
+    #[test]
+    fn test_kind_map_overrides() {
+        let kind_map = KindMap::new(HashMap::from([
+            (".gff".to_string(), FileKind::Gtf),
+            ("fasta2".to_string(), FileKind::Fasta),
+        ]));
+        assert_eq!(
+            kind_map.classify("genes.gff"),
+            Some(("genes", FileKind::Gtf))
+        );
+        assert_eq!(
+            kind_map.classify("genes.gff3"),
+            Some(("genes", FileKind::Gff))
+        );
+        assert_eq!(kind_map.classify("x.fasta2"), Some(("x", FileKind::Fasta)));
+    }
+
+    #[test]
+    fn test_scan_filters() {
+        let options = ScanOptions {
+            exclude: ScanOptions::patterns(&["*.tmp".to_string(), "logs/*".to_string()]).unwrap(),
+            ..ScanOptions::default()
+        };
+        let root = Path::new("/refs");
+        assert!(options.admits(Path::new("/refs/ecoli.fa"), root));
+        assert!(!options.admits(Path::new("/refs/ecoli.fa.tmp"), root));
+        assert!(!options.admits(Path::new("/refs/logs/run.bed"), root));
+    }
+}
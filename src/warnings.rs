@@ -0,0 +1,70 @@
+//! The warnings submodule.
+//!
+//! Some operations hit problems that shouldn't abort them, like a skipped file
+//! or an unreachable source in a bulk registration. Rather than only printing
+//! these, operations collect them as structured `Warning`s that are handed back
+//! to Python alongside the result, so that programmatic callers can inspect and
+//! act on them. In strict mode, the first warning is raised as an error instead.
+
+use anyhow::{bail, Result};
+use pyo3::prelude::*;
+
+#[pyclass]
+#[pyo3(name = "RefmanWarning")]
+#[derive(Debug, Clone)]
+pub struct Warning {
+    /// A short, stable, machine-readable identifier, e.g. `unavailable-source`.
+    #[pyo3(get)]
+    pub code: String,
+
+    /// A human-readable description of what happened.
+    #[pyo3(get)]
+    pub message: String,
+}
+
+#[pymethods]
+impl Warning {
+    fn __repr__(&self) -> String {
+        format!(
+            "RefmanWarning(code='{}', message='{}')",
+            self.code, self.message
+        )
+    }
+
+    fn __str__(&self) -> String {
+        format!("[{}] {}", self.code, self.message)
+    }
+}
+
+/// Collects the warnings raised over the course of a single operation.
+#[derive(Debug, Default)]
+pub struct Warnings {
+    strict: bool,
+    collected: Vec<Warning>,
+}
+
+impl Warnings {
+    pub fn new(strict: bool) -> Self {
+        Self {
+            strict,
+            collected: Vec::new(),
+        }
+    }
+
+    /// Record a warning, or fail with it in strict mode.
+    pub fn push(&mut self, code: &str, message: impl Into<String>) -> Result<()> {
+        let message = message.into();
+        if self.strict {
+            bail!("[{code}] {message}");
+        }
+        self.collected.push(Warning {
+            code: code.to_string(),
+            message,
+        });
+        Ok(())
+    }
+
+    pub fn into_vec(self) -> Vec<Warning> {
+        self.collected
+    }
+}
@@ -0,0 +1,78 @@
+//! The writers submodule.
+//!
+//! Lets Python callers send downloaded files somewhere other than the local
+//! filesystem, like an object store behind `fsspec`. A download destination is
+//! either a local directory or a writer factory: a callable that takes a file
+//! name and returns a writable binary file-like object. Files for a writer
+//! factory are staged in a temporary directory, streamed through the objects it
+//! returns, and then removed.
+
+use std::{
+    env,
+    fs::{self, File},
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use pyo3::{exceptions::PyValueError, prelude::*, types::PyBytes};
+
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Where a download's files end up.
+pub enum Destination<'py> {
+    Local(PathBuf),
+    Writer(&'py PyAny),
+}
+
+impl<'py> Destination<'py> {
+    /// Interpret the `dest` argument of a Python download call, where `None`
+    /// means the current working directory.
+    pub fn from_py(dest: Option<&'py PyAny>) -> PyResult<Self> {
+        let Some(dest) = dest else {
+            return Ok(Destination::Local(env::current_dir()?));
+        };
+        if let Ok(path) = dest.extract::<PathBuf>() {
+            return Ok(Destination::Local(path));
+        }
+        if dest.is_callable() {
+            return Ok(Destination::Writer(dest));
+        }
+        Err(PyValueError::new_err(
+            "`dest` must be a path or a callable returning a writable binary file object for a file name.",
+        ))
+    }
+}
+
+/// A scratch directory for staging files bound for a writer factory.
+pub fn staging_dir(label: &str) -> PathBuf {
+    env::temp_dir().join(format!("refman-{label}-{}", std::process::id()))
+}
+
+/// Stream each of `files` through a file object obtained from `factory`,
+/// called with the file's name.
+pub fn write_through(py: Python<'_>, factory: &PyAny, files: &[PathBuf]) -> PyResult<()> {
+    let mut buffer = vec![0; CHUNK_SIZE];
+    for path in files {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let writer = factory.call1((name,))?;
+        let mut file = File::open(path)?;
+        loop {
+            let read = file.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            writer.call_method1("write", (PyBytes::new(py, &buffer[..read]),))?;
+        }
+        writer.call_method0("close")?;
+    }
+    Ok(())
+}
+
+/// Remove a staging directory, ignoring failures since it lives under the
+/// system's temporary directory anyway.
+pub fn clean_up(staging: &Path) {
+    let _ = fs::remove_dir_all(staging);
+}
@@ -4,7 +4,7 @@ import refman
 
 
 def test_workflow_integration() -> None:
-    refman.init("integration-test", "Temporary configuration to be used for integration testing", global_project=False)
+    refman.init("integration-test", "Temporary configuration to be used for integration testing", global_project=False, force=True)
     refman.register(
         "test1",
         fasta="https://dholk.primate.wisc.edu/_webdav/dho/public/DHO%20Lab%20Bespoke%20Reference%20Dataset%20Registry/Pathogen%20Genomics/%40files/sars-cov-2/MN908947.3.fasta",